@@ -54,7 +54,8 @@ pub fn char_enum(_input: TokenStream, annotated_item: TokenStream) -> TokenStrea
                 )*
                 #id,
             });
-            let char_to_ident = data.iter().map(|(_, id, literal)| quote!{#literal => #ident::#id});
+            let char_to_ident = data.iter().map(|(_, id, literal)| quote!{#literal => Some(#ident::#id)});
+            let literals = data.iter().map(|(_, _, literal)| literal);
             let ident_to_char = data.iter().map(|(_, id, literal)| quote!{#ident::#id => #literal});
             let has_encode_decode = Ident::new(&(ident.to_string() + "__HasEncodeDecode__"), ident.span());
 
@@ -70,18 +71,32 @@ pub fn char_enum(_input: TokenStream, annotated_item: TokenStream) -> TokenStrea
                 #[allow(non_camel_case_types)]
                 #vis trait #has_encode_decode {
                     fn decode(chr: char) -> #ident;
+                    fn try_decode(chr: char) -> Option<#ident>;
                     fn encode(&self) -> char;
+                    /// every character that [Self::try_decode] accepts
+                    fn encodings() -> &'static [char];
                 }
 
                 #[automatically_derived]
                 impl #has_encode_decode for #ident {
                     fn decode(chr: char) -> #ident {
+                        match Self::try_decode(chr) {
+                            Some(v) => v,
+                            None => panic!("Unknown character `{}`", chr)
+                        }
+                    }
+
+                    fn try_decode(chr: char) -> Option<#ident> {
                         match chr {
                             #( #char_to_ident, )*
-                            _ => panic!("Unknown character `{}`", chr)
+                            _ => None
                         }
                     }
 
+                    fn encodings() -> &'static [char] {
+                        &[#( #literals, )*]
+                    }
+
                     fn encode(&self) -> char {
                         match self {
                             #( #ident_to_char, )*
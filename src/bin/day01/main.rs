@@ -1,29 +1,32 @@
 use std::fs;
 
+use utils::parse::{ParseResult, ParseResultExt, Span};
+
 fn main() {
     println!("AOC 2023 Day 1");
     let contents = fs::read_to_string("src/bin/day01/input.txt").expect("Failed to read input.txt");
     let mut total1 = 0;
     let mut total2 = 0;
-    for line in contents.split("\n") {
+    for line in Span::new(&contents).lines() {
         if !line.is_empty() {
-            total1 += extract1(line);
-            total2 += extract2(line);
+            total1 += extract1(line).expect_in("src/bin/day01/input.txt");
+            total2 += extract2(line).expect_in("src/bin/day01/input.txt");
         }
     }
     println!("Sum of calibration values (part 1): {}", total1);
     println!("Sum of calibration values (part 2): {}", total2);
 }
 
-fn extract1(line: &str) -> u32 {
+fn extract1(line: Span) -> ParseResult<u32> {
     return extract(line, false);
 }
 
-fn extract2(line: &str) -> u32 {
+fn extract2(line: Span) -> ParseResult<u32> {
     return extract(line, true);
 }
 
-fn extract(line: &str, include_words: bool) -> u32 {
+fn extract(span: Span, include_words: bool) -> ParseResult<u32> {
+    let line = span.as_str();
     let mut first: i32 = -1;
     let mut last: i32 = -1;
     let mut chars = line.chars();
@@ -62,12 +65,12 @@ fn extract(line: &str, include_words: bool) -> u32 {
         }
     }
     if first == -1 {
-        panic!("No digits found on line \"{}\"", line);
+        return Err(span.error("at least one digit"));
     }
     if last == -1 {
         last = first;
     };
-    return ((first * 10) + last) as u32;
+    return Ok(((first * 10) + last) as u32);
 }
 
 fn extract_word_num(txt: &str) -> Option<u32> {
@@ -94,10 +97,16 @@ fn extract_word_num(txt: &str) -> Option<u32> {
 
 #[test]
 fn extraction_part1() {
-    assert_eq!(12, extract1("1abc2"));
-    assert_eq!(38, extract1("pqr3stu8vwx"));
-    assert_eq!(15, extract1("a1b2c3d4e5f"));
-    assert_eq!(77, extract1("treb7uchet"));
+    assert_eq!(12, extract1("1abc2".into()).unwrap());
+    assert_eq!(38, extract1("pqr3stu8vwx".into()).unwrap());
+    assert_eq!(15, extract1("a1b2c3d4e5f".into()).unwrap());
+    assert_eq!(77, extract1("treb7uchet".into()).unwrap());
+}
+
+#[test]
+fn extraction_failure() {
+    let err = extract1(Span::new("abc\ndef").lines().nth(1).unwrap()).unwrap_err();
+    assert_eq!((2, 1), (err.line, err.column));
 }
 
 #[test]
@@ -108,12 +117,12 @@ fn extracts_words() {
 
 #[test]
 fn extraction_part2() {
-    assert_eq!(12, extract2("1abc2"));
-    assert_eq!(38, extract2("pqr3stu8vwx"));
-    assert_eq!(15, extract2("a1b2c3d4e5f"));
-    assert_eq!(77, extract2("treb7uchet"));
+    assert_eq!(12, extract2("1abc2".into()).unwrap());
+    assert_eq!(38, extract2("pqr3stu8vwx".into()).unwrap());
+    assert_eq!(15, extract2("a1b2c3d4e5f".into()).unwrap());
+    assert_eq!(77, extract2("treb7uchet".into()).unwrap());
 
-    assert_eq!(29, extract2("two1nine"));
-    assert_eq!(83, extract2("eighttwothree"));
-    assert_eq!(14, extract2("zoneight234"));
+    assert_eq!(29, extract2("two1nine".into()).unwrap());
+    assert_eq!(83, extract2("eighttwothree".into()).unwrap());
+    assert_eq!(14, extract2("zoneight234".into()).unwrap());
 }
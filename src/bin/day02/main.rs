@@ -1,15 +1,17 @@
 use std::fs;
 
+use utils::parse::{ParseResult, ParseResultExt, Span};
+
 fn main() {
     println!("AOC 2023 Day 2");
     let contents = fs::read_to_string("src/bin/day02/input.txt").expect("Failed to read input.txt");
     let mut total: u32 = 0;
     let mut total_power: u32 = 0;
-    for line in contents.split("\n") {
+    for line in Span::new(&contents).lines() {
         if line.is_empty() {
             continue;
         }
-        let record = parse(line);
+        let record = parse(line).expect_in("src/bin/day02/input.txt");
         if possible_record(&record, 12, 13, 14) {
             total += record.id;
         }
@@ -19,6 +21,7 @@ fn main() {
     println!("Total power: {}", total_power);
 }
 
+#[derive(Debug)]
 struct GameRecord {
     id: u32,
     max_red: u32,
@@ -33,9 +36,9 @@ impl GameRecord {
 }
 
 // `Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green`
-fn parse(line: &str) -> GameRecord {
-    let id_records = line.split_once(": ").unwrap();
-    let game_id = id_records.0.strip_prefix("Game ").unwrap().parse::<u32>().unwrap();
+fn parse<'a>(line: impl Into<Span<'a>>) -> ParseResult<GameRecord> {
+    let id_records = line.into().split_once(": ")?;
+    let game_id = id_records.0.strip_prefix("Game ")?.parse::<u32>()?;
 
     let groups = id_records.1.split("; ");
     let mut max_red: u32 = 0;
@@ -45,9 +48,9 @@ fn parse(line: &str) -> GameRecord {
     for group in groups {
         let entries = group.split(", ");
         for entry in entries {
-            let (count_str, color) = entry.split_once(" ").unwrap();
-            let count = count_str.parse::<u32>().unwrap();
-            match color {
+            let (count_str, color) = entry.split_once(" ")?;
+            let count = count_str.parse::<u32>()?;
+            match color.as_str() {
                 "red" => {
                     if count > max_red {
                         max_red = count;
@@ -63,16 +66,16 @@ fn parse(line: &str) -> GameRecord {
                         max_blue = count;
                     }
                 },
-                c => panic!("Unknown color {}", c)
+                _ => return Err(color.error("`red`, `green` or `blue`"))
             }
         }
     }
-    return GameRecord { id: game_id, max_red, max_green, max_blue };
+    return Ok(GameRecord { id: game_id, max_red, max_green, max_blue });
 }
 
 #[allow(dead_code)]
 fn possible(line: &str, avail_red: u32, avail_green: u32, avail_blue: u32) -> bool {
-    return possible_record(&parse(line), avail_red, avail_green, avail_blue);
+    return possible_record(&parse(line).unwrap(), avail_red, avail_green, avail_blue);
 }
 
 fn possible_record(record: &GameRecord, avail_red: u32, avail_green: u32, avail_blue: u32) -> bool {
@@ -81,12 +84,15 @@ fn possible_record(record: &GameRecord, avail_red: u32, avail_green: u32, avail_
 
 #[test]
 fn parsing() {
-    let g = parse("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green");
+    let g = parse("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green").unwrap();
     assert_eq!(g.id, 1);
     assert_eq!(g.max_red, 4);
     assert_eq!(g.max_green, 2);
     assert_eq!(g.max_blue, 6);
     assert_eq!(g.power(), 48);
+
+    let err = parse("Game 1: 3 blue, 4 rde").unwrap_err();
+    assert_eq!((1, 19), (err.line, err.column));
 }
 
 #[test]
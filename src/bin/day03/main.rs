@@ -1,8 +1,10 @@
 use std::fs;
 
+use utils::parse::{ParseResult, ParseResultExt, Span};
+
 fn main() {
     println!("AOC 2023 Day 3");
-    let schem: &mut Schematic = &mut load_schematic("src/bin/day03/input.txt");
+    let schem: &mut Schematic = &mut load_schematic("src/bin/day03/input.txt").expect_in("src/bin/day03/input.txt");
     println!("Part 1 sum: {}", schem.process());
     println!("Part 2 sum: {}", schem.count_gears());
 }
@@ -55,21 +57,27 @@ struct Schematic {
     result: Option<u32>,
 }
 
-fn load_schematic(fname: &str) -> Schematic {
+fn load_schematic(fname: &str) -> ParseResult<Schematic> {
     let contents = fs::read_to_string(fname).expect("Failed to load file");
     return Schematic::create(&contents);
 }
 
 impl Schematic {
-    fn create(data: &str) -> Schematic {
-        let lines: Vec<&str> = data.split("\n").filter(|x| !x.is_empty()).collect();
+    fn create(data: &str) -> ParseResult<Schematic> {
+        let lines: Vec<Span> = Span::new(data).lines().filter(|x| !x.is_empty()).collect();
         let height = lines.len();
-        let width = lines[0].chars().count();
+        let width = match lines.first() {
+            Some(line) => line.as_str().chars().count(),
+            None => return Err(Span::new(data).error("at least one row"))
+        };
         let mut data: Vec<Vec<Parts>> = vec![];
         let mut symbols: Vec<Coord> = vec![];
         for row in 0..height {
             let mut row_data: Vec<Parts> = vec![];
-            let mut line = lines[row].chars();
+            if lines[row].as_str().chars().count() != width {
+                return Err(lines[row].error(format!("a row of {} characters", width)));
+            }
+            let mut line = lines[row].as_str().chars();
             let mut building_number: Option<Coord> = None;
             for column in 0..width {
                 let chr = line.next().unwrap();
@@ -105,7 +113,7 @@ impl Schematic {
             }
             data.push(row_data);
         }
-        return Schematic {width, height, data, symbols, result: None };
+        return Ok(Schematic {width, height, data, symbols, result: None });
     }
 
     fn process(&mut self) -> u32 {
@@ -189,7 +197,7 @@ impl Schematic {
 
 #[test]
 fn process_works() {
-    let s: &mut Schematic = &mut load_schematic("src/bin/day03/test.txt");
+    let s: &mut Schematic = &mut load_schematic("src/bin/day03/test.txt").unwrap();
     s.process();
     s.debug_print();
     assert_eq!(4361, s.process());
@@ -1,10 +1,12 @@
 use std::fs;
 
+use utils::parse::{ParseResult, ParseResultExt, Span};
+
 fn main() {
     println!("AOC 2023 Day 4");
 
     let contents = fs::read_to_string("src/bin/day04/input.txt").expect("Failed to read file");
-    let mut pile: CardPile = CardPile::load(&contents);
+    let mut pile: CardPile = CardPile::load(&contents).expect_in("src/bin/day04/input.txt");
     let total: u32 = pile.calculate_total_points();
     println!("Part 1: {}", total);
     let copies: u32 = pile.calculate_copies();
@@ -17,16 +19,20 @@ struct CardPile {
 }
 
 impl CardPile {
-    fn load(txt: &str) -> CardPile {
+    fn load(txt: &str) -> ParseResult<CardPile> {
         let mut pile = CardPile::new();
-        let lines = txt.split("\n");
+        let lines = Span::new(txt).lines();
         for line in lines {
             if line.is_empty() {
                 continue;
             }
-            pile.push(Card::parse(line));
+            let card = Card::parse(line)?;
+            if card.id != pile.cards.len() as u32 + 1 {
+                return Err(line.error(format!("card {}", pile.cards.len() + 1)));
+            }
+            pile.push(card);
         }
-        return pile;
+        return Ok(pile);
     }
 
     fn new() -> CardPile {
@@ -90,29 +96,25 @@ struct Card {
     matches: u32
 }
 
-fn nums_to_vec(nums: &str) -> Vec<u32> {
-    return nums.split(" ")
-        .filter(|t| !t.is_empty())
-        .map(|t| t.parse::<u32>().unwrap())
-        .collect();
+fn nums_to_vec(nums: Span) -> ParseResult<Vec<u32>> {
+    return nums.numbers(" ");
 }
 
 impl Card {
-    fn parse(text: &str) -> Card {
-        let (card_id, numbers) = text.trim().split_once(": ").unwrap();
-        let id: u32 = card_id.strip_prefix("Card ").unwrap().trim().parse::<u32>()
-            .expect(&("Faild to parse id from ".to_owned() + card_id));
-        let (winning, have) = numbers.split_once(" | ").unwrap();
+    fn parse<'a>(text: impl Into<Span<'a>>) -> ParseResult<Card> {
+        let (card_id, numbers) = text.into().trim().split_once(": ")?;
+        let id: u32 = card_id.strip_prefix("Card ")?.parse::<u32>()?;
+        let (winning, have) = numbers.split_once(" | ")?;
 
-        let win_vec: Vec<u32> = nums_to_vec(winning);
-        let have_vec: Vec<u32> = nums_to_vec(have);
+        let win_vec: Vec<u32> = nums_to_vec(winning)?;
+        let have_vec: Vec<u32> = nums_to_vec(have)?;
 
         let mut win_sorted: Vec<u32> = win_vec.clone();
         let mut have_sorted: Vec<u32> = have_vec.clone();
         win_sorted.sort_unstable();
         have_sorted.sort_unstable();
 
-        return Card { id, winning: win_vec, have: have_vec, sorted_winning: win_sorted, sorted_have: have_sorted, points: None, instances: 1, matches: 0 };
+        return Ok(Card { id, winning: win_vec, have: have_vec, sorted_winning: win_sorted, sorted_have: have_sorted, points: None, instances: 1, matches: 0 });
     }
 
     fn calculate_points(&mut self) -> u32 {
@@ -152,12 +154,12 @@ impl Card {
 
 #[test]
 fn point_calculation() {
-    assert_eq!(8, Card::parse("Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53").calculate_points());
-    assert_eq!(2, Card::parse("Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19").calculate_points());
-    assert_eq!(2, Card::parse("Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1").calculate_points());
-    assert_eq!(1, Card::parse("Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83").calculate_points());
-    assert_eq!(0, Card::parse("Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36").calculate_points());
-    assert_eq!(0, Card::parse("Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11").calculate_points());
+    assert_eq!(8, Card::parse("Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53").unwrap().calculate_points());
+    assert_eq!(2, Card::parse("Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19").unwrap().calculate_points());
+    assert_eq!(2, Card::parse("Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1").unwrap().calculate_points());
+    assert_eq!(1, Card::parse("Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83").unwrap().calculate_points());
+    assert_eq!(0, Card::parse("Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36").unwrap().calculate_points());
+    assert_eq!(0, Card::parse("Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11").unwrap().calculate_points());
 }
 
 #[test]
@@ -168,7 +170,7 @@ Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
 Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
 Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
 Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
-");
+").unwrap();
     let total = pile.calculate_copies();
     assert_eq!(1, pile.get(1).instances);
     assert_eq!(2, pile.get(2).instances);
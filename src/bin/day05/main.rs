@@ -1,9 +1,12 @@
 use std::{collections::HashMap, fs};
 
+use utils::{info, parse::{ParseResult, ParseResultExt, Span}};
+
 fn main() {
     println!("AOC 2023 Day 5");
+    utils::log::init();
     let contents = fs::read_to_string("src/bin/day05/input.txt").expect("Failed to read input");
-    let almanac: Almanac = Almanac::load(&contents);
+    let almanac: Almanac = Almanac::load(&contents).expect_in("src/bin/day05/input.txt");
 
     let mut min: Vec<(u64, u64)> = almanac.seeds.iter()
         .map(|seed| (*seed, almanac.map("seed", "location", *seed)))
@@ -15,7 +18,7 @@ fn main() {
     let seed_id = almanac.name_id.get("seed").unwrap();
     let location_id = almanac.name_id.get("location").unwrap();
     let mappers = almanac.mapper_chain(seed_id, location_id);
-    println!("Created mappers");
+    info!("Created mappers");
     let ranges = almanac.expand_ranges();
     let minimum_loc2 = RangeMap::apply_sequence_to_ranges_iter(ranges, mappers)
         .map(|r| r.start)
@@ -30,12 +33,15 @@ struct RangeMapEntry {
     range_length: i64,
 }
 impl RangeMapEntry {
-    fn load(entry: &str) -> RangeMapEntry {
-        let mut parts = entry.trim().split(" ").filter(|s| !s.is_empty());
-        let dst = parts.next().unwrap().parse::<u64>().unwrap() as i64;
-        let src = parts.next().unwrap().parse::<u64>().unwrap() as i64;
-        let range_length = parts.next().unwrap().parse::<u64>().unwrap() as i64;
-        return RangeMapEntry { dest_start: dst, source_start: src, range_length };
+    fn load(entry: Span) -> ParseResult<RangeMapEntry> {
+        let parts: Vec<u64> = entry.numbers(" ")?;
+        if parts.len() != 3 {
+            return Err(entry.error("three numbers (destination, source, length)"));
+        }
+        let dst = parts[0] as i64;
+        let src = parts[1] as i64;
+        let range_length = parts[2] as i64;
+        return Ok(RangeMapEntry { dest_start: dst, source_start: src, range_length });
     }
 
     fn map(&self, val: u64) -> Option<u64> {
@@ -67,13 +73,10 @@ struct RangeMap {
 }
 #[allow(dead_code)]
 impl RangeMap {
-    fn load(name: &str, entries: &str) -> RangeMap {
-        let mut ent: Vec<RangeMapEntry> = entries.split("\n")
-            .filter(|s| !s.is_empty())
-            .map(|s| RangeMapEntry::load(s))
-            .collect();
+    fn load(name: &str, entries: Span) -> ParseResult<RangeMap> {
+        let mut ent: Vec<RangeMapEntry> = entries.parse_lines(|s| RangeMapEntry::load(s))?;
         ent.sort_by(|a, b| a.source_start.partial_cmp(&b.source_start).unwrap());
-        return RangeMap { name: name.to_owned(), entries: ent };
+        return Ok(RangeMap { name: name.to_owned(), entries: ent });
     }
 
     fn map(&self, val: u64) -> u64 {
@@ -196,16 +199,17 @@ struct Almanac {
     name_id: HashMap<String, u64>
 }
 impl Almanac {
-    fn load(data: &str) -> Almanac {
-        let (seeds, maps_str) = data.trim().split_once("\n\n").unwrap();
+    fn load(data: &str) -> ParseResult<Almanac> {
+        let (seeds, maps_str) = Span::new(data).trim().split_once("\n\n")?;
         let maps = maps_str.split("\n\n");
         let mut next_id: u64 = 0;
         let mut all_maps: HashMap<u64, (u64, RangeMap)> = HashMap::new();
         let mut name_id: HashMap<String, u64> = HashMap::new();
         for map in maps {
-            let (title, entries) = map.split_once(" map:\n").unwrap();
-            let (from, to) = title.split_once("-to-").unwrap();
-            let map = RangeMap::load(title, entries);
+            let (title, entries) = map.split_once(" map:\n")?;
+            let (from, to) = title.split_once("-to-")?;
+            let (from, to) = (from.as_str(), to.as_str());
+            let map = RangeMap::load(title.as_str(), entries)?;
             let from_id: u64 = match name_id.get(from) {
                 Some(&id) => id,
                 None => {
@@ -226,12 +230,8 @@ impl Almanac {
             };
             all_maps.insert(from_id, (to_id, map));
         }
-        let seed_vals: Vec<u64> = seeds.split(" ")
-            .filter(|s| !s.is_empty())
-            .filter(|s| s != &"seeds:")
-            .map(|s| s.parse::<u64>().expect(&("Failed to parse [".to_owned()+s+"]")))
-            .collect();
-        return Almanac { seeds: seed_vals, maps: all_maps, name_id };
+        let seed_vals: Vec<u64> = seeds.strip_prefix("seeds:")?.numbers(" ")?;
+        return Ok(Almanac { seeds: seed_vals, maps: all_maps, name_id });
     }
 
     fn map(&self, from_name: &str, to_name: &str, val: u64) -> u64 {
@@ -279,7 +279,7 @@ impl Almanac {
 
 #[test]
 fn almanac_loads() {
-    let almanac = Almanac::load(&get_test_output());
+    let almanac = Almanac::load(&get_test_output()).unwrap();
     assert_eq!(81, almanac.map("seed", "soil", 79));
     assert_eq!(35, almanac.map("seed", "location", 13));
     assert_eq!(82, almanac.map("fertilizer", "temperature", 57));
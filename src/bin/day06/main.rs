@@ -1,44 +1,53 @@
 use std::fs;
 
+use utils::{debug, parse::{ParseResult, ParseResultExt, Span}};
+
 fn main() {
     println!("AOC 2023 Day 6");
+    utils::log::init();
     test_main();
 
     let contents = fs::read_to_string("src/bin/day06/input.txt").expect("Failed to read input");
-    let (times, distances) = contents.trim().split_once("\n").unwrap();
-
-    let times_iter = times.split(" ")
-        .filter(|s| !s.is_empty())
-        .filter(|s| &"Time:" != s)
-        .map(|s| s.parse::<u64>().unwrap());
-
-    let distances_iter = distances.split(" ")
-        .filter(|s| !s.is_empty())
-        .filter(|s| &"Distance:" != s)
-        .map(|s| s.parse::<u64>().unwrap());
+    let (races, kerned) = parse_races(&contents).expect_in("src/bin/day06/input.txt");
 
-    let races: Vec<Race> = times_iter.zip(distances_iter).map(|(time, distance)| Race { time, distance }).collect();
     let variance_product: u64 = races.iter()
         .map(|race| possibilities(race.time, race.distance))
         .product();
     println!("Part 1 variance product: {}", variance_product);
 
-    let kerned_time: u64 = times.replace("Time:", "").replace(" ", "").parse::<u64>().unwrap();
-    let kerned_distance: u64 = distances.replace("Distance:", "").replace(" ", "").parse::<u64>().unwrap();
-    let variance_2: u64 = possibilities(kerned_time, kerned_distance);
+    let variance_2: u64 = possibilities(kerned.time, kerned.distance);
     println!("Part 2 kerned variance: {}", variance_2);
 }
 
+/// return: (races, single race with the spaces between digits removed)
+fn parse_races(data: &str) -> ParseResult<(Vec<Race>, Race)> {
+    let (times, distances) = Span::new(data).trim().split_once("\n")?;
+    let times = times.trim().strip_prefix("Time:")?;
+    let distances = distances.trim().strip_prefix("Distance:")?;
+
+    let time_values: Vec<u64> = times.numbers(" ")?;
+    let distance_values: Vec<u64> = distances.numbers(" ")?;
+    if time_values.len() != distance_values.len() {
+        return Err(distances.error(format!("{} distances", time_values.len())));
+    }
+    let races: Vec<Race> = time_values.into_iter().zip(distance_values).map(|(time, distance)| Race { time, distance }).collect();
+
+    let kerned_time: u64 = times.parse_with("digits", |s| s.replace(" ", "").parse::<u64>().ok())?;
+    let kerned_distance: u64 = distances.parse_with("digits", |s| s.replace(" ", "").parse::<u64>().ok())?;
+    return Ok((races, Race { time: kerned_time, distance: kerned_distance }));
+}
+
 fn test_main() {
     let time = 30;
     let record = 200;
     let lower = bound(time, record, true);
     let upper = bound(time, record, false);
-    println!("\nFor {} ms race with record {} mm:", time, record);
-    println!("\tLower bound {} ms ({} mm)", lower, distance_traveled(lower, time));
-    println!("\tUpper bound {} ms ({} mm)\n\n", upper, distance_traveled(upper, time));
+    debug!("For {} ms race with record {} mm:", time, record);
+    debug!("\tLower bound {} ms ({} mm)", lower, distance_traveled(lower, time));
+    debug!("\tUpper bound {} ms ({} mm)", upper, distance_traveled(upper, time));
 }
 
+#[derive(Debug)]
 struct Race {
     time: u64,
     distance: u64
@@ -72,6 +81,16 @@ fn possibilities(race_time: u64, record_distance: u64) -> u64 {
     return (upper - lower) + 1;
 }
 
+#[test]
+fn race_parsing() {
+    let (races, kerned) = parse_races("Time:      7  15   30\nDistance:  9  40  200\n").unwrap();
+    assert_eq!(vec![(7, 9), (15, 40), (30, 200)], races.iter().map(|r| (r.time, r.distance)).collect::<Vec<_>>());
+    assert_eq!((71530, 940200), (kerned.time, kerned.distance));
+
+    let err = parse_races("Time: 7 15\nDistance: 9 4o").unwrap_err();
+    assert_eq!((2, 13), (err.line, err.column));
+}
+
 #[test]
 fn possibility_count() {
     assert_eq!(4, possibilities(7, 9));
@@ -1,22 +1,29 @@
 use std::{cmp::Ordering, collections::HashMap, fs};
 
+use utils::{debug, parse::{ParseResult, ParseResultExt, Span}};
+
 fn main() {
     println!("AOC 2023 Day 7");
+    utils::log::init();
     let content = fs::read_to_string("src/bin/day07/input.txt").expect("Could not find input");
-    let lines = content.trim().split("\n");
-    let mut hands: Vec<(Hand, u64)> = lines.map(|l| l.trim().split_once(" ").map(|(a, b)| (a.trim(), b.trim())))
-        .map(|o| o.unwrap())
-        .map(|(card_str, count_str)| (Hands::parse_hand(card_str), count_str.parse::<u64>().unwrap()))
-        .collect();
+    let mut hands: Vec<(Hand, u64)> = parse_hands(&content).expect_in("src/bin/day07/input.txt");
     hands.sort_by(|(hand_a, _), (hand_b, _)| compare_hands(*hand_a, *hand_b));
-    println!("Sorted hands:");
+    debug!("Sorted hands:");
     for hand in &hands {
-        println!("\t[{:?}]: {}", hand.0, hand.1);
+        debug!("\t[{:?}]: {}", hand.0, hand.1);
     }
     let sum_winnings: u64 = hands.iter().map(|(_, bid)| bid).enumerate().map(|(idx, bid)| (idx as u64 + 1) * bid).sum();
     println!("Sum of winnings: {}", sum_winnings);
 }
 
+/// return: (hand, bid) for every line
+fn parse_hands(data: &str) -> ParseResult<Vec<(Hand, u64)>> {
+    return Span::new(data).parse_lines(|l| {
+        let (card_str, count_str) = l.trim().split_once(" ")?;
+        return Ok((Hands::parse_hand(card_str.trim())?, count_str.parse::<u64>()?));
+    });
+}
+
 
 type Hand = [Card; 5];
 
@@ -78,13 +85,15 @@ enum Hands {
 impl Hands {
     #[allow(dead_code)]
     fn from_str(data: &str) -> Hands {
-        return Hands::from(Hands::parse_hand(data));
+        return Hands::from(Hands::parse_hand(data.into()).unwrap());
     }
 
-    fn parse_hand(data: &str) -> Hand {
-        assert_eq!(5, data.len());
-        let cards: Vec<Card> = data.chars().into_iter().map(|c| Card::parse(c).unwrap()).collect();
-        return [cards[0], cards[1], cards[2], cards[3], cards[4]];
+    fn parse_hand(data: Span) -> ParseResult<Hand> {
+        let cards: Vec<Card> = data.decode_chars("a card (`AKQJT98765432`)", Card::parse)?;
+        if cards.len() != 5 {
+            return Err(data.error("a hand of 5 cards"));
+        }
+        return Ok([cards[0], cards[1], cards[2], cards[3], cards[4]]);
     }
 
     fn from(hand: Hand) -> Hands {
@@ -157,7 +166,7 @@ fn hand_parsing() {
 
 #[test]
 fn hand_cmp() {
-    assert_eq!(Ordering::Greater, compare_hands(Hands::parse_hand("33332"), Hands::parse_hand("2AAAA")));
+    assert_eq!(Ordering::Greater, compare_hands(Hands::parse_hand("33332".into()).unwrap(), Hands::parse_hand("2AAAA".into()).unwrap()));
 }
 
 #[test]
@@ -1,17 +1,27 @@
 use std::{collections::HashMap, fs};
 
+use utils::{debug, info, parse::{ParseResult, ParseResultExt, Span}, trace};
+
 fn main() {
     println!("AOC 2023 Day 8");
+    utils::log::init();
 
     let contents = fs::read_to_string("src/bin/day08/input.txt").expect("Failed to load input");
-    let (pattern, network_data) = contents.split_once("\n\n").unwrap();
-    let network = Network::load(network_data);
-    let steps = network.traverse(pattern);
+    let (pattern, network) = parse(&contents).expect_in("src/bin/day08/input.txt");
+    let steps = network.traverse(&pattern);
     println!("Part 1 steps: {}", steps);
-    let steps2 = network.lcm_all(pattern);
+    let steps2 = network.lcm_all(&pattern);
     println!("Part 2 steps: {}", steps2);
 }
 
+/// return: (direction pattern, network)
+fn parse(contents: &str) -> ParseResult<(String, Network)> {
+    let (pattern, network_data) = Span::new(contents).split_once("\n\n")?;
+    let pattern = pattern.trim();
+    let directions = pattern.decode_chars("`L` or `R`", |c| if c == 'L' || c == 'R' { Some(c) } else { None })?;
+    return Ok((String::from_iter(directions), Network::load(network_data)?));
+}
+
 type NodeID = String;
 
 #[derive(Debug)]
@@ -21,24 +31,21 @@ struct Network {
 }
 #[allow(dead_code)]
 impl Network {
-    fn load(data: &str) -> Network {
+    fn load<'a>(data: impl Into<Span<'a>>) -> ParseResult<Network> {
         let mut connections: HashMap<NodeID, (NodeID, NodeID)> = HashMap::new();
         let mut starting_nodes: Vec<NodeID> = vec![];
-        let lines = data.trim().split("\n")
-            .map(|l| l.split_once(" = ").unwrap());
-        let split_more = lines
-            .map(|(id, ids)| {
-                let cleaned = ids.replace("(", "").replace(")", "");
-                let split_ids: (&str, &str) = cleaned.split_once(", ").unwrap();
-                return (id, (split_ids.0.to_owned(), split_ids.1.to_owned()));
-            });
+        let split_more = data.into().parse_lines(|l| {
+            let (id, ids) = l.trim().split_once(" = ")?;
+            let (left, right) = ids.strip_prefix("(")?.strip_suffix(")")?.split_once(", ")?;
+            return Ok((id.as_str(), (left.as_str(), right.as_str())));
+        })?;
         for (id, (left, right)) in split_more {
             connections.insert(id.to_owned(), (left.to_owned(), right.to_owned()));
             if id.ends_with("A") {
                 starting_nodes.push(id.to_owned());
             }
         }
-        return Network { connections, starting_nodes };
+        return Ok(Network { connections, starting_nodes });
     }
 
     fn traverse(&self, pattern: &str) -> u64 {
@@ -77,15 +84,14 @@ impl Network {
     }
 
     fn lcm_all(&self, pattern: &str) -> u64 {
-        println!("Starting to traverse {} starting nodes", self.starting_nodes.len());
+        info!("Starting to traverse {} starting nodes", self.starting_nodes.len());
         let cycles: Vec<u64> = self.starting_nodes.iter().map(|start| self.traverse_one_endings(pattern, start.to_owned())).collect();
-        println!("Mapped cycles {:?}", cycles);
+        debug!("Mapped cycles {:?}", cycles);
         let reduced = cycles.iter().map(|v| *v).reduce(|acc, e| {
             let least = lcm(acc, e);
-            println!("Reducing ({}, {}) -> {}", acc, e, least);
+            debug!("Reducing ({}, {}) -> {}", acc, e, least);
             return least;
         });
-        println!("Reduced");
         return reduced.unwrap();
     }
 
@@ -137,9 +143,9 @@ fn lcm(a: u64, b: u64) -> u64 {
     for factor_b in prime_factorize(b) {
         counts_b.insert(factor_b, counts_b.get(&factor_b).unwrap_or(&0) + 1);
     }
-    println!("lcm({}, {}):", a, b);
-    println!("\tcounts_a: {:?}", counts_a);
-    println!("\tcounts_b: {:?}", counts_b);
+    trace!("lcm({}, {}):", a, b);
+    trace!("\tcounts_a: {:?}", counts_a);
+    trace!("\tcounts_b: {:?}", counts_b);
     for (factor, count) in counts_b.iter() {
         counts_a.insert(*factor, *counts_a.get(factor).unwrap_or(count).max(count));
     }
@@ -149,15 +155,13 @@ fn lcm(a: u64, b: u64) -> u64 {
 #[allow(dead_code)]
 fn get_test_network() -> (String, Network) {
     let contents = fs::read_to_string("src/bin/day08/test.txt").expect("Failed to load test.txt");
-    let (pattern, network_data) = contents.split_once("\n\n").unwrap();
-    return (pattern.to_owned(), Network::load(network_data));
+    return parse(&contents).unwrap();
 }
 
 #[allow(dead_code)]
 fn get_test_network_2() -> (String, Network) {
     let contents = fs::read_to_string("src/bin/day08/test2.txt").expect("Failed to load test.txt");
-    let (pattern, network_data) = contents.split_once("\n\n").unwrap();
-    return (pattern.to_owned(), Network::load(network_data));
+    return parse(&contents).unwrap();
 }
 
 #[test]
@@ -1,20 +1,26 @@
 use std::fs;
 
+use utils::parse::{ParseResult, ParseResultExt, Span};
+
 fn main() {
     println!("AOC 2023 Day 9");
 
     let contents = fs::read_to_string("src/bin/day09/input.txt").expect("Failed to load input");
-    let prediction_sum = predict_sum(&contents, trickle_predict);
+    let histories = parse_histories(&contents).expect_in("src/bin/day09/input.txt");
+    let prediction_sum = predict_sum(&histories, trickle_predict);
     println!("Sum of predictions: {}", prediction_sum);
 
-    let pre_prediction_sum = predict_sum(&contents, trickle_pre_predict);
+    let pre_prediction_sum = predict_sum(&histories, trickle_pre_predict);
     println!("Sum of pre-predictions: {}", pre_prediction_sum);
 }
 
-fn predict_sum(data: &str, predictor: fn (Vec<Vec<i64>>) -> i64) -> i64 {
-    return data.trim().split("\n")
-        .map(|l| l.split(" ").map(|s| s.parse::<i64>().unwrap()).collect::<Vec<i64>>())
-        .map(|v| predictor(diff_stack(v)))
+fn parse_histories(data: &str) -> ParseResult<Vec<Vec<i64>>> {
+    return Span::new(data).parse_lines(|l| l.numbers(" "));
+}
+
+fn predict_sum(histories: &[Vec<i64>], predictor: fn (Vec<Vec<i64>>) -> i64) -> i64 {
+    return histories.iter()
+        .map(|v| predictor(diff_stack(v.clone())))
         .sum();
 }
 
@@ -73,8 +79,8 @@ fn prediction() {
 
 #[test]
 fn full_pipeline() {
-    assert_eq!(114, predict_sum("0 3 6 9 12 15
+    assert_eq!(114, predict_sum(&parse_histories("0 3 6 9 12 15
 1 3 6 10 15 21
 10 13 16 21 30 45
-", trickle_predict));
+").unwrap(), trickle_predict));
 }
@@ -1,30 +1,44 @@
-use std::{fs, collections::hash_map::DefaultHasher, hash::{Hash, Hasher}, time::SystemTime};
+use std::{env, fs, collections::hash_map::DefaultHasher, hash::{Hash, Hasher}, time::SystemTime};
+
+use utils::{info, log::Level, log_enabled, parse::{ParseResult, ParseResultExt, Span}, polygon::Polygon, warn};
 
 fn main() {
     println!("AOC 2023 Day 10");
+    utils::log::init();
 
     let test = false;
     let mut map: Map = if test {parse_example(4, true)} else {parse_input()};
 
-    println!("Map is {}x{}", map.width, map.height);
+    info!("Map is {}x{}", map.width, map.height);
 
     map.build_all();
-    println!("Starting coord: {:?}", map.starting_pos);
+    info!("Starting coord: {:?}", map.starting_pos);
     map.calculate_starting_network();
 
-    map.print();
-    println!("");
+    if log_enabled!(Level::Debug) {
+        map.print();
+    }
 
     let max_dist = map.calculate_distance();
-    if test {
+    if log_enabled!(Level::Trace) {
         map.print_dist();
     }
 
-    println!("\nmax dist: {}\n", max_dist);
+    println!("max dist: {}", max_dist);
 
     let inner_count = map.calculate_in_loop();
-    map.print_in_loop(false);
+    if log_enabled!(Level::Debug) {
+        map.print_in_loop(false);
+    }
     println!("inner count: {}", inner_count);
+
+    if env::args().any(|a| a == "--polygon") {
+        let polygon_count = map.enclosed_by_polygon();
+        if polygon_count != inner_count {
+            warn!("Pick's theorem encloses {} tiles, but the scanline found {}", polygon_count, inner_count);
+        }
+        println!("inner count (polygon): {}", polygon_count);
+    }
 }
 
 #[derive(PartialEq, Clone, Copy)]
@@ -145,24 +159,26 @@ struct Map {
     max_id: u64
 }
 impl Map {
-    fn parse(data: &str) -> Map {
+    fn parse(data: &str) -> ParseResult<Map> {
+        let span = Span::new(data);
         let mut starting_pos: Option<Coord> = None;
 
-        let mut lines: Vec<&str> = data.trim().split("\n").collect();
-        let height: usize = lines.len() + 2;
-        let width: usize = lines[0].chars().count() + 2;
-        
-        let pad = ".".repeat(width);
-        lines.insert(0, &pad);
-        lines.push(&pad);
-
-        let pipes = lines.iter()
-            .map(|line| (".".to_owned()+line+".").chars()
-                .map(|c| Pipe::parse(c).expect("Failed to parse pipe"))
-                .map(|p| Entry { pipe: p, id: None, distance: 0xffff_ffff_ffff_ffff, in_loop: false })
-                .collect::<Vec<_>>()
-            )
-            .collect::<Vec<Vec<Entry>>>();
+        let rows = span.grid("a pipe (one of `|-LJ7F.S`)", Pipe::parse)?;
+        let height: usize = rows.len() + 2;
+        let width: usize = rows[0].len() + 2;
+
+        let entry = |p: Pipe| Entry { pipe: p, id: None, distance: 0xffff_ffff_ffff_ffff, in_loop: false };
+        let pad = || (0..width).map(|_| entry(Pipe::Ground)).collect::<Vec<_>>();
+
+        let mut pipes: Vec<Vec<Entry>> = vec![pad()];
+        for row in rows {
+            let mut padded = vec![entry(Pipe::Ground)];
+            padded.extend(row.into_iter().map(entry));
+            padded.push(entry(Pipe::Ground));
+            pipes.push(padded);
+        }
+        pipes.push(pad());
+
         'Outer: for row in 0..height {
             for column in 0..width {
                 if pipes[row][column].pipe == Pipe::Start {
@@ -173,9 +189,9 @@ impl Map {
         }
         if let Some(sp) = starting_pos {
             let time: u64 = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs(); 
-            return Map { pipes, starting_pos: sp, width, height, max_id: time};
+            return Ok(Map { pipes, starting_pos: sp, width, height, max_id: time});
         }
-        panic!("No starting point found");
+        return Err(span.error("a starting tile `S`"));
     }
     
     fn print(&self) {
@@ -354,17 +370,49 @@ impl Map {
         }
         return count;
     }
+
+    /// The main loop's tiles in order, starting (and not ending) at the starting position
+    fn trace_loop(&self) -> Vec<Coord> {
+        let main_id = self.get(self.starting_pos).id.expect("No starting id");
+        let mut path = vec![self.starting_pos];
+        let mut came_from = Side::values().into_iter()
+            .find(|s| {
+                let other = self.get(s.offset(&self.starting_pos));
+                other.is_main(main_id) && other.pipe.is_side_open(&s.opposite())
+            })
+            .expect("Start isn't connected to its loop")
+            .opposite();
+        let mut c = came_from.opposite().offset(&self.starting_pos);
+        while c != self.starting_pos {
+            path.push(c);
+            let pipe = &self.get(c).pipe;
+            let next = Side::values().into_iter()
+                .find(|s| *s != came_from && pipe.is_side_open(s))
+                .expect("Loop ends in a dead end");
+            came_from = next.opposite();
+            c = next.offset(&c);
+        }
+        return path;
+    }
+
+    /// Count enclosed tiles with the shoelace formula and Pick's theorem, taking the centres of the
+    /// main loop's tiles as the corners of a polygon
+    fn enclosed_by_polygon(&self) -> u64 {
+        let corners = self.trace_loop().iter().map(|c| (c.column as i64, c.row as i64)).collect();
+        return Polygon::new(corners).interior_points();
+    }
 }
 
 fn parse_example(id: usize, extra_pipes: bool) -> Map {
-    let contents = fs::read_to_string("src/bin/day10/ex0".to_owned()+&id.to_string()+(if extra_pipes {"b"} else {"a"})+".txt")
+    let fname = "src/bin/day10/ex0".to_owned()+&id.to_string()+(if extra_pipes {"b"} else {"a"})+".txt";
+    let contents = fs::read_to_string(&fname)
         .expect("Failed to load exmaple");
-    return Map::parse(&contents);
+    return Map::parse(&contents).expect_in(&fname);
 }
 
 fn parse_input() -> Map {
     let contents = fs::read_to_string("src/bin/day10/input.txt").expect("Failed to load input");
-    return Map::parse(&contents);
+    return Map::parse(&contents).expect_in("src/bin/day10/input.txt");
 }
 
 fn colorize_id(input: &str, id: u64) -> String {
@@ -413,3 +461,16 @@ fn highlight(input: &str, actually: bool, r: u8, g: u8, b: u8) -> String {
     }
     return "\x1b[48;2;".to_owned()+&r.to_string()+";"+&g.to_string()+";"+&b.to_string()+"m"+input+"\x1b[0m";
 }
+
+#[test]
+fn polygon_matches_scanline() {
+    let expected = [1, 1, 1, 1, 4, 4, 8, 10];
+    for (i, (id, extra_pipes)) in (1..=4).flat_map(|id| [(id, false), (id, true)]).enumerate() {
+        let mut map = parse_example(id, extra_pipes);
+        map.build_all();
+        map.calculate_starting_network();
+        let inner_count = map.calculate_in_loop();
+        assert_eq!(expected[i], inner_count, "ex0{}{}", id, if extra_pipes {"b"} else {"a"});
+        assert_eq!(inner_count, map.enclosed_by_polygon(), "ex0{}{}", id, if extra_pipes {"b"} else {"a"});
+    }
+}
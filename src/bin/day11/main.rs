@@ -1,10 +1,12 @@
 use std::fs;
 
+use utils::parse::{ParseResult, ParseResultExt, Span};
+
 fn main() {
     println!("AOC 2023 Day 11");
 
     let contents = fs::read_to_string("src/bin/day11/input.txt").expect("Failed to read input");
-    let map: &mut StarMap = &mut StarMap::load(&contents);
+    let map: &mut StarMap = &mut StarMap::load(&contents).expect_in("src/bin/day11/input.txt");
 
     map.expand_all();
 
@@ -27,7 +29,7 @@ fn get_test_map() -> StarMap {
 ..........
 .......#..
 #...#.....
-");
+").unwrap();
 }
 
 struct Coord {
@@ -128,22 +130,12 @@ impl StarMap {
         return sum;
     }
 
-    fn load(data: &str) -> StarMap {
-        let mut field: Vec<Vec<StarType>> = vec![];
-
-        let lines = data.trim().split("\n").enumerate();
-        let points = lines.map(|(i, l)| (i, l.trim().chars().enumerate()));
-        for (_, line) in points {
-            let mut row: Vec<StarType> = vec![];
-            for (_, chr) in line {
-                row.push(StarType::decode(chr).expect(&format!("Unkown character {}", chr)));
-            }
-            field.push(row);
-        }
+    fn load(data: &str) -> ParseResult<StarMap> {
+        let field: Vec<Vec<StarType>> = Span::new(data).grid("`.` or `#`", StarType::decode)?;
 
         let width = field[0].len();
         let height = field.len();
-        return StarMap { field, width, height, expanded_rows: vec![], expanded_columns: vec![] };
+        return Ok(StarMap { field, width, height, expanded_rows: vec![], expanded_columns: vec![] });
     }
 
     fn expand_all(&mut self) {
@@ -234,7 +226,7 @@ fn counting() {
 ..........
 .......#..
 #...#.....
-");
+").unwrap();
     map.expand_empty_columns();
     map.expand_empty_rows();
 
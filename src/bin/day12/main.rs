@@ -1,47 +1,79 @@
-#![allow(unused_mut)]
+use std::{env, fs, fmt::{Display, Error, Formatter}, io::{Write, stdout}, sync::atomic::{AtomicUsize, Ordering}};
 
-use std::{fs, fmt::{Display, Error, Formatter}, hash::{Hash, Hasher}, io::{Write, stdout}};
-
-use memoize::memoize;
+use rand::Rng;
+use utils::{info, parse::{ParseResult, ParseResultExt, Span}};
 
 fn main() {
     println!("AOC 2023 Day 12");
+    utils::log::init();
 
     let contents = fs::read_to_string("src/bin/day12/input.txt").expect("Failed to read input");
-    //debugging();
+    // `--unfold=<n>` repeats each record n times for part 2 instead of 5
+    let factor: usize = env::args().find_map(|a| a.strip_prefix("--unfold=").and_then(|n| n.parse().ok())).unwrap_or(5);
 
-    let records: Vec<Record> = contents.trim().split("\n").map(|s| Record::parse(s)).collect();
-    let sum: u64 = records.iter().map(|r| r.arrangements()).sum();
+    let records: Vec<Record> = Span::new(&contents).parse_lines(Record::parse).expect_in("src/bin/day12/input.txt");
+    let sum: u128 = utils::parallel::map(&records, |r| r.arrangements()).into_iter().sum();
     println!("Total arrangement count: {}", sum);
 
-    let mut sum2: u64 = 0;
-    for (i, record) in records.iter().enumerate() {
+    let done = AtomicUsize::new(0);
+    let sum2: u128 = utils::parallel::map(&records, |record| {
+        let count = record.unfold(factor).arrangements();
+        let i = done.fetch_add(1, Ordering::Relaxed) + 1;
         if i % 5 == 0 {
             let progress: f64 = (i as f64) / (records.len() as f64);
             print!("\x1b[2K\r");
             print!("  Progress: {}%", (progress*100_00.0).round()/100.0);
             stdout().flush().unwrap();
         }
-        sum2 += record.expand().arrangements();
-    }
+        return count;
+    }).into_iter().sum();
     println!("\n");
-    //let sum2: u64 = records.iter().map(|r| r.expand().arrangements()).sum();
     println!("Total expanded arrangement count: {}", sum2);
+
+    // `--enumerate=<line>` lists the first arrangements of a record, and `--sample=<line>` picks
+    // one of its unfolded arrangements at random
+    let line_arg = |flag: &str| env::args().find_map(|a| a.strip_prefix(flag).and_then(|n| n.parse::<usize>().ok()));
+    // lines count from 1, so 0 wraps round to past the end
+    let record_on = |line: usize| records.get(line.wrapping_sub(1)).ok_or(format!("No record on line {} (there are {})", line, records.len()));
+    if let Some(line) = line_arg("--enumerate=") {
+        match record_on(line) {
+            Ok(record) => {
+                println!("{} has {} arrangements:", record, record.arrangements());
+                for arrangement in record.enumerate(20) {
+                    record.verify(&arrangement).expect("Enumerated an invalid arrangement");
+                    println!("  {}", Record::variant_to_string(&arrangement));
+                }
+            },
+            Err(e) => println!("{}", e)
+        }
+    }
+    if let Some(line) = line_arg("--sample=") {
+        match record_on(line) {
+            Ok(record) => {
+                let record = record.unfold(factor);
+                let arrangement = record.sample(&mut rand::thread_rng()).expect("No arrangements to sample from");
+                record.verify(&arrangement).expect("Sampled an invalid arrangement");
+                info!("Sampled from {} arrangements", record.arrangements());
+                println!("{}", Record::variant_to_string(&arrangement));
+            },
+            Err(e) => println!("{}", e)
+        }
+    }
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Clone, Copy, Debug)]
 enum Symbol {
     Operational,
     Damaged,
     Unknown
 }
 impl Symbol {
-    fn decode(chr: char) -> Symbol {
+    fn decode(chr: char) -> Option<Symbol> {
         return match chr {
-            '.' => Symbol::Operational,
-            '#' => Symbol::Damaged,
-            '?' => Symbol::Unknown,
-            _ => panic!("Unknown symbol")
+            '.' => Some(Symbol::Operational),
+            '#' => Some(Symbol::Damaged),
+            '?' => Some(Symbol::Unknown),
+            _ => None
         }
     }
 
@@ -54,68 +86,182 @@ impl Symbol {
     }
 }
 
-#[allow(dead_code)]
-fn colorize(input: &str, r: u8, g: u8, b: u8) -> String {
-    return "\x1b[38;2;".to_owned()+&r.to_string()+";"+&g.to_string()+";"+&b.to_string()+"m"+input+"\x1b[0m";
-}
-
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone)]
 struct Record {
     conditions: Vec<Symbol>,
     contiguous_damage: Vec<u64>
 }
 impl Record {
-    fn parse(line: &str) -> Record {
-        let (conditions_dat, damage_dat) = line.trim().split_once(" ").unwrap();
-        let conditions: Vec<Symbol> = conditions_dat.chars().map(|c| Symbol::decode(c)).collect();
-        let contiguous_damage: Vec<u64> = damage_dat.split(",").map(|s| s.parse::<u64>().unwrap()).collect();
-        return Record { conditions, contiguous_damage };
+    fn parse<'a>(line: impl Into<Span<'a>>) -> ParseResult<Record> {
+        let (conditions_dat, damage_dat) = line.into().trim().split_once(" ")?;
+        let conditions: Vec<Symbol> = conditions_dat.decode_chars("`.`, `#` or `?`", Symbol::decode)?;
+        let contiguous_damage: Vec<u64> = damage_dat.numbers(",")?;
+        if let Some(idx) = contiguous_damage.iter().position(|d| *d == 0) {
+            return Err(damage_dat.split(",").nth(idx).unwrap().error("a non-zero group size"));
+        }
+        return Ok(Record { conditions, contiguous_damage });
     }
 
+    /// Part 2's unfolding: five copies
+    #[cfg(test)]
     fn expand(&self) -> Record {
-        let mut new_conditions: Vec<Symbol> = vec![];
-        let mut new_cont_damage: Vec<u64> = vec![];
-        for i in 1..=5u8 {
-            new_conditions.extend(self.copy_conditions());
-            if i < 5 {
-                new_conditions.push(Symbol::Unknown);
+        return self.unfold(5);
+    }
+
+    /// `factor` copies of the conditions joined by `?`, and `factor` copies of the groups
+    fn unfold(&self, factor: usize) -> Record {
+        let mut conditions: Vec<Symbol> = vec![];
+        for i in 0..factor {
+            if i > 0 {
+                conditions.push(Symbol::Unknown);
             }
-            new_cont_damage.extend(self.copy_damage());
+            conditions.extend(&self.conditions);
         }
-        return Record { conditions: new_conditions, contiguous_damage: new_cont_damage };
+        return Record { conditions, contiguous_damage: self.contiguous_damage.repeat(factor) };
+    }
+
+    fn arrangements(&self) -> u128 {
+        return self.table().get(0, 0);
     }
 
+    /// Whether a group of `len` damaged springs can start at `position`: nothing in it is known to
+    /// be operational, and it isn't followed straight away by another damaged one
     #[inline]
-    fn copy_conditions(&self) -> Vec<Symbol> {
-        let mut new: Vec<Symbol> = vec![];
-        new.reserve_exact(self.conditions.len());
-        for s in &self.conditions {
-            new.push(*s);
+    fn group_fits(&self, operational_before: &[usize], position: usize, len: usize) -> bool {
+        let end = position + len;
+        return end <= self.conditions.len()
+            && operational_before[end] == operational_before[position]
+            && self.conditions.get(end) != Some(&Symbol::Damaged);
+    }
+
+    /// `ways[position][group]`: arrangements of `conditions[position..]` into
+    /// `contiguous_damage[group..]`, filled in from the end backwards
+    fn table(&self) -> Table {
+        let (n, groups) = (self.conditions.len(), self.contiguous_damage.len());
+        let mut operational_before = vec![0; n + 1];
+        for (i, s) in self.conditions.iter().enumerate() {
+            operational_before[i + 1] = operational_before[i] + (*s == Symbol::Operational) as usize;
         }
-        return new;
+
+        let mut table = Table { ways: vec![0; (n + 1) * (groups + 1)], groups, operational_before };
+        table.ways[n * (groups + 1) + groups] = 1;
+        for position in (0..n).rev() {
+            for group in 0..=groups {
+                let mut ways = 0;
+                if self.conditions[position] != Symbol::Damaged {
+                    ways += table.get(position + 1, group);
+                }
+                if self.conditions[position] != Symbol::Operational && group < groups {
+                    let len = self.contiguous_damage[group] as usize;
+                    if self.group_fits(&table.operational_before, position, len) {
+                        // skip the operational spring after the group too, unless it's the end
+                        ways += table.get((position + len + 1).min(n), group + 1);
+                    }
+                }
+                table.ways[position * (groups + 1) + group] = ways;
+            }
+        }
+        return table;
     }
 
-    #[inline]
-    fn copy_damage(&self) -> Vec<u64> {
-        let mut new: Vec<u64> = vec![];
-        new.reserve_exact(self.contiguous_damage.len());
-        for s in &self.contiguous_damage {
-            new.push(*s);
+    /// The first `limit` arrangements in order ('.' before '#' at each unknown spring)
+    fn enumerate(&self, limit: usize) -> Vec<Vec<Symbol>> {
+        let table = self.table();
+        let mut found = vec![];
+        // (position, group, arrangement so far)
+        let mut stack: Vec<(usize, usize, Vec<Symbol>)> = vec![(0, 0, vec![])];
+        while let Some((position, group, arrangement)) = stack.pop() {
+            if found.len() == limit {
+                break;
+            }
+            if table.get(position, group) == 0 {
+                continue;
+            }
+            if position == self.conditions.len() {
+                found.push(arrangement);
+                continue;
+            }
+            // pushed in reverse, so the operational choice comes out first
+            if let Some((next, next_group, springs)) = self.damaged_choice(&table, position, group) {
+                let mut damaged = arrangement.clone();
+                damaged.extend(springs);
+                stack.push((next, next_group, damaged));
+            }
+            if self.conditions[position] != Symbol::Damaged {
+                let mut operational = arrangement;
+                operational.push(Symbol::Operational);
+                stack.push((position + 1, group, operational));
+            }
         }
-        return new;
+        return found;
     }
 
-    fn arrangements(&self) -> u64 {
-        #[cfg(test)]
-        println!("\n\n\n\n");
-        return self.clone().mut_ok_subvariants(0);
+    /// One arrangement, every one equally likely
+    fn sample(&self, rng: &mut impl Rng) -> Option<Vec<Symbol>> {
+        let table = self.table();
+        if table.get(0, 0) == 0 {
+            return None;
+        }
+        let (mut position, mut group) = (0, 0);
+        let mut arrangement = vec![];
+        while position < self.conditions.len() {
+            let operational = if self.conditions[position] != Symbol::Damaged { table.get(position + 1, group) } else { 0 };
+            let total = table.get(position, group);
+            if rng.gen_range(0..total) < operational {
+                arrangement.push(Symbol::Operational);
+                position += 1;
+            } else {
+                let (next, next_group, springs) = self.damaged_choice(&table, position, group).unwrap();
+                arrangement.extend(springs);
+                (position, group) = (next, next_group);
+            }
+        }
+        return Some(arrangement);
     }
 
-    fn mut_ok_subvariants(&mut self, depth: usize) -> u64 { 
-        return mut_ok_subvariants(self.clone(), depth);
+    /// Starting the next group at `position`, if that's allowed: where it leaves off, and the
+    /// springs it covers (with the operational one after it)
+    fn damaged_choice(&self, table: &Table, position: usize, group: usize) -> Option<(usize, usize, Vec<Symbol>)> {
+        if self.conditions[position] == Symbol::Operational || group == table.groups {
+            return None;
+        }
+        let len = self.contiguous_damage[group] as usize;
+        if !self.group_fits(&table.operational_before, position, len) {
+            return None;
+        }
+        let mut springs = vec![Symbol::Damaged; len];
+        let mut next = position + len;
+        if next < self.conditions.len() {
+            springs.push(Symbol::Operational);
+            next += 1;
+        }
+        return Some((next, group + 1, springs));
+    }
+
+    /// Check a fully known arrangement against this record
+    fn verify(&self, arrangement: &[Symbol]) -> Result<(), String> {
+        if arrangement.len() != self.conditions.len() {
+            return Err(format!("{} springs instead of {}", arrangement.len(), self.conditions.len()));
+        }
+        for (i, (known, actual)) in self.conditions.iter().zip(arrangement).enumerate() {
+            if *actual == Symbol::Unknown {
+                return Err(format!("spring {} is still unknown", i + 1));
+            }
+            if *known != Symbol::Unknown && known != actual {
+                return Err(format!("spring {} should be `{}`, not `{}`", i + 1, known.encode(), actual.encode()));
+            }
+        }
+        let groups: Vec<u64> = Record::variant_to_string(arrangement).split('.')
+            .filter(|run| !run.is_empty())
+            .map(|run| run.len() as u64)
+            .collect();
+        if groups != self.contiguous_damage {
+            return Err(format!("damaged groups are {:?}, not {:?}", groups, self.contiguous_damage));
+        }
+        return Ok(());
     }
 
-    fn variant_to_string(data: &Vec<Symbol>) -> String {
+    fn variant_to_string(data: &[Symbol]) -> String {
         return String::from_iter(data.iter().map(|s| s.encode()));
     }
 }
@@ -128,143 +274,102 @@ impl Display for Record {
         }
     }
 }
-impl Clone for Record {
-    fn clone(&self) -> Self {
-        return Record { conditions: self.copy_conditions(), contiguous_damage: self.copy_damage() };
-    }
+
+/// The arrangement counts for every suffix of a [Record], see [Record::table]
+struct Table {
+    /// row-major, `groups + 1` per position
+    ways: Vec<u128>,
+    groups: usize,
+    /// `operational_before[i]`: known operational springs before position `i`
+    operational_before: Vec<usize>
 }
-impl Hash for Record {
-    fn hash<H>(&self, state: &mut H) where H: Hasher {
-        format!("{}", self).hash(state);
+impl Table {
+    #[inline]
+    fn get(&self, position: usize, group: usize) -> u128 {
+        return self.ways[position * (self.groups + 1) + group];
     }
 }
-impl Eq for Record {}
 
-#[memoize]
-// record DOES need to be mutable, memoization borks stuff
-fn mut_ok_subvariants(mut record: Record, depth: usize) -> u64 {
-    #[cfg(test)]
-    let colors = [
-        (255, 0, 0),
-        (255, 127, 0),
-        (255, 255, 0),
-        (0, 255, 0),
-        (0, 0, 255),
-        (75, 0, 130),
-        (148, 0, 211)
-    ];
-    #[cfg(test)]
-    let mut indentation = "".to_owned();
-    #[cfg(test)]
-    for d in 0..depth {
-        let (r, g, b) = colors[d%colors.len()];
-        indentation += &colorize(" |", r, g, b);
-    }
-    #[cfg(test)]
-    println!("{}Checking {}", indentation, record);
-    while record.conditions.len() > 0 && record.conditions[0] == Symbol::Operational { // strip leading ....
-        record.conditions.remove(0);
-    }
-    if record.conditions.len() == 0 {
-        if record.contiguous_damage.len() == 0 {
-            #[cfg(test)]
-            println!("{}> {}", indentation, colorize("ran out simultaneously", 0, 255, 0));
-            return 1;
-        } else {
-            #[cfg(test)]
-            println!("{}> ran out of damaged area", indentation);
-            return 0;
-        }
-    }
-    if record.contiguous_damage.len() == 0 {
-        for s in &record.conditions {
-            if *s == Symbol::Damaged {
-                return 0;
-            }
-        }
-        #[cfg(test)]
-        println!("{}> {}", indentation, colorize("ran out with '.' padding", 0, 255, 0));
-        return 1;
-    }
-    match record.conditions.remove(0) {
-        Symbol::Operational => panic!("Operational should be stripped"),
-        Symbol::Damaged => {
-            let damage_len = record.contiguous_damage[0] as usize;
-            if record.conditions.len() < damage_len-1 {
-                #[cfg(test)]
-                println!("{}> not enough conditions", indentation);
-                return 0;
-            }
-            // in this case conditions[0..damage_len-1] have to all be
-            // '#' or '?' (coerced to '#'); AKA none of them can be Operational
-            // the -1 is because we pre-remove the leading '#'
-            for idx in 0..damage_len-1 {
-                if record.conditions[idx] == Symbol::Operational {
-                    #[cfg(test)]
-                    println!("{}> found operational at idx {} in damaged area of length {}, record: {}", indentation, idx, damage_len, record);
-                    return 0;
-                }
-            }
-            // then, conditions[damage_len] has to either a) not exist or b) equal '.' or '?'
-            // and then check children
-            if record.conditions.len() == damage_len-1 {
-                if record.contiguous_damage.len() == 1 {
-                    #[cfg(test)]
-                    println!("{}> {}", indentation, colorize("perfect end length", 0, 255, 0));
-                    return 1;
-                } else {
-                    #[cfg(test)]
-                    println!("{}> not enough space for conditions", indentation);
-                    return 0;
-                }
-            }
-            if record.conditions[damage_len-1] == Symbol::Damaged {
-                #[cfg(test)]
-                println!("{}> damaged at end of desired damage length", indentation);
-                return 0;
-            }
-            let mut child = Record {
-                conditions: record.conditions[damage_len..record.conditions.len()].to_vec(),
-                contiguous_damage: record.contiguous_damage[1..record.contiguous_damage.len()].to_vec()
-            };
-            return child.mut_ok_subvariants(depth+1);
-        },
-        Symbol::Unknown => {
-            let mut rc0: Vec<Symbol> = vec![]; // operational variant, pre-strip
-            let mut rc1: Vec<Symbol> = vec![Symbol::Damaged];
+#[test]
+fn arrangement_count() {
+    assert_eq!(1, Record::parse(".##.?#??.#.?# 2,1,1,1").unwrap().arrangements());
+    assert_eq!(1, Record::parse("#??.### 1,1,3").unwrap().arrangements());
+    assert_eq!(1, Record::parse("???.### 1,1,3").unwrap().arrangements());
+    assert_eq!(4, Record::parse(".??..??...?##. 1,1,3").unwrap().arrangements());
+    assert_eq!(1, Record::parse("?#?#?#?#?#?#?#? 1,3,1,6").unwrap().arrangements());
+    assert_eq!(4, Record::parse("????.######..#####. 1,6,5").unwrap().arrangements());
+    assert_eq!(10, Record::parse("###???????? 3,2,1").unwrap().arrangements());
+    assert_eq!(10, Record::parse("?###???????? 3,2,1").unwrap().arrangements());
+}
 
-            rc0.extend(record.copy_conditions());
-            rc1.extend(record.copy_conditions());
+#[test]
+fn argument_count_expanded() {
+    assert_eq!(16, Record::parse("????.#...#... 4,1,1").unwrap().expand().arrangements());
+    assert_eq!(16384, Record::parse(".??..??...?##. 1,1,3").unwrap().expand().arrangements());
+    assert_eq!(1, Record::parse("?#?#?#?#?#?#?#? 1,3,1,6").unwrap().expand().arrangements());
+    assert_eq!(506250, Record::parse("?###???????? 3,2,1").unwrap().expand().arrangements());
+}
 
-            #[cfg(test)]
-            println!("{} {}", indentation, colorize(&format!("[ '.{}' ]", Record::variant_to_string(&rc0)), 255, 0, 255));
-            let mut r0 = Record { conditions: rc0, contiguous_damage: record.copy_damage() };
-            let c0 = r0.mut_ok_subvariants(depth+1);
-            #[cfg(test)]
-            println!("{} {}", indentation, colorize(&format!("[ '{}' ]", Record::variant_to_string(&rc1)), 255, 0, 255));
-            let mut r1 = Record { conditions: rc1, contiguous_damage: record.copy_damage() };
-            let c1 = r1.mut_ok_subvariants(depth+1);
-            return c0 + c1;
-        },
-    };
+#[test]
+fn parallel_sums() {
+    let records: Vec<Record> = Span::new("???.### 1,1,3
+.??..??...?##. 1,1,3
+?#?#?#?#?#?#?#? 1,3,1,6
+????.#...#... 4,1,1
+????.######..#####. 1,6,5
+?###???????? 3,2,1
+").parse_lines(Record::parse).unwrap();
+    assert_eq!(21, utils::parallel::map_with(3, &records, |r| r.arrangements()).into_iter().sum::<u128>());
+    assert_eq!(525152, utils::parallel::map_with(3, &records, |r| r.expand().arrangements()).into_iter().sum::<u128>());
 }
 
 #[test]
-fn arrangement_count() {
-    assert_eq!(1, Record::parse(".##.?#??.#.?# 2,1,1,1").arrangements());
-    assert_eq!(1, Record::parse("#??.### 1,1,3").arrangements());
-    assert_eq!(1, Record::parse("???.### 1,1,3").arrangements());
-    assert_eq!(4, Record::parse(".??..??...?##. 1,1,3").arrangements());
-    assert_eq!(1, Record::parse("?#?#?#?#?#?#?#? 1,3,1,6").arrangements());
-    assert_eq!(4, Record::parse("????.######..#####. 1,6,5").arrangements());
-    assert_eq!(10, Record::parse("###???????? 3,2,1").arrangements());
-    assert_eq!(10, Record::parse("?###???????? 3,2,1").arrangements());
+fn unfolding() {
+    let record = Record::parse("???.### 1,1,3").unwrap();
+    assert_eq!("???.###????.### 1,1,3,1,1,3", record.unfold(2).to_string());
+    assert!(record.expand() == record.unfold(5));
+    assert_eq!(1, record.unfold(1).arrangements());
+    assert_eq!(16384, Record::parse(".??..??...?##. 1,1,3").unwrap().unfold(5).arrangements());
+    assert_eq!(1, Record::parse(".??..??...?##. 1,1,3").unwrap().unfold(0).arrangements());
+    // well past what fits in a u64
+    assert!(Record::parse("???????????? 1").unwrap().unfold(20).arrangements() > u64::MAX as u128);
 }
 
 #[test]
-fn argument_count_expanded() {
-    assert_eq!(16, Record::parse("????.#...#... 4,1,1").expand().arrangements());
-    assert_eq!(16384, Record::parse(".??..??...?##. 1,1,3").expand().arrangements());
-    assert_eq!(1, Record::parse("?#?#?#?#?#?#?#? 1,3,1,6").expand().arrangements());
-    assert_eq!(506250, Record::parse("?###???????? 3,2,1").expand().arrangements());
+fn concrete_arrangements() {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    let record = Record::parse("?###???????? 3,2,1").unwrap();
+    let all = record.enumerate(100);
+    assert_eq!(10, all.len());
+    assert_eq!(".###....##.#", Record::variant_to_string(&all[0]));
+    assert_eq!(".###.##.#...", Record::variant_to_string(&all[9]));
+    assert_eq!(3, record.enumerate(3).len());
+    for arrangement in &all {
+        assert_eq!(Ok(()), record.verify(arrangement));
+    }
+    // all different
+    let mut strings: Vec<String> = all.iter().map(|a| Record::variant_to_string(a)).collect();
+    strings.sort();
+    strings.dedup();
+    assert_eq!(10, strings.len());
+
+    let mut rng = StdRng::seed_from_u64(12);
+    let unfolded = record.unfold(3);
+    for _ in 0..20 {
+        let arrangement = unfolded.sample(&mut rng).unwrap();
+        assert_eq!(Ok(()), unfolded.verify(&arrangement));
+    }
+    assert_eq!(None, Record::parse("#.# 3").unwrap().sample(&mut rng));
+}
+
+#[test]
+fn verifying() {
+    let record = Record::parse("???.### 1,1,3").unwrap();
+    let parse = |s: &str| s.chars().map(|c| Symbol::decode(c).unwrap()).collect::<Vec<Symbol>>();
+    assert_eq!(Ok(()), record.verify(&parse("#.#.###")));
+    assert_eq!(Err("6 springs instead of 7".to_owned()), record.verify(&parse("#.#.##")));
+    assert_eq!(Err("spring 2 is still unknown".to_owned()), record.verify(&parse("#?#.###")));
+    assert_eq!(Err("spring 5 should be `#`, not `.`".to_owned()), record.verify(&parse("#.#..##")));
+    assert_eq!(Err("damaged groups are [2, 3], not [1, 1, 3]".to_owned()), record.verify(&parse(".##.###")));
 }
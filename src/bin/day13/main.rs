@@ -1,31 +1,36 @@
 use std::fs;
 
+use utils::{debug, log::Level, log_enabled, parse::{ParseResult, ParseResultExt, Span}, trace};
+
 fn main() {
     println!("AOC 2023 Day 13");
+    utils::log::init();
 
     let contents = fs::read_to_string("src/bin/day13/input.txt").expect("Failed to read input");
-    let sum = calculate(&contents, 0);
+    let sum = calculate(&contents, 0).expect_in("src/bin/day13/input.txt");
     println!("Part 1: {}", sum);
-    let sum2 = calculate(&contents, 1);
+    let sum2 = calculate(&contents, 1).expect_in("src/bin/day13/input.txt");
     println!("Part 2: {}", sum2);
 }
 
-fn calculate(contents: &str, smudge_tolerance: usize) -> usize {
-    let fields = contents.trim().split("\n\n").map(|f| AshField::load(f));
+fn calculate(contents: &str, smudge_tolerance: usize) -> ParseResult<usize> {
+    let fields = Span::new(contents).trim().separated("\n\n", AshField::load)?;
     let mut sum = 0;
-    for (i, field) in fields.enumerate() {
+    for (i, field) in fields.iter().enumerate() {
         match field.get_vertical_mirror(smudge_tolerance) {
             Some(v) => sum += v,
-            None => println!("No vertical mirror in field {}", i)
+            None => debug!("No vertical mirror in field {}", i)
         }
         match field.get_horizontal_mirror(smudge_tolerance) {
             Some(v) => sum += 100*v,
-            None => println!("No horizontal mirror in field {}", i)
+            None => debug!("No horizontal mirror in field {}", i)
+        }
+        if log_enabled!(Level::Debug) {
+            field.visualize(smudge_tolerance);
+            println!();
         }
-        println!("");
-        field.visualize(smudge_tolerance);
     }
-    return sum;
+    return Ok(sum);
 }
 
 /// Smudge counter is only modified for 'true' return values
@@ -56,8 +61,12 @@ struct AshField {
     height: usize
 }
 impl AshField {
-    fn load(data: &str) -> AshField {
-        let rows: Vec<String> = data.trim().split("\n").map(|s| s.to_owned()).collect();
+    fn load<'a>(data: impl Into<Span<'a>>) -> ParseResult<AshField> {
+        let rows: Vec<String> = data.into()
+            .grid("`.` or `#`", |c| if c == '.' || c == '#' { Some(c) } else { None })?
+            .into_iter()
+            .map(String::from_iter)
+            .collect();
         let mut columns: Vec<String> = vec![];
         for column in 0..rows[0].len() {
             let mut c = "".to_owned();
@@ -68,23 +77,23 @@ impl AshField {
         }
         let width = columns.len();
         let height = rows.len();
-        return AshField { rows, columns, width, height };
+        return Ok(AshField { rows, columns, width, height });
     }
 
     fn get_horizontal_mirror(&self, smudge_tolerance: usize) -> Option<usize> {
-        println!("\n\nchecking mirror with height {}", self.height);
+        trace!("checking mirror with height {}", self.height);
         // Strategy:
         // Iterate through the rows and if rows[i] == rows[i+1], expand out from there to check
         'Outer: for i in 0..self.height-1 {
             let mut smudges = 0;
             if smudgeably_equal(&self.rows[i], &self.rows[i+1], &mut smudges, smudge_tolerance) {
-                println!("\n> found potential mirroring at {} <-> {}", i, i+1);
+                trace!("> found potential mirroring at {} <-> {}", i, i+1);
                 // check outwards
                 let max_offset = i.min(self.height-i-2);
-                println!("> min checked: {}, max checked: {}", i-max_offset, i+max_offset);
+                trace!("> min checked: {}, max checked: {}", i-max_offset, i+max_offset);
                 for offset in 1..=max_offset {
                     if !smudgeably_equal(&self.rows[i-offset], &self.rows[i+offset+1], &mut smudges, smudge_tolerance) {
-                        println!(">> failed cmp for {} <-> {}", i-offset, i+offset+1);
+                        trace!(">> failed cmp for {} <-> {}", i-offset, i+offset+1);
                         continue 'Outer;
                     }
                 }
@@ -165,7 +174,7 @@ fn horizontal_detection() {
 ##......#
 ..#.##.#.
 ..##..##.
-#.#.##.#.").get_horizontal_mirror(0), "0 lacks horizontal");
+#.#.##.#.").unwrap().get_horizontal_mirror(0), "0 lacks horizontal");
     assert_eq!(Some(4), AshField::load("
 #...##..#
 #....#..#
@@ -173,7 +182,7 @@ fn horizontal_detection() {
 #####.##.
 #####.##.
 ..##..###
-#....#..#").get_horizontal_mirror(0), "1 has horizontal(4)");
+#....#..#").unwrap().get_horizontal_mirror(0), "1 has horizontal(4)");
 }
 
 #[test]
@@ -185,7 +194,7 @@ fn vertical_detection() {
 ##......#
 ..#.##.#.
 ..##..##.
-#.#.##.#.").get_vertical_mirror(0), "0 has vertical(5)");
+#.#.##.#.").unwrap().get_vertical_mirror(0), "0 has vertical(5)");
     assert_eq!(None, AshField::load("
 #...##..#
 #....#..#
@@ -193,7 +202,7 @@ fn vertical_detection() {
 #####.##.
 #####.##.
 ..##..###
-#....#..#").get_vertical_mirror(0), "1 lacks vertical");
+#....#..#").unwrap().get_vertical_mirror(0), "1 lacks vertical");
 }
 
 #[test]
@@ -214,7 +223,7 @@ fn end_to_end_smudged() {
 #####.##.
 ..##..###
 #....#..#
-", 1));
+", 1).unwrap());
 }
 
 #[test]
@@ -235,7 +244,7 @@ fn end_to_end() {
 #####.##.
 ..##..###
 #....#..#
-", 0));
+", 0).unwrap());
     assert_eq!(709, calculate("
 #.##..##.
 ..#.##.#.
@@ -276,5 +285,5 @@ fn end_to_end() {
 ..#.##.#.
 ..##..##.
 #.#.##.#.
-", 0));
+", 0).unwrap());
 }
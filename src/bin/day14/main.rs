@@ -1,53 +1,52 @@
-use std::{fs, hash::{Hash, Hasher}};
+use std::{env, fs, collections::HashMap};
 
-use memoize::memoize;
+use char_enum_impl::char_enum;
+use utils::{info, parse::{ParseResult, ParseResultExt, Span}, viewer::{Render, Step, Viewer}};
 
 fn main() {
     println!("AOC 2023 Day 14");
+    utils::log::init();
 
     let contents = fs::read_to_string("src/bin/day14/input.txt").expect("Failed to read input");
-    let platform = Platform::parse(&contents);
-    println!("Part 1: {}", platform.load());
+    let platform = Platform::parse(&contents).expect_in("src/bin/day14/input.txt");
 
-    println!("Spinning a billion times");
-    let load2 = platform.a_billion_cycles().rotate_no_tilt().load();
-    println!("Part 2: {}", load2);
-/*
-    println!("\n\n");
-    debugging();*/
-}
+    // `--program=<tilts>` and `--repeats=<n>` run something other than part 2's spin cycle, and
+    // `--edge=<N|E|S|W>` measures the load on another support beam
+    let program = env::args().find_map(|a| a.strip_prefix("--program=").map(|p| p.to_owned())).unwrap_or("NWSE".to_owned());
+    let program = parse_program(&program).expect_in("--program");
+    let repeats: usize = env::args().find_map(|a| a.strip_prefix("--repeats=").and_then(|n| n.parse().ok())).unwrap_or(1_000_000_000);
+    let edge = env::args().find_map(|a| a.strip_prefix("--edge=").map(|p| p.to_owned())).unwrap_or("N".to_owned());
+    let edge = Span::new(&edge).parse_with("one of `N`, `E`, `S` or `W`", |s| Edge::try_decode(s.chars().next()?)).expect_in("--edge");
 
-#[allow(dead_code)]
-fn debugging() {
-    let platform = Platform::parse("
-O....#....
-O.OO#....#
-.....##...
-OO.#O....O
-.O.....O#.
-O.#..O.#.#
-..O..#O..O
-.......O..
-#....###..
-#OO..#....
-").initial_spin_cycle();
-    println!("After 1 cycle:");
-    platform.rotate_no_tilt().print();
+    // `--view` steps through the program one tilt at a time in the terminal instead
+    if env::args().any(|a| a == "--view") {
+        Viewer::new(Spinning { platform, program, tilts: 0 }).run().expect("Viewer failed");
+        return;
+    }
+
+    let mut tilted = platform.clone();
+    tilted.tilt(Edge::North);
+    println!("Part 1: {}", tilted.load(Edge::North));
+
+    info!("Running {} {} times", String::from_iter(program.iter().map(|e| e.encode())), repeats);
+    let mut spun = platform;
+    spun.run(&program, repeats);
+    println!("Part 2: {}", spun.load(edge));
 }
 
-#[derive(Clone, Copy, PartialEq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum Tile {
     RoundRock,
     CubeRock,
     Empty
 }
 impl Tile {
-    fn decode(chr: char) -> Tile {
+    fn decode(chr: char) -> Option<Tile> {
         match chr {
-            'O' => Tile::RoundRock,
-            '#' => Tile::CubeRock,
-            '.' => Tile::Empty,
-            _ => panic!("Invalid tile")
+            'O' => Some(Tile::RoundRock),
+            '#' => Some(Tile::CubeRock),
+            '.' => Some(Tile::Empty),
+            _ => None
         }
     }
 
@@ -60,215 +59,155 @@ impl Tile {
     }
 }
 
-#[memoize]
-fn spin_cycle(platform: Platform) -> Platform {
-    let mut p: Platform = platform;
-    for _ in 1..=4 {
-        p = p.spin();
-    }
-    return p;
+/// A side of the platform, to tilt towards or measure the load on
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[char_enum]
+enum Edge {
+    North = 'N',
+    East = 'E',
+    South = 'S',
+    West = 'W'
 }
 
-#[inline(always)]
-fn greatest_multiple(v: usize, less_than: usize) -> usize {
-    println!("Calculation greatest multiple of {} less than {}", v, less_than);
-    let count: usize = less_than / v;
-    return v * count;
+/// A sequence of tilts such as `NWSE`
+fn parse_program(program: &str) -> ParseResult<Vec<Edge>> {
+    return Span::new(program).trim().decode_chars("one of `N`, `E`, `S` or `W`", Edge::try_decode);
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 struct Platform {
-    /// columns[0] is left, columns[#len-1] is right
-    /// columns[0][0] is north, columns[0][#len-1] is south
-    columns: Vec<Vec<Tile>>,
-    southern_space: Vec<usize>, // index of the southern-most supported space (empty itself)
+    /// [row][column] indexed, row 0 is north
+    tiles: Vec<Vec<Tile>>,
+    width: usize,
     height: usize
 }
 impl Platform {
-    fn a_billion_cycles(self) -> Platform {
-        // cycle length seems to be 7 (aka can skip 7n steps for same result)
-        //const CYCLES: usize = 6 + 70;
-        const CYCLES: usize = 1_000_000_000;
-        let mut old: Vec<Platform> = vec![self.clone()];
-        let mut p: Platform = self;
-        let mut skip: Option<usize> = None;
-        for i in 0..CYCLES {
-            if let Some(s) = skip {
-                if s > 0 {
-                    skip = Some(s -1);
-                    continue;
-                }
-            }
-            if i == 0 {
-                p = p.initial_spin_cycle();
-
-                #[cfg(test)]
-                println!("\nAfter initial cycle:");
-                #[cfg(test)]
-                p.clone().rotate_no_tilt().print();
-            } else {
-                p = p.spin_cycle();
-                #[cfg(test)]
-                if i < 3 {
-                    println!("\nAfter {} cycles", i+1);
-                    p.clone().rotate_no_tilt().print();
-                }
-            }
-            
-            if let None = skip {
-                for (loop_start, o) in (&old).iter().enumerate() {
-                    if p == *o {
-                        let cycle_len = i-loop_start + 1;
-                        println!("i={}, LOOP starting at {}, with len {}", i, loop_start, cycle_len);
-                        let s = greatest_multiple(cycle_len, CYCLES - i);
-                        println!("Skipping {}", s);
-
-                        #[cfg(test)]
-                        println!("ME");
-                        #[cfg(test)]
-                        p.print();
-                        #[cfg(test)]
-                        println!("OTHER");
-                        #[cfg(test)]
-                        o.print();
-
-                        skip = Some(s);
-                    }
-                }
-                old.push(p.clone());
-            }
-        }
-        return p;
-    }
-
-    fn print(&self) {
-        for i in 0..self.height {
-            println!("{}", String::from_iter(self.columns.iter().map(|c| format!("{}", c[i].encode()))));
-        }
+    fn parse(data: &str) -> ParseResult<Platform> {
+        let tiles: Vec<Vec<Tile>> = Span::new(data).grid("`O`, `#` or `.`", Tile::decode)?;
+        let width = tiles[0].len();
+        let height = tiles.len();
+        return Ok(Platform { tiles, width, height });
     }
 
+    /// The `k`th tile in from `edge` along `line` (a column for north and south, a row for east
+    /// and west), as (row, column)
     #[inline(always)]
-    fn spin_cycle(self) -> Platform {
-        return spin_cycle(self);
-    }
-
-    fn initial_spin_cycle(self) -> Platform {
-        #[cfg(test)]
-        println!("\nbefore spin:");
-        #[cfg(test)]
-        self.print();
-
-        return self.spin().spin().spin(); // only 3 spins, since we are initialized with a north
-                                          // tilt
-    }
-
-    fn spin(&self) -> Platform {
-        let mut columns = vec![];
-        let mut southern_space = vec![];
-        for _ in 0..self.height {
-            columns.push(vec![]);
-            southern_space.push(0);
-        }
-        let height = self.columns.len();
-        let mut platform = Platform { columns, southern_space, height };
-        for column in &self.columns {
-            platform.insert(column.iter().rev().map(|t| *t).collect());
-        }
-        /*println!("\nSPUN");
-        platform.print();*/
-        return platform;
+    fn towards(&self, edge: Edge, line: usize, k: usize) -> (usize, usize) {
+        return match edge {
+            Edge::North => (k, line),
+            Edge::South => (self.height - 1 - k, line),
+            Edge::West => (line, k),
+            Edge::East => (line, self.width - 1 - k)
+        };
     }
 
-    fn rotate_no_tilt(&self) -> Platform {
-        let mut columns = vec![];
-        let mut southern_space = vec![];
-        for _ in 0..self.height {
-            columns.push(vec![]);
-            southern_space.push(0);
-        }
-        let height = self.columns.len();
-        let mut platform = Platform { columns, southern_space, height };
-        for column in &self.columns {
-            let row = column.iter().rev().map(|t| *t);
-            for (i, tile) in row.enumerate() {
-                platform.columns[i].push(tile);
+    /// Roll every round rock as far towards `edge` as it goes
+    fn tilt(&mut self, edge: Edge) {
+        let (lines, length) = match edge {
+            Edge::North | Edge::South => (self.width, self.height),
+            Edge::East | Edge::West => (self.height, self.width)
+        };
+        for line in 0..lines {
+            // how far in the next rock to arrive would stop
+            let mut free = 0;
+            for k in 0..length {
+                let (row, column) = self.towards(edge, line, k);
+                match self.tiles[row][column] {
+                    Tile::CubeRock => free = k + 1,
+                    Tile::RoundRock => {
+                        self.tiles[row][column] = Tile::Empty;
+                        let (row, column) = self.towards(edge, line, free);
+                        self.tiles[row][column] = Tile::RoundRock;
+                        free += 1;
+                    },
+                    Tile::Empty => {}
+                }
             }
         }
-        return platform;
-    }
-
-    fn parse(data: &str) -> Platform {
-        let lines: Vec<&str> = data.trim().split("\n").collect();
-        let width = lines[0].len();
-        let mut columns = vec![];
-        let mut southern_space = vec![];
-        for _ in 0..width {
-            columns.push(vec![]);
-            southern_space.push(0);
-        }
-
-        let mut platform = Platform { columns, southern_space, height: lines.len() };
-        for line in lines {
-            let tiles = line.chars().map(|c| Tile::decode(c)).collect();
-            platform.insert(tiles);
-        }
-        return platform;
     }
 
-    fn insert(&mut self, row: Vec<Tile>) {
-        assert_eq!(self.columns.len(), row.len());
-        for (i, tile) in row.into_iter().enumerate() {
-            match tile {
-                Tile::RoundRock => {
-                    self.columns[i].push(Tile::Empty); // if there's a supporting block right
-                                                       // below, then this will be immediately
-                                                       // overriden by a RoundRock
-                    self.columns[i][self.southern_space[i]] = Tile::RoundRock;
-                    self.southern_space[i] += 1;
-                },
-                Tile::CubeRock => {
-                    self.columns[i].push(Tile::CubeRock);
-                    self.southern_space[i] = self.columns[i].len();
-                },
-                Tile::Empty => self.columns[i].push(Tile::Empty)
+    /// Run the tilts in `program` in order, `repeats` times over. Once the platform comes back to
+    /// a state it was in before, the rest is skipped ahead by whole periods.
+    fn run(&mut self, program: &[Edge], repeats: usize) {
+        let mut seen: HashMap<Platform, usize> = HashMap::new();
+        let mut done = 0;
+        while done < repeats {
+            if let Some(previous) = seen.insert(self.clone(), done) {
+                let period = done - previous;
+                info!("Repeat {} is the same as repeat {}, skipping ahead in steps of {}", done, previous, period);
+                done = repeats - (repeats - done) % period;
+                seen.clear();
+                // the remainder is shorter than a period, so won't find another match
+                if done == repeats {
+                    break;
+                }
+            }
+            for edge in program {
+                self.tilt(*edge);
             }
+            done += 1;
         }
     }
 
-    fn load(&self) -> usize {
+    /// Each round rock weighs as many rows (or columns) as it is from the opposite side
+    fn load(&self, edge: Edge) -> usize {
         let mut load = 0;
-        for i in 0..self.height {
-            let factor = self.height - i;
-            for column in &self.columns {
-                if let Tile::RoundRock = column[i] {
-                    load += factor;
+        for row in 0..self.height {
+            for column in 0..self.width {
+                if let Tile::RoundRock = self.tiles[row][column] {
+                    load += match edge {
+                        Edge::North => self.height - row,
+                        Edge::South => row + 1,
+                        Edge::West => self.width - column,
+                        Edge::East => column + 1
+                    };
                 }
             }
         }
         return load;
     }
-}
-impl PartialEq for Platform {
-    fn eq(&self, other: &Platform) -> bool {
-        if self.height != other.height {
-            return false;
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        for row in &self.tiles {
+            out += &String::from_iter(row.iter().map(|t| t.encode()));
+            out += "\n";
         }
-        if self.columns != other.columns {
-            return false;
+        return out;
+    }
+}
+
+/// A platform going through a program one tilt at a time, for the viewer
+#[derive(Clone)]
+struct Spinning {
+    platform: Platform,
+    program: Vec<Edge>,
+    tilts: usize
+}
+impl Step for Spinning {
+    fn step(&mut self) -> bool {
+        if self.program.is_empty() {
+            return true;
         }
-        return true;
+        self.platform.tilt(self.program[self.tilts % self.program.len()]);
+        self.tilts += 1;
+        return false;
     }
 }
-impl Eq for Platform {}
-impl Hash for Platform {
-    fn hash<H>(&self, state: &mut H) where H: Hasher {
-        self.columns.hash(state);
+impl Render for Spinning {
+    fn render(&self) -> String {
+        return self.platform.render();
+    }
+
+    fn status(&self) -> String {
+        let len = self.program.len().max(1);
+        return format!("repeat {} tilt {}, load: {}", self.tilts / len, self.tilts % len, self.platform.load(Edge::North));
     }
 }
 
-#[test]
-fn load_calculation() {
-    assert_eq!(136, Platform::parse("
+#[cfg(test)]
+fn example() -> Platform {
+    return Platform::parse("
 O....#....
 O.OO#....#
 .....##...
@@ -279,38 +218,97 @@ O.#..O.#.#
 .......O..
 #....###..
 #OO..#....
-").load());
+").unwrap();
+}
+
+#[test]
+fn load_calculation() {
+    let mut platform = example();
+    platform.tilt(Edge::North);
+    assert_eq!(136, platform.load(Edge::North));
 }
 
 #[test]
 fn spin_load_calculation() {
-    assert_eq!(87, Platform::parse("
-O....#....
-O.OO#....#
-.....##...
-OO.#O....O
-.O.....O#.
-O.#..O.#.#
-..O..#O..O
-.......O..
-#....###..
-#OO..#....
-").initial_spin_cycle().rotate_no_tilt().load());
+    let mut platform = example();
+    platform.run(&parse_program("NWSE").unwrap(), 1);
+    assert_eq!(87, platform.load(Edge::North));
+    assert_eq!(platform.render(), "
+.....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#....
+".trim_start());
 }
 
 #[test]
-#[ignore = "Slow, takes about 7 seconds"]
 fn all_spin_load_calculation() {
-    assert_eq!(64, Platform::parse("
-O....#....
-O.OO#....#
-.....##...
-OO.#O....O
-.O.....O#.
-O.#..O.#.#
-..O..#O..O
-.......O..
-#....###..
-#OO..#....
-").a_billion_cycles().rotate_no_tilt().load());
+    let mut platform = example();
+    platform.run(&parse_program("NWSE").unwrap(), 1_000_000_000);
+    assert_eq!(64, platform.load(Edge::North));
+}
+
+#[test]
+fn tilting() {
+    let platform = Platform::parse("
+O.#.
+.O.O
+O..#
+").unwrap();
+    let tilted = |edge: Edge| {
+        let mut p = platform.clone();
+        p.tilt(edge);
+        return p.render();
+    };
+    assert_eq!("OO#O\nO...\n...#\n", tilted(Edge::North));
+    assert_eq!("..#.\nO..O\nOO.#\n", tilted(Edge::South));
+    assert_eq!("O.#.\nOO..\nO..#\n", tilted(Edge::West));
+    assert_eq!(".O#.\n..OO\n..O#\n", tilted(Edge::East));
+
+    // loads against each edge, untilted
+    assert_eq!(3 + 2 + 2 + 1, platform.load(Edge::North));
+    assert_eq!(1 + 2 + 2 + 3, platform.load(Edge::South));
+    assert_eq!(4 + 3 + 1 + 4, platform.load(Edge::West));
+    assert_eq!(1 + 2 + 4 + 1, platform.load(Edge::East));
+}
+
+#[test]
+fn programs() {
+    assert!(parse_program("NWSX").is_err());
+
+    // a program that undoes itself settles immediately, so any number of repeats is cheap
+    let mut platform = example();
+    platform.run(&parse_program("NS").unwrap(), usize::MAX);
+    let mut once = example();
+    once.run(&parse_program("NS").unwrap(), 1);
+    assert!(platform == once);
+
+    // cycle skipping gives the same as doing every repeat
+    for repeats in [0, 1, 5, 17, 40] {
+        let mut skipped = example();
+        skipped.run(&parse_program("NEENW").unwrap(), repeats);
+        let mut slow = example();
+        for _ in 0..repeats {
+            for edge in parse_program("NEENW").unwrap() {
+                slow.tilt(edge);
+            }
+        }
+        assert!(skipped == slow, "after {} repeats", repeats);
+    }
+}
+
+#[test]
+fn viewing() {
+    let mut viewer = Viewer::new(Spinning { platform: example(), program: parse_program("NWSE").unwrap(), tilts: 0 });
+    assert_eq!(104, viewer.current().platform.load(Edge::North));
+    viewer.jump_to(1);
+    assert_eq!(136, viewer.current().platform.load(Edge::North));
+    viewer.jump_to(4);
+    assert!(viewer.screen().contains("repeat 1 tilt 0, load: 87"));
 }
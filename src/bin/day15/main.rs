@@ -1,5 +1,6 @@
 use std::{fs, env};
 use arr_macro::arr;
+use utils::parse::{ParseResult, ParseResultExt, Span};
 
 fn main() {
     println!("AOC 2023 Day 15");
@@ -13,7 +14,7 @@ fn main() {
         println!("Hash of '{}' is {}", arg, hash(&arg));
     }
 
-    let hm: HashMap = HashMap::parse(&contents);
+    let hm: HashMap = HashMap::parse(&contents).expect_in("src/bin/day15/input.txt");
     println!("Part 2: {}", hm.focusing_power());
 }
 
@@ -53,14 +54,13 @@ enum Instruction {
     Add(String, u8)
 }
 impl Instruction {
-    fn decode(data: &str) -> Instruction {
+    fn decode(data: Span) -> ParseResult<Instruction> {
         match data.split_once("=") {
-            Some((label, focal_length_str)) => {
-                return Instruction::Add(label.to_owned(), focal_length_str.parse::<u8>().unwrap())
+            Ok((label, focal_length_str)) => {
+                return Ok(Instruction::Add(label.as_str().to_owned(), focal_length_str.parse::<u8>()?));
             },
-            None => {
-                assert!(data.ends_with("-"));
-                return Instruction::Remove(data.strip_suffix("-").unwrap().to_owned());
+            Err(_) => {
+                return Ok(Instruction::Remove(data.strip_suffix("-")?.as_str().to_owned()));
             }
         }
     }
@@ -80,11 +80,11 @@ struct HashMap {
     backing: [Vec<LensPair>; 256]
 }
 impl HashMap {
-    fn parse(instructions: &str) -> HashMap {
+    fn parse(instructions: &str) -> ParseResult<HashMap> {
         let mut hm = HashMap { backing: arr![Vec::new(); 256] };
-        let parsed = instructions.trim().split(",").map(|s| Instruction::decode(s));
-        parsed.for_each(|i| hm.handle(i));
-        return hm;
+        let parsed = Span::new(instructions).trim().separated(",", Instruction::decode)?;
+        parsed.into_iter().for_each(|i| hm.handle(i));
+        return Ok(hm);
     }
 
     fn handle(&mut self, instruction: Instruction) {
@@ -129,5 +129,5 @@ impl HashMap {
 
 #[test]
 fn instruction_parsing() {
-    assert_eq!(145, HashMap::parse("rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7").focusing_power());
+    assert_eq!(145, HashMap::parse("rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7").unwrap().focusing_power());
 }
@@ -1,41 +1,48 @@
-use std::fs;
+use std::{env, fs};
 
 use char_enum_impl::{char_enum, data_enum};
-use utils::{colorize, highlight};
+use utils::{colorize, debug, graph::tarjan, highlight, info, log::Level, log_enabled, parse::{ParseResult, ParseResultExt, Span}, viewer::{Render, Step, Viewer}};
 
 fn main() {
     println!("AOC 2023 Day 16");
+    utils::log::init();
 
     let contents = fs::read_to_string("src/bin/day16/input.txt").expect("Failed to read input");
 
     let mut field: Field;
     let test = false;
     if !test {
-        field = Field::parse(&contents, 0, 0, BeamDirection::East);
+        field = Field::parse(&contents, 0, 0, BeamDirection::East).expect_in("src/bin/day16/input.txt");
     } else {
         field = get_test();
     }
 
-    if test {
+    // `--view` steps through part 1's beams in the terminal instead
+    if env::args().any(|a| a == "--view") {
+        Viewer::new(field).run().expect("Viewer failed");
+        return;
+    }
+
+    if log_enabled!(Level::Debug) {
         field.print();
     }
 
     for step in 1_usize..=2000 {
         if field.step() {
-            println!("Exiting early on step {} because beams are done", step);
+            debug!("Exiting early on step {} because beams are done", step);
             break;
         }
         field.cleanup_outer();
 
-        if step % 10 == 0 && test {
-            println!("\nStep {}", step);
+        if step % 10 == 0 && log_enabled!(Level::Debug) {
+            debug!("Step {}", step);
             field.print();
         }
     }
     field.cleanup_outer();
 
-    if test {
-        println!("\nFinal");
+    if log_enabled!(Level::Debug) {
+        debug!("Final");
         field.print();
     }
 
@@ -45,51 +52,218 @@ fn main() {
     let width = field.width - 2;
     let height = field.height - 2;
 
-    let mut max = 0;
+    let graph = BeamGraph::parse(&contents).expect_in("src/bin/day16/input.txt");
+    info!("{} beam states in {} strongly connected components", width * height * 4, graph.energized.len());
+    let max = graph.most_energized();
+    println!("\n\nPart 2 final count: {}", max);
+}
 
-    println!("Iterating through {} starting locations", (width*2) + (height*2));
+/// Best energized count over every edge entry point, each simulated on its own thread.
+/// Slow, but obviously right: see [BeamGraph] for the fast way
+#[cfg(test)]
+fn most_energized(data: &str, width: usize, height: usize) -> usize {
+    info!("Iterating through {} starting locations", 2 * (width + height));
 
-    // top going down and bottom going up
-    for column in 0..width {
-        let mut field = Field::parse(&contents, 0, column, BeamDirection::South);
+    return utils::parallel::map(&entry_points(width, height), |(row, column, dir)| {
+        let mut field = Field::parse(data, *row, *column, *dir).unwrap();
         while !field.step() {}
         field.cleanup_outer();
-        max = max.max(field.count());
-
-        let mut field = Field::parse(&contents, height-1, column, BeamDirection::North);
-        while !field.step() {}
-        field.cleanup_outer();
-        max = max.max(field.count());
-    }
+        return field.count();
+    }).into_iter().max().unwrap_or(0);
+}
 
-    println!("Done with top and bottom entry");
+#[test]
+fn end_to_end() {
+    let mut field = get_test();
+    while !field.step() {}
+    field.cleanup_outer();
+    assert_eq!(46, field.count());
+}
 
+/// Every tile on the edge, heading inwards
+fn entry_points(width: usize, height: usize) -> Vec<(usize, usize, BeamDirection)> {
+    let mut starts: Vec<(usize, usize, BeamDirection)> = vec![];
+    // top going down and bottom going up
+    for column in 0..width {
+        starts.push((0, column, BeamDirection::South));
+        starts.push((height-1, column, BeamDirection::North));
+    }
     // left going east and right going west
     for row in 0..height {
-        let mut field = Field::parse(&contents, row, 0, BeamDirection::East);
-        while !field.step() {}
-        field.cleanup_outer();
-        max = max.max(field.count());
+        starts.push((row, 0, BeamDirection::East));
+        starts.push((row, width-1, BeamDirection::West));
+    }
+    return starts;
+}
 
-        let mut field = Field::parse(&contents, row, width-1, BeamDirection::West);
-        while !field.step() {}
-        field.cleanup_outer();
-        max = max.max(field.count());
+/// Beams as a directed graph: a node for each state (tile, direction the beam is travelling
+/// through it), with edges to the states the tile's mirror or splitter sends it on to.
+///
+/// A beam from any state lights up exactly the tiles of the states reachable from it, so the
+/// strongly connected components are condensed and each one's tiles are gathered from those
+/// downstream of it, once, rather than re-simulating for every entry point.
+struct BeamGraph {
+    width: usize,
+    height: usize,
+    /// indexed by [BeamGraph::state]
+    component_of: Vec<usize>,
+    /// tiles energized starting anywhere in each component
+    energized: Vec<usize>
+}
+impl BeamGraph {
+    fn parse(data: &str) -> ParseResult<BeamGraph> {
+        return Ok(BeamGraph::new(&parse_tiles(data)?));
     }
 
-    println!("\n\nPart 2 final count: {}", max);
+    fn new(tiles: &[Vec<Tile>]) -> BeamGraph {
+        let (width, height) = (tiles[0].len(), tiles.len());
+        let state = |row: usize, column: usize, dir: BeamDirection| (row * width + column) * 4 + dir.index();
+
+        let mut edges: Vec<Vec<usize>> = vec![vec![]; width * height * 4];
+        for row in 0..height {
+            for column in 0..width {
+                for dir in BeamDirection::values() {
+                    for out in tiles[row][column].outgoing(dir) {
+                        let (ocol, orow) = out.value();
+                        let (next_row, next_column) = (row as isize + orow, column as isize + ocol);
+                        if next_row < 0 || next_column < 0 || next_row >= height as isize || next_column >= width as isize {
+                            continue;
+                        }
+                        edges[state(row, column, dir)].push(state(next_row as usize, next_column as usize, out));
+                    }
+                }
+            }
+        }
+
+        let components = tarjan(&edges);
+        let mut component_of = vec![0; edges.len()];
+        for (idx, component) in components.iter().enumerate() {
+            for node in component {
+                component_of[*node] = idx;
+            }
+        }
+        // edges between components, and how many components still need each one's tiles
+        let mut downstream: Vec<Vec<usize>> = vec![vec![]; components.len()];
+        let mut waiting = vec![0; components.len()];
+        for (idx, component) in components.iter().enumerate() {
+            let mut next: Vec<usize> = component.iter()
+                .flat_map(|node| edges[*node].iter().map(|other| component_of[*other]))
+                .filter(|other| *other != idx)
+                .collect();
+            next.sort();
+            next.dedup();
+            for other in &next {
+                waiting[*other] += 1;
+            }
+            downstream[idx] = next;
+        }
+
+        // tarjan gives components with everything downstream of them already done, so each tile
+        // set only has to be kept until the last component upstream has used it
+        let words = (width * height + 63) / 64;
+        let mut tile_sets: Vec<Option<Vec<u64>>> = vec![None; components.len()];
+        let mut energized = vec![0; components.len()];
+        for (idx, component) in components.iter().enumerate() {
+            let mut tiles = vec![0_u64; words];
+            for node in component {
+                let tile = node / 4;
+                tiles[tile / 64] |= 1 << (tile % 64);
+            }
+            for other in &downstream[idx] {
+                for (word, theirs) in tiles.iter_mut().zip(tile_sets[*other].as_ref().unwrap()) {
+                    *word |= theirs;
+                }
+                waiting[*other] -= 1;
+                if waiting[*other] == 0 {
+                    tile_sets[*other] = None;
+                }
+            }
+            energized[idx] = tiles.iter().map(|word| word.count_ones() as usize).sum();
+            if waiting[idx] > 0 {
+                tile_sets[idx] = Some(tiles);
+            }
+        }
+
+        return BeamGraph { width, height, component_of, energized };
+    }
+
+    /// Tiles energized by a beam entering at (row, column) going in `dir`
+    fn energized_from(&self, row: usize, column: usize, dir: BeamDirection) -> usize {
+        return self.energized[self.component_of[(row * self.width + column) * 4 + dir.index()]];
+    }
+
+    fn most_energized(&self) -> usize {
+        return entry_points(self.width, self.height).into_iter()
+            .map(|(row, column, dir)| self.energized_from(row, column, dir))
+            .max().unwrap_or(0);
+    }
+}
+
+impl Step for Field {
+    fn step(&mut self) -> bool {
+        let done = Field::step(self);
+        self.cleanup_outer();
+        return done;
+    }
+}
+impl Render for Field {
+    fn render(&self) -> String {
+        return Field::render(self);
+    }
+
+    fn status(&self) -> String {
+        return format!("beams: {}, energized: {}", self.beams.len(), self.count());
+    }
 }
 
 #[test]
-fn end_to_end() {
-    let mut field = get_test();
-    while !field.step() {}
-    field.cleanup_outer();
-    assert_eq!(46, field.count());
+fn most_energized_entry() {
+    assert_eq!(51, most_energized(TEST_INPUT, 10, 10));
+    let graph = BeamGraph::parse(TEST_INPUT).unwrap();
+    assert_eq!(46, graph.energized_from(0, 0, BeamDirection::East));
+    assert_eq!(51, graph.energized_from(0, 3, BeamDirection::South));
+    assert_eq!(51, graph.most_energized());
+}
+
+#[test]
+fn viewing() {
+    let mut viewer = Viewer::new(get_test());
+    viewer.jump_to(1000);
+    assert_eq!(46, viewer.current().count());
+    assert!(viewer.screen().contains("(done) | beams: 0, energized: 46 |"));
+}
+
+#[test]
+fn graph_matches_simulation() {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    let mut rng = StdRng::seed_from_u64(16);
+    for _ in 0..5 {
+        let (width, height) = (rng.gen_range(5..25), rng.gen_range(5..25));
+        let mut data = String::new();
+        for _ in 0..height {
+            for _ in 0..width {
+                // mostly empty, like the real input
+                data.push(if rng.gen_bool(0.7) { '.' } else { ['/', '\\', '|', '-'][rng.gen_range(0..4)] });
+            }
+            data.push('\n');
+        }
+
+        let graph = BeamGraph::parse(&data).unwrap();
+        for (row, column, dir) in entry_points(width, height) {
+            let mut field = Field::parse(&data, row, column, dir).unwrap();
+            while !field.step() {}
+            field.cleanup_outer();
+            assert_eq!(field.count(), graph.energized_from(row, column, dir), "from ({}, {}) in\n{}", row, column, data);
+        }
+    }
 }
 
 fn get_test() -> Field {
-    return Field::parse(r".|...\....
+    return Field::parse(TEST_INPUT, 0, 0, BeamDirection::East).unwrap();
+}
+
+const TEST_INPUT: &str = r".|...\....
 |.-.\.....
 .....|-...
 ........|.
@@ -99,9 +273,9 @@ fn get_test() -> Field {
 .-.-/..|..
 .|....-|.\
 ..//.|....
-", 0, 0, BeamDirection::East);
-}
+";
 
+#[derive(Clone, Copy)]
 #[char_enum]
 enum Tile {
     Outer = '#',
@@ -111,8 +285,31 @@ enum Tile {
     SplitterVert = '|',
     SplitterHorz = '-'
 }
+impl Tile {
+    /// The directions a beam going `dir` through this tile leaves in
+    fn outgoing(&self, dir: BeamDirection) -> Vec<BeamDirection> {
+        return match self {
+            Tile::Outer => vec![],
+            Tile::Empty => vec![dir],
+            Tile::MirrorForwards => vec![dir.reflect_forward()],
+            Tile::MirrorBackwards => vec![dir.reflect_backward()],
+            Tile::SplitterVert if dir.is_horizontal() => vec![BeamDirection::North, BeamDirection::South],
+            Tile::SplitterHorz if dir.is_vertical() => vec![BeamDirection::East, BeamDirection::West],
+            Tile::SplitterVert | Tile::SplitterHorz => vec![dir]
+        };
+    }
+}
 
-#[derive(Copy, Clone)]
+/// The grid as given, without [Field]'s padding
+fn parse_tiles(data: &str) -> ParseResult<Vec<Vec<Tile>>> {
+    // `#` is reserved for the padding around the edge
+    return Span::new(data).grid("`.`, `/`, `\\`, `|` or `-`", |c| match Tile::try_decode(c) {
+        Some(Tile::Outer) | None => None,
+        tile => tile
+    });
+}
+
+#[derive(Copy, Clone, Debug)]
 #[data_enum((isize, isize))]
 enum BeamDirection {
     North = (0, -1),
@@ -121,6 +318,19 @@ enum BeamDirection {
     West = (-1, 0)
 }
 impl BeamDirection {
+    fn values() -> [BeamDirection; 4] {
+        return [BeamDirection::North, BeamDirection::South, BeamDirection::East, BeamDirection::West];
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            BeamDirection::North => 0,
+            BeamDirection::South => 1,
+            BeamDirection::East => 2,
+            BeamDirection::West => 3
+        }
+    }
+
     fn is_vertical(&self) -> bool {
         match self {
             BeamDirection::North | BeamDirection::South => true,
@@ -167,6 +377,7 @@ impl BeamDirection {
     }
 }
 
+#[derive(Clone)]
 struct Beam {
     direction: BeamDirection,
     row: usize,
@@ -180,10 +391,6 @@ impl Beam {
         self.column = ((self.column as isize) + ocol) as usize;
     }
 
-    fn clone(&self) -> Beam {
-        return Beam { direction: self.direction, row: self.row, column: self.column };
-    }
-
     #[inline]
     fn prev_row(&self) -> usize {
         let (_, orow) = self.direction.value();
@@ -197,6 +404,7 @@ impl Beam {
     }
 }
 
+#[derive(Clone)]
 struct Field {
     /// [row][column] indexed
     tiles: Vec<Vec<Tile>>,
@@ -211,13 +419,19 @@ struct Field {
     beams: Vec<Beam>
 }
 impl Field {
-    fn parse(data: &str, start_row: usize, start_column: usize, start_dir: BeamDirection) -> Field {
-        let mut lines: Vec<String> = data.trim().split("\n").map(|l| "#".to_owned() + l + "#").collect();
-
-        let width = lines[0].len();
-        lines.insert(0, "#".repeat(width));
-        lines.push("#".repeat(width));
-        let height = lines.len();
+    fn parse(data: &str, start_row: usize, start_column: usize, start_dir: BeamDirection) -> ParseResult<Field> {
+        let grid = parse_tiles(data)?;
+
+        let width = grid[0].len() + 2;
+        let mut tiles: Vec<Vec<Tile>> = vec![(0..width).map(|_| Tile::Outer).collect()];
+        for row in grid {
+            let mut padded = vec![Tile::Outer];
+            padded.extend(row);
+            padded.push(Tile::Outer);
+            tiles.push(padded);
+        }
+        tiles.push((0..width).map(|_| Tile::Outer).collect());
+        let height = tiles.len();
 
         let lit: Vec<Vec<bool>> = {
             let mut lit = vec![vec![false; width]; height];
@@ -226,16 +440,9 @@ impl Field {
             lit
         };
         let done: Vec<Vec<u8>> = vec![vec![0; width]; height];
-        let tiles = lines.into_iter()
-            .map(|s| s.chars()
-                .map(|c| Tile::decode(c))
-                .collect()
-            )
-            .collect();
-
         let beams = vec![Beam { direction: start_dir, row: start_row+1, column: start_column+1}];
 
-        return Field { tiles, lit, width, height, beams, done };
+        return Ok(Field { tiles, lit, width, height, beams, done });
     }
 
     /// should be called POST travel and before [Field::mark_done]
@@ -252,6 +459,12 @@ impl Field {
     }
 
     fn print(&self) {
+        print!("{}", self.render());
+    }
+
+    /// Lit tiles in green, with the beams' current tiles highlighted
+    fn render(&self) -> String {
+        let mut out = String::new();
         for row in 0..self.height {
             for column in 0..self.width {
                 let (r, g, b) = if self.lit[row][column] { (0, 255, 0) } else { (150, 0, 0) };
@@ -266,11 +479,11 @@ impl Field {
                     }
                     actually
                 };
-                let highlighted = highlight(&colorized, actually, 0, 120, 120);
-                print!("{}", highlighted);
+                out += &highlight(&colorized, actually, 0, 120, 120);
             }
-            println!("");
+            out += "\n";
         }
+        return out;
     }
 
     /// Move every beam one step
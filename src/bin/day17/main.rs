@@ -1,19 +1,21 @@
-use std::{fs, ops::Add};
+use std::{collections::HashSet, fs, ops::Add};
 use char_enum_impl::data_enum;
-use utils::{DijkstraData, DijkstraNode};
+use utils::{DijkstraData, DijkstraNode, colorize, debug, highlight, log::Level, log_enabled, parse::{ParseResult, ParseResultExt, Span}};
 
 fn main() {
     println!("AOC 2023 Day 17");
+    utils::log::init();
 
     let contents = fs::read_to_string("src/bin/day17/input.txt").expect("Failed to read input");
-    let map = Map::parse(&contents, false);
+    let map = Map::parse(&contents).expect_in("src/bin/day17/input.txt");
 
-    let best = map.best_distance();
-    println!("Part 1: {}", best);
-
-    let map2 = Map::parse(&contents, true);
-    let best2 = map2.best_distance();
-    println!("Part 2: {}", best2);
+    for (part, rules) in [(1, Rules::crucible(&map)), (2, Rules::ultra_crucible(&map))] {
+        let (best, path) = map.best_path(&rules).expect("Pathfinding failed");
+        println!("Part {}: {}", part, best);
+        if log_enabled!(Level::Debug) {
+            debug!("Part {} path:\n{}", part, map.render(&path));
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Hash, Copy, Clone)]
@@ -43,6 +45,16 @@ impl Direction {
         }
     }
 
+    #[cfg(test)]
+    fn arrow(&self) -> char {
+        match self {
+            Direction::North => '^',
+            Direction::South => 'v',
+            Direction::East => '>',
+            Direction::West => '<'
+        }
+    }
+
     #[inline(always)]
     fn values() -> Vec<Direction> {
         return vec![Direction::North, Direction::East, Direction::South, Direction::West];
@@ -58,62 +70,99 @@ impl Add<(u8, u8)> for Direction {
     }
 }
 
+/// How a crucible is allowed to move, and where it goes
+#[derive(Clone, Debug)]
+struct Rules {
+    /// blocks to move in a straight line before turning (or stopping at the target)
+    min_run: u8,
+    /// blocks it can move in a straight line before it has to turn
+    max_run: u8,
+    /// whether it can turn right around
+    reversing: bool,
+    /// extra heat lost on every change of direction
+    turn_cost: usize,
+    /// (row, column)
+    start: (usize, usize),
+    /// (row, column)
+    target: (usize, usize)
+}
+impl Rules {
+    /// Part 1: at most 3 blocks in a straight line, top left to bottom right
+    fn crucible(map: &Map) -> Rules {
+        return Rules {
+            min_run: 1,
+            max_run: 3,
+            reversing: false,
+            turn_cost: 0,
+            start: (0, 0),
+            target: (map.height as usize - 1, map.width as usize - 1)
+        };
+    }
+
+    /// Part 2: between 4 and 10 blocks in a straight line
+    fn ultra_crucible(map: &Map) -> Rules {
+        return Rules { min_run: 4, max_run: 10, ..Rules::crucible(map) };
+    }
+}
+
+/// The context for the search: a map and what a crucible may do on it
+struct Crucible<'a> {
+    map: &'a Map,
+    rules: &'a Rules
+}
+
 #[derive(PartialEq, Eq, Hash, Clone, Copy)]
 struct Node {
     row: u8,
     column: u8,
     travelling_direction: Direction,
+    /// 0 only at the start, where any direction is allowed
     straight_dist: u8,
 }
 impl Node {
-    fn maybe_adjacent(&self, map: &Map, direction: Direction) -> Option<(Self, usize)> {
-        if self.travelling_direction == direction { // forward
-
-            if (map.part_1() && self.straight_dist >= 3) || (map.part_2 && self.straight_dist >= 10) {
+    fn maybe_adjacent(&self, crucible: &Crucible, direction: Direction) -> Option<(Self, usize)> {
+        let (map, rules) = (crucible.map, crucible.rules);
+        let forward = self.straight_dist != 0 && self.travelling_direction == direction;
+        if forward {
+            if self.straight_dist >= rules.max_run {
                 return None;
             }
-
-            let (nr, nc) = direction + (self.row, self.column);
-            if nr < 0 || nc < 0 || nr >= map.height as i16 || nc >= map.width as i16 { // bounds check
+        } else if self.straight_dist != 0 {
+            let turning = direction == self.travelling_direction.left_turn() || direction == self.travelling_direction.right_turn();
+            if !turning && !rules.reversing {
                 return None;
             }
-            let (nr, nc) = (nr as u8, nc as u8);
-
-            return Some((
-                    Node {
-                        row: nr,
-                        column: nc,
-                        travelling_direction: direction,
-                        straight_dist: self.straight_dist + 1
-                    }, map.heat_loss[nr as usize][nc as usize] as usize));
-        } else if self.travelling_direction.right_turn() == direction
-            || self.travelling_direction.left_turn() == direction { // right or left
-
-            // must move at least 4 blocks before turning an Ultra Crucible
-            if map.part_2 && (self.straight_dist != 0 && self.straight_dist < 4) {
+            // must finish the current run before turning
+            if self.straight_dist < rules.min_run {
                 return None;
             }
+        }
 
-            let (nr, nc) = direction + (self.row, self.column);
-            if nr < 0 || nc < 0 || nr >= map.height as i16 || nc >= map.width as i16 { // bounds check
-                return None;
-            }
-            let (nr, nc) = (nr as u8, nc as u8);
-
-            return Some((
-                    Node {
-                        row: nr,
-                        column: nc,
-                        travelling_direction: direction,
-                        straight_dist: 1
-                    }, map.heat_loss[nr as usize][nc as usize] as usize));
-        } else { // cannot go backwards
+        let (nr, nc) = direction + (self.row, self.column);
+        if nr < 0 || nc < 0 || nr >= map.height as i16 || nc >= map.width as i16 { // bounds check
             return None;
         }
+        let (nr, nc) = (nr as u8, nc as u8);
+
+        let mut cost = map.heat_loss[nr as usize][nc as usize] as usize;
+        if !forward && self.straight_dist != 0 {
+            cost += rules.turn_cost;
+        }
+        return Some((
+                Node {
+                    row: nr,
+                    column: nc,
+                    travelling_direction: direction,
+                    straight_dist: if forward { self.straight_dist + 1 } else { 1 }
+                }, cost));
+    }
+
+    fn is_target(&self, rules: &Rules) -> bool {
+        return (self.row as usize, self.column as usize) == rules.target && self.straight_dist >= rules.min_run;
     }
 }
-impl DijkstraNode<Map> for Node {
-    fn get_connected(&self, context: &Map)-> Vec<(Self, usize)> {
+impl DijkstraNode<Crucible<'_>> for Node {
+    fn get_connected(&self, context: &Crucible)-> Vec<(Self, usize)> {
         let mut out = vec![];
         for direction in Direction::values() {
             if let Some(v) = self.maybe_adjacent(context, direction) {
@@ -128,59 +177,65 @@ struct Map {
     /// indexed [row][column]
     heat_loss: Vec<Vec<u8>>,
     width: u8,
-    height: u8,
-    part_2: bool
+    height: u8
 }
 impl Map {
-    fn parse(data: &str, part_2: bool) -> Map {
-        let lines: Vec<&str> = data.trim().split("\n").collect();
-        let width = lines[0].len() as u8;
-        let height = lines.len() as u8;
-
-        let heat_loss: Vec<Vec<u8>> = lines.iter()
-            .map(|l| l.chars()
-                .map(|c| c.to_digit(10).unwrap() as u8)
-                .collect()
-            )
-            .collect();
-        return Map { heat_loss, width, height, part_2 };
+    fn parse(data: &str) -> ParseResult<Map> {
+        let heat_loss: Vec<Vec<u8>> = Span::new(data).grid("a heat loss digit", |c| c.to_digit(10).map(|d| d as u8))?;
+        let width = heat_loss[0].len() as u8;
+        let height = heat_loss.len() as u8;
+        return Ok(Map { heat_loss, width, height });
     }
 
-    #[inline(always)]
-    fn part_1(&self) -> bool {
-        return !self.part_2;
+    #[cfg(test)]
+    fn best_distance(&self, rules: &Rules) -> usize {
+        return self.best_path(rules).expect("Pathfinding failed").0;
     }
 
-    fn best_distance(self) -> usize {
-        let target_row = self.height - 1;
-        let target_column = self.width - 1;
-        let initial = Node { row: 0, column: 0, travelling_direction: Direction::East, straight_dist: 0};
-        let part_1 = self.part_1();
-        /*
-        fn hlt(node: &Node) -> bool {
-            if node.row == target_row && node.column == target_column && (part_1 || node.straight_dist >= 4) {
-                return true;
+    /// The least heat lost getting from the start to the target, and the blocks entered on the
+    /// way as (row, column, direction moved to get there); the start itself isn't included
+    fn best_path(&self, rules: &Rules) -> Option<(usize, Vec<(usize, usize, Direction)>)> {
+        let initial = Node { row: rules.start.0 as u8, column: rules.start.1 as u8, travelling_direction: Direction::East, straight_dist: 0 };
+        let d = DijkstraData::dijkstra(initial, Crucible { map: self, rules }, |node: &Node| node.is_target(rules));
+        let (mut node, best) = d.best_distance.iter()
+            .filter(|(n, _)| n.is_target(rules))
+            .min_by_key(|(_, d)| **d)
+            .map(|(n, d)| (*n, *d))?;
+
+        let mut path = vec![];
+        while node != initial {
+            path.push((node.row as usize, node.column as usize, node.travelling_direction));
+            node = d.prev_in_chain[&node];
+        }
+        path.reverse();
+        return Some((best, path));
+    }
+
+    /// The heat loss grid, digits coloured from green (1) to red (9), with the path highlighted
+    fn render(&self, path: &[(usize, usize, Direction)]) -> String {
+        let on_path: HashSet<(usize, usize)> = path.iter().map(|(row, column, _)| (*row, *column)).collect();
+        let mut out = String::new();
+        for (row, line) in self.heat_loss.iter().enumerate() {
+            for (column, cost) in line.iter().enumerate() {
+                let heat = (*cost as u32).saturating_sub(1) * 255 / 8;
+                let digit = colorize(&cost.to_string(), heat as u8, 255 - heat as u8, 60);
+                out += &highlight(&digit, on_path.contains(&(row, column)), 40, 40, 40);
             }
-            return false;
-        }*/
-        let hlt = |node: &Node| {
-            return node.row == target_row && node.column == target_column && (part_1 || node.straight_dist >= 4);
-        };
-        let d = DijkstraData::dijkstra(initial, self, hlt);
-        let best = *d.best_distance.iter()
-            .filter(|(n, _)| n.row == target_row
-                        && n.column == target_column
-                        // for part 2, the crucible must go at least 4 on each leg
-                        && (part_1 || n.straight_dist >= 4))
-            .map(|(_, d)| d)
-            .min().expect("Pathfinding failed");
-        return best;
+            out += "\n";
+        }
+        return out;
     }
 }
 
-#[test]
-fn correct_distance() {
-    let map = Map::parse("
+/// The directions taken, as arrows, for comparing paths in tests
+#[cfg(test)]
+fn arrows(path: &[(usize, usize, Direction)]) -> String {
+    return path.iter().map(|(_, _, dir)| dir.arrow()).collect();
+}
+
+#[cfg(test)]
+fn example() -> &'static str {
+    return "
 2413432311323
 3215453535623
 3255245654254
@@ -194,35 +249,77 @@ fn correct_distance() {
 1224686865563
 2546548887735
 4322674655533
-", false);
-    assert_eq!(102, map.best_distance());
+";
+}
+
+#[test]
+fn correct_distance() {
+    let map = Map::parse(example()).unwrap();
+    assert_eq!(102, map.best_distance(&Rules::crucible(&map)));
 }
 
 #[test]
 fn correct_distance_2() {
+    let map = Map::parse(example()).unwrap();
+    assert_eq!(94, map.best_distance(&Rules::ultra_crucible(&map)));
+
     let map = Map::parse("
-2413432311323
-3215453535623
-3255245654254
-3446585845452
-4546657867536
-1438598798454
-4457876987766
-3637877979653
-4654967986887
-4564679986453
-1224686865563
-2546548887735
-4322674655533
-", true);
-    assert_eq!(94, map.best_distance());
+111111111111
+999999999991
+999999999991
+999999999991
+999999999991
+").unwrap();
+    assert_eq!(71, map.best_distance(&Rules::ultra_crucible(&map)));
+}
 
+#[test]
+fn custom_rules() {
     let map = Map::parse("
 111111111111
 999999999991
 999999999991
 999999999991
 999999999991
-", true);
-    assert_eq!(71, map.best_distance());
+").unwrap();
+    let ultra = Rules::ultra_crucible(&map);
+    let (best, path) = map.best_path(&ultra).unwrap();
+    assert_eq!(71, best);
+    assert_eq!(15, path.len());
+    assert_eq!(best, path.iter().map(|(r, c, _)| map.heat_loss[*r][*c] as usize).sum());
+    assert_eq!((4, 11), (path.last().unwrap().0, path.last().unwrap().1));
+
+    // the path is drawn over the grid
+    let render = map.render(&path);
+    assert_eq!(5, render.lines().count());
+    assert_eq!(path.len(), render.matches("\x1b[48;2;").count());
+
+    // with no limits on going straight, the cheapest way is along the ones
+    let free = Rules { min_run: 1, max_run: 20, ..Rules::crucible(&map) };
+    let (best, path) = map.best_path(&free).unwrap();
+    assert_eq!(15, best);
+    assert_eq!(">>>>>>>>>>>vvvv", arrows(&path));
+    // there's no way around turning once, so a turn cost just adds to it
+    let (best, path) = map.best_path(&Rules { turn_cost: 5, ..free.clone() }).unwrap();
+    assert_eq!(20, best);
+    assert_eq!(">>>>>>>>>>>vvvv", arrows(&path));
+
+    // but it can make a staircase of cheap blocks not worth following
+    let stairs = Map::parse("
+1199
+9119
+9811
+").unwrap();
+    let (best, path) = stairs.best_path(&Rules { min_run: 1, max_run: 20, ..Rules::crucible(&stairs) }).unwrap();
+    assert_eq!(5, best);
+    assert_eq!(">v>v>", arrows(&path));
+    let (best, path) = stairs.best_path(&Rules { min_run: 1, max_run: 20, turn_cost: 5, ..Rules::crucible(&stairs) }).unwrap();
+    assert_eq!(12 + 2 * 5, best);
+    assert_eq!(">vv>>", arrows(&path));
+
+    // backwards: from the bottom right to the top left, where reversing doesn't help
+    let back = Rules { start: (4, 11), target: (0, 0), reversing: true, ..free };
+    let (best, path) = map.best_path(&back).unwrap();
+    assert_eq!(15, best);
+    assert_eq!("^^^^<<<<<<<<<<<", arrows(&path));
 }
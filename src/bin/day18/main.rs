@@ -1,9 +1,11 @@
-use std::fs;
+use std::{env, fs};
 
 use char_enum_impl::char_enum;
+use utils::{info, parse::{ParseResult, ParseResultExt, Span}, polygon::{Direction, Polygon}, warn};
 
 fn main() {
     println!("AOC 2023 Day 18");
+    utils::log::init();
 
     let contents: String;
     if true {
@@ -11,78 +13,139 @@ fn main() {
     } else {
         contents = example();
     }
-    //Vec<(Moves, u8)> 
-    let data: Vec<(Moves, usize)> = contents.trim().split("\n")
-        .map(|line| line
-            .strip_suffix(")").unwrap()
-            .split_once(" (#").unwrap()
-        ).map(|(_, color)| color.to_owned())
-        .map(|color| (color[0..5].to_owned(), color.chars().nth(5).unwrap()))
-        .map(|(enc_dist, dir)| (Moves::decode(dir), usize::from_str_radix(&enc_dist, 16).unwrap()))
-        .collect();
-
-    /*
-     * Algorithm, thanks to the lovely people on r/adventofcode
-     * (No way do I know about Pick's theorem and the shoelace formula)
-     *
-     * specifically helpful posts/comments:
-     * u/Boojum: https://www.reddit.com/r/adventofcode/comments/18l2tap/comment/kdv5bzi/
-     * u/SEGV_AGAIN: https://www.reddit.com/r/adventofcode/comments/18l8mao/2023_day_18_intuition_for_why_spoiler_alone/
-     *
-     * total_area = inner_area + (perimeter/2) + 1
-     *
-     * this works b/c Pick's theorem calculates the area of a shape with edges *centered* in the
-     * perimeter trenches
-     *
-     * this results in perimeter trenches having the following extra 'outside' area
-     * 1/2 in edge pieces, as such:
-     * X|#
-     *
-     * X|#
-     *
-     * 3/4 in convex corners, as such:
-     * X X
-     *  +-
-     * X|#
-     *
-     * 1/4 in concave corners, as such
-     * X|#
-     * -+
-     * # #
-     *
-     * convave and convex corners balance out, resulting in an average 1/2 contribution from each
-     * and there are 4 convex corners (see a rectangle), so that adds another 1 (2 units of area
-     * are already included in the perimeter/2 calculation)
-     */
-    
-    /*
-     * Shoelace (triangle variant)
-     * for every edge from (x1, y1) to (x2, y2)
-     * add (x1*y2 - x2*y1)/2 to the total
-     */
-
-    let mut sum: isize = 0; // due to some fun little shoelace-shenannigans, this could (probably)
-                            // become negative at some point in time
-                            // NOTE: to avoid floating point misery, divide TOTAL_AREA in half at the very end
-                            // formula becomes: (sum + perimeter + 2) / 2
-    let mut perimeter: usize = 0;
-
-    // calculate all the edges
-    let (mut x1, mut y1) = (0, 0);
-    for (dir, dist) in data {
-        perimeter += dist;
-
-        let (x2, y2) = dir.offset(x1, y1, dist as isize);
-        sum += x1*y2 - x2*y1;
-
-        // prepare for next iter
-        x1 = x2;
-        y1 = y2;
+    let span = Span::new(&contents);
+    let plan: Vec<(Moves, usize)> = span.parse_lines(parse_plain).expect_in("src/bin/day18/input.txt");
+    println!("Part 1: {}", lagoon_size(&plan));
+    let data: Vec<(Moves, usize)> = span.parse_lines(parse_color).expect_in("src/bin/day18/input.txt");
+    println!("Part 2: {}", lagoon_size(&data));
+
+    // `--svg1=<file>` and `--svg2=<file>` draw the plan as read for part 1 or part 2
+    let colours: Vec<String> = span.parse_lines(parse_hex).expect_in("src/bin/day18/input.txt");
+    for (flag, plan) in [("--svg1=", &plan), ("--svg2=", &data)] {
+        if let Some(path) = env::args().find_map(|a| a.strip_prefix(flag).map(|p| p.to_owned())) {
+            fs::write(&path, to_svg(plan, &colours, 800.0)).expect("Failed to write SVG file");
+            info!("Wrote {}", path);
+        }
+    }
+}
+
+/*
+ * Algorithm, thanks to the lovely people on r/adventofcode
+ * (No way do I know about Pick's theorem and the shoelace formula)
+ *
+ * specifically helpful posts/comments:
+ * u/Boojum: https://www.reddit.com/r/adventofcode/comments/18l2tap/comment/kdv5bzi/
+ * u/SEGV_AGAIN: https://www.reddit.com/r/adventofcode/comments/18l8mao/2023_day_18_intuition_for_why_spoiler_alone/
+ *
+ * total_area = inner_area + (perimeter/2) + 1
+ *
+ * this works b/c Pick's theorem calculates the area of a shape with edges *centered* in the
+ * perimeter trenches
+ *
+ * this results in perimeter trenches having the following extra 'outside' area
+ * 1/2 in edge pieces, as such:
+ * X|#
+ *
+ * X|#
+ *
+ * 3/4 in convex corners, as such:
+ * X X
+ *  +-
+ * X|#
+ *
+ * 1/4 in concave corners, as such
+ * X|#
+ * -+
+ * # #
+ *
+ * convave and convex corners balance out, resulting in an average 1/2 contribution from each
+ * and there are 4 convex corners (see a rectangle), so that adds another 1 (2 units of area
+ * are already included in the perimeter/2 calculation)
+ *
+ * The shoelace sum and Pick's theorem live in utils::polygon
+ */
+fn lagoon_size(plan: &[(Moves, usize)]) -> u64 {
+    let polygon = Polygon::from_moves(plan.iter().map(|(dir, dist)| (dir.direction(), *dist as i64)));
+    if let Some((a, b)) = polygon.self_intersection() {
+        warn!("The trench crosses itself at steps {} and {}", a + 1, b + 1);
     }
+    return polygon.area_with_boundary();
+}
+
+/// The move as written: `R 6 (#70c710)`
+fn parse_plain(line: Span) -> ParseResult<(Moves, usize)> {
+    let mut parts = line.trim().split_whitespace();
+    let dir = parts.next().unwrap().parse_with("one of `U`, `D`, `L` or `R`", |s| match s {
+        "U" => Some(Moves::Up),
+        "D" => Some(Moves::Down),
+        "L" => Some(Moves::Left),
+        "R" => Some(Moves::Right),
+        _ => None
+    })?;
+    let dist = match parts.next() {
+        Some(dist) => dist.parse()?,
+        None => return Err(line.trim().error("a distance after the direction"))
+    };
+    return Ok((dir, dist));
+}
+
+/// Decode the real move hidden in the `(#rrrrrd)` colour of a dig plan line
+fn parse_color(line: Span) -> ParseResult<(Moves, usize)> {
+    let (_, color) = line.trim().split_once(" (#")?;
+    let color = color.strip_suffix(")")?;
+    return color.parse_with("five hex digits of distance and a direction from 0 to 3", |s| {
+        if s.len() != 6 {
+            return None;
+        }
+        let dist = usize::from_str_radix(s.get(0..5)?, 16).ok()?;
+        let dir = Moves::try_decode(s[5..].chars().next()?)?;
+        return Some((dir, dist));
+    });
+}
 
-    let sum = sum as usize;
-    let total = (sum + perimeter + 2) / 2;
-    println!("Total area: {}", total);
+/// The `#rrggbb` colour at the end of a dig plan line, for drawing
+fn parse_hex(line: Span) -> ParseResult<String> {
+    let (_, color) = line.trim().split_once(" (")?;
+    let color = color.strip_suffix(")")?;
+    return color.parse_with("a `#rrggbb` colour", |s| {
+        let hex = s.strip_prefix('#')?;
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        return Some(s.to_owned());
+    });
+}
+
+/// Draw the trench as a `size` pixel (on its longer side) SVG, each segment in its listed colour
+/// over the filled lagoon. Corners are scaled down to fit, so part 2's plan gets hairline edges.
+fn to_svg(plan: &[(Moves, usize)], colours: &[String], size: f64) -> String {
+    let polygon = Polygon::from_moves(plan.iter().map(|(dir, dist)| (dir.direction(), *dist as i64)));
+    let (min_x, max_x) = (polygon.vertices.iter().map(|v| v.0).min().unwrap(), polygon.vertices.iter().map(|v| v.0).max().unwrap());
+    let (min_y, max_y) = (polygon.vertices.iter().map(|v| v.1).min().unwrap(), polygon.vertices.iter().map(|v| v.1).max().unwrap());
+    // one extra tile each way, since the trench is dug around the corners
+    let (tiles_x, tiles_y) = ((max_x - min_x + 1) as f64, (max_y - min_y + 1) as f64);
+    let scale = size / tiles_x.max(tiles_y);
+    let point = |(x, y): (i64, i64)| ((x - min_x) as f64 * scale + scale / 2.0, (y - min_y) as f64 * scale + scale / 2.0);
+    let stroke = scale.max(1.0);
+
+    let (width, height) = (tiles_x * scale, tiles_y * scale);
+    let mut out = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0:.0}\" height=\"{1:.0}\" viewBox=\"0 0 {0:.1} {1:.1}\">\n", width, height);
+    out += &format!("<rect width=\"{:.1}\" height=\"{:.1}\" fill=\"white\"/>\n", width, height);
+    let corners: Vec<String> = polygon.vertices.iter().map(|v| {
+        let (x, y) = point(*v);
+        return format!("{:.1},{:.1}", x, y);
+    }).collect();
+    out += &format!("<polygon points=\"{}\" fill=\"#d8d8d8\"/>\n", corners.join(" "));
+    let mut corner = (0, 0);
+    for ((dir, dist), colour) in plan.iter().zip(colours) {
+        let next = dir.direction().offset(corner, *dist as i64);
+        let ((x1, y1), (x2, y2)) = (point(corner), point(next));
+        out += &format!("<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"{:.1}\" stroke-linecap=\"square\"/>\n",
+            x1, y1, x2, y2, colour, stroke);
+        corner = next;
+    }
+    out += "</svg>\n";
+    return out;
 }
 
 fn example() -> String {
@@ -112,12 +175,40 @@ enum Moves {
     Right = '0'
 }
 impl Moves {
-    fn offset(&self, x: isize, y: isize, dist: isize) -> (isize, isize) {
+    fn direction(&self) -> Direction {
         match self {
-            Moves::Up => (x, y-dist),
-            Moves::Down => (x, y+dist),
-            Moves::Left => (x-dist, y),
-            Moves::Right => (x+dist, y)
+            Moves::Up => Direction::Up,
+            Moves::Down => Direction::Down,
+            Moves::Left => Direction::Left,
+            Moves::Right => Direction::Right
         }
     }
 }
+
+#[test]
+fn lagoon_sizes() {
+    let contents = example();
+    let span = Span::new(&contents);
+    assert_eq!(62, lagoon_size(&span.parse_lines(parse_plain).unwrap()));
+    assert_eq!(952408144115, lagoon_size(&span.parse_lines(parse_color).unwrap()));
+}
+
+#[test]
+fn plan_drawing() {
+    let contents = example();
+    let span = Span::new(&contents);
+    let colours = span.parse_lines(parse_hex).unwrap();
+    assert_eq!("#70c710", colours[0]);
+
+    // part 1: 7x10 tiles, 80 pixels per tile
+    let svg = to_svg(&span.parse_lines(parse_plain).unwrap(), &colours, 800.0);
+    assert!(svg.contains("width=\"560\" height=\"800\""));
+    assert_eq!(14, svg.matches("<line").count());
+    assert!(svg.contains("<line x1=\"40.0\" y1=\"40.0\" x2=\"520.0\" y2=\"40.0\" stroke=\"#70c710\" stroke-width=\"80.0\""));
+    assert_eq!(1, svg.matches("<polygon").count());
+
+    // part 2 is far larger than the picture, so every tile is squashed under one pixel
+    let svg = to_svg(&span.parse_lines(parse_color).unwrap(), &colours, 800.0);
+    assert!(svg.contains("height=\"800\""));
+    assert_eq!(14, svg.matches("stroke-width=\"1.0\"").count());
+}
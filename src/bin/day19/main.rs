@@ -1,148 +1,527 @@
-use std::{fs, collections::HashMap};
+use std::{env, fs, collections::{HashMap, HashSet}, fmt::{self, Display, Formatter}};
 
 use char_enum_impl::char_enum;
+use utils::{info, parse::{ParseResult, ParseResultExt, Span}, trace};
 
 fn main() {
     println!("AOC 2023 Day 19");
+    utils::log::init();
 
     let contents = fs::read_to_string("src/bin/day19/input.txt").expect("Failed to read input");
-    let sum = process(&contents);
+
+    let (workflows, _) = Span::new(&contents).trim().split_once("\n\n").expect_in("src/bin/day19/input.txt");
+    let program = Program::compile(workflows).expect_in("src/bin/day19/input.txt");
+    for id in program.unreachable() {
+        info!("Workflow {} can't be reached", program.names[id]);
+    }
+    // `--ratings=x=1-8000,m=0-10` analyses the workflows for other ratings than the puzzle's
+    let ratings = match env::args().find_map(|a| a.strip_prefix("--ratings=").map(|p| p.to_owned())) {
+        Some(ratings) => Hypercube::parse(Span::new(&ratings), Hypercube::initial()).expect_in("--ratings"),
+        None => Hypercube::initial()
+    };
+    for (id, idx) in program.dead_rules(ratings) {
+        info!("Rule {} of workflow {} never matches", idx + 1, program.names[id]);
+    }
+    for (id, fate) in program.fates(ratings).into_iter().enumerate() {
+        if let Some(accepts) = fate {
+            info!("Workflow {} always {}", program.names[id], if accepts { "accepts" } else { "rejects" });
+        }
+    }
+    let simplified = program.simplified(ratings);
+    info!("Simplified from {} rules in {} workflows to {} rules in {} workflows",
+        program.size(), program.workflows.len(), simplified.size(), simplified.workflows.len());
+    if let Some(path) = env::args().find_map(|a| a.strip_prefix("--dot=").map(|p| p.to_owned())) {
+        fs::write(&path, simplified.to_dot()).expect("Failed to write DOT file");
+        println!("Wrote the simplified workflows to {}", path);
+    }
+    let sum = process(&contents).expect_in("src/bin/day19/input.txt");
     println!("Part 1: {}", sum);
 
-    let sum2 = process_hyper(&contents);
+    let sum2 = process_hyper(&contents).expect_in("src/bin/day19/input.txt");
     println!("Part 2: {}", sum2);
+
+    // --within=x=1-2000,m=500 shows how the parts in that box (and the `--ratings`) get accepted
+    if let Some(within) = env::args().find_map(|a| a.strip_prefix("--within=").map(|p| p.to_owned())) {
+        let space = Hypercube::parse(Span::new(&within), ratings).expect_in("--within");
+        let boxes = program.accepted_boxes(space);
+        for accepted in &boxes {
+            println!("{} ({} parts): {}", accepted.cube, accepted.cube.volume(), program.path_text(&accepted.path));
+        }
+        println!("{} of {} parts in {} are accepted", program.accepted_volume(space), space.volume(), space);
+    }
 }
 
-fn parse_workflows(data: &str) -> HashMap<String, Workflow> {
-    let workflows_iter = data.trim().split("\n").map(|l| Workflow::parse(l));
-    let mut workflows: HashMap<String, Workflow> = HashMap::new();
-    for (k, v) in workflows_iter {
-        workflows.insert(k, v);
+fn process(data: &str) -> ParseResult<usize> {
+    let (workflows, xmases) = Span::new(data).trim().split_once("\n\n")?;
+    let program = Program::compile(workflows)?;
+    let xmases: Vec<Xmas> = xmases.parse_lines(Xmas::parse)?;
+    return Ok(program.accepted_rating(&xmases));
+}
+
+fn process_hyper(data: &str) -> ParseResult<usize> {
+    let (workflows, _) = Span::new(data).trim().split_once("\n\n")?;
+    let program = Program::compile(workflows)?;
+    return Ok(program.accepted_volume(Hypercube::initial()));
+}
+
+/// Where a rule sends a part
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Target {
+    Accept,
+    Reject,
+    Workflow(usize)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Condition {
+    Always,
+    Greater(Variable, usize),
+    Less(Variable, usize)
+}
+impl Condition {
+    #[inline]
+    fn matches(&self, xmas: &Xmas) -> bool {
+        return match self {
+            Condition::Always => true,
+            Condition::Greater(var, bound) => xmas.get(var) > *bound,
+            Condition::Less(var, bound) => xmas.get(var) < *bound
+        };
+    }
+
+    /// The parts of `cube` that match, and those that don't
+    fn divide(&self, cube: Hypercube) -> (Hypercube, Hypercube) {
+        return match *self {
+            Condition::Always => (cube, Hypercube::EMPTY),
+            Condition::Greater(_, usize::MAX) | Condition::Less(_, 0) => (Hypercube::EMPTY, cube),
+            Condition::Greater(var, bound) => {
+                let (fail, pass) = cube.split(&var, bound);
+                (pass, fail)
+            },
+            Condition::Less(var, bound) => cube.split(&var, bound - 1)
+        };
+    }
+}
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        return match self {
+            Condition::Always => Ok(()),
+            Condition::Greater(var, bound) => write!(f, "{}>{}", var.encode(), bound),
+            Condition::Less(var, bound) => write!(f, "{}<{}", var.encode(), bound)
+        };
     }
-    return workflows;
 }
 
-fn process(data: &str) -> usize {
-    let (workflows, xmases) = data.trim().split_once("\n\n").unwrap();
-    let workflows = parse_workflows(workflows);
-    let mut xmases: Vec<(String, Xmas)> = xmases.trim()
-        .split("\n")
-        .map(|l| ("in".to_owned(), Xmas::parse(l)))
-        .collect();
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Rule {
+    condition: Condition,
+    target: Target
+}
 
-    let mut sum: usize = 0;
+/// Lowest and highest rating of every variable in the puzzle
+const RATING_RANGE: (usize, usize) = (1, 4000);
+
+/// The workflows with their names interned, so running a part never touches a string
+struct Program {
+    /// indexed by workflow id
+    names: Vec<String>,
+    /// indexed by workflow id
+    workflows: Vec<Vec<Rule>>,
+    /// id of `in`
+    start: usize
+}
+impl Program {
+    fn compile(data: Span) -> ParseResult<Program> {
+        let mut names: Vec<String> = vec![];
+        let mut parsed: Vec<(Span, Workflow)> = vec![];
+        for line in data.trim().lines() {
+            let (name, workflow) = Workflow::parse(line)?;
+            if names.contains(&name) {
+                return Err(line.error("a workflow with a new name"));
+            }
+            names.push(name);
+            parsed.push((line, workflow));
+        }
+        let ids: HashMap<&str, usize> = names.iter().enumerate().map(|(id, name)| (name.as_str(), id)).collect();
+        let start = *ids.get("in").ok_or_else(|| data.trim().error("a workflow named `in`"))?;
+
+        let mut workflows: Vec<Vec<Rule>> = vec![];
+        for (line, workflow) in &parsed {
+            let mut rules: Vec<Rule> = vec![];
+            for step in &workflow.steps {
+                let (condition, target) = match step {
+                    Step::Unconditional(target) => (Condition::Always, target),
+                    Step::GreaterThan(var, bound, target) => (Condition::Greater(*var, *bound), target),
+                    Step::LessThan(var, bound, target) => (Condition::Less(*var, *bound), target)
+                };
+                let target = match target.as_str() {
+                    "A" => Target::Accept,
+                    "R" => Target::Reject,
+                    name => Target::Workflow(*ids.get(name)
+                        .ok_or_else(|| line.error(format!("a known workflow instead of `{}`", name)))?)
+                };
+                rules.push(Rule { condition, target });
+            }
+            workflows.push(rules);
+        }
 
-    while xmases.len() > 0 {
-        let (workflow_id, xmas) = xmases.pop().unwrap();
-        let workflow = workflows.get(&workflow_id).unwrap();
-        let target: &str = &workflow.process(&xmas);
-        match target {
-            "A" => sum += xmas.rating(),
-            "R" => {},
-            _ => xmases.push((target.to_owned(), xmas))
+        let program = Program { names, workflows, start };
+        if let Some(id) = program.cycle() {
+            return Err(parsed[id].0.error("workflows that don't send parts round in circles"));
         }
+        return Ok(program);
     }
 
-    return sum;
-}
+    /// A workflow that can lead back to itself, if any
+    fn cycle(&self) -> Option<usize> {
+        // 0: unvisited, 1: on the current path, 2: done
+        let mut state: Vec<u8> = vec![0; self.workflows.len()];
+        fn visit(program: &Program, id: usize, state: &mut Vec<u8>) -> Option<usize> {
+            if state[id] == 1 {
+                return Some(id);
+            }
+            if state[id] == 2 {
+                return None;
+            }
+            state[id] = 1;
+            for rule in &program.workflows[id] {
+                if let Target::Workflow(next) = rule.target {
+                    if let Some(found) = visit(program, next, state) {
+                        return Some(found);
+                    }
+                }
+            }
+            state[id] = 2;
+            return None;
+        }
+        return (0..self.workflows.len()).find_map(|id| visit(self, id, &mut state));
+    }
+
+    /// Whether `xmas` ends up accepted
+    fn accepts(&self, xmas: &Xmas) -> bool {
+        let mut current = self.start;
+        loop {
+            let rule = self.workflows[current].iter().find(|r| r.condition.matches(xmas)).expect("Workflows end unconditionally");
+            match rule.target {
+                Target::Accept => return true,
+                Target::Reject => return false,
+                Target::Workflow(next) => current = next
+            }
+        }
+    }
 
-fn process_hyper(data: &str) -> usize {
-    let mut sum: usize = 0;
+    /// Total rating of the accepted parts
+    fn accepted_rating(&self, xmases: &[Xmas]) -> usize {
+        return xmases.iter().filter(|x| self.accepts(x)).map(|x| x.rating()).sum();
+    }
 
-    let (workflows, _) = data.trim().split_once("\n\n").unwrap();
-    let workflows = parse_workflows(workflows);
+    /// Split `space` into the boxes of parts that get accepted, each with the rules that accept it.
+    /// The boxes don't overlap. Running this on a small box (even a single part) shows which
+    /// paths accept which bits of it.
+    fn accepted_boxes(&self, space: Hypercube) -> Vec<AcceptedBox> {
+        let mut out: Vec<AcceptedBox> = vec![];
+        self.collect_accepted(self.start, space, &mut vec![], &mut out);
+        return out;
+    }
 
-    let mut cubes: Vec<(String, Hypercube)> = vec![("in".to_owned(), Hypercube::initial())];
+    fn collect_accepted(&self, id: usize, mut cube: Hypercube, path: &mut Vec<(usize, usize)>, out: &mut Vec<AcceptedBox>) {
+        for (idx, rule) in self.workflows[id].iter().enumerate() {
+            if cube.is_empty() {
+                return;
+            }
+            let (pass, rest) = rule.condition.divide(cube);
+            cube = rest;
+            if pass.is_empty() {
+                continue;
+            }
+            path.push((id, idx));
+            match rule.target {
+                Target::Accept => out.push(AcceptedBox { cube: pass, path: path.clone() }),
+                Target::Reject => {},
+                Target::Workflow(next) => self.collect_accepted(next, pass, path, out)
+            }
+            path.pop();
+        }
+    }
 
-    while cubes.len() > 0 {
-        let (workflow_id, cube) = cubes.pop().unwrap();
-        let mut cube = cube;
-        let workflow = workflows.get(&workflow_id).unwrap();
+    /// Number of accepted parts among those in `constraints`
+    fn accepted_volume(&self, constraints: Hypercube) -> usize {
+        return self.accepted_boxes(constraints).into_iter().map(|b| b.cube.volume()).sum();
+    }
 
-        // steps handling:
-        // Unconditional: whole cube goes to target
-        // GreaterThan(var, bound, target): split(var, bound), [1] goes to target, [0] becomes the
-        //   new value of cube
-        // LessThan(var, bound, target): split(var, bound-1), [0] goes to target, [1] becomes the
-        //   new value of cube
+    /// `in (s<1351) -> px (else) -> A`
+    fn path_text(&self, path: &[(usize, usize)]) -> String {
+        let mut out = String::new();
+        for (id, idx) in path {
+            let rule = self.workflows[*id][*idx];
+            let condition = match rule.condition {
+                Condition::Always => "else".to_owned(),
+                condition => condition.to_string()
+            };
+            out += &format!("{} ({}) -> ", self.names[*id], condition);
+        }
+        if let Some((id, idx)) = path.last() {
+            out += self.target_name(self.workflows[*id][*idx].target);
+        }
+        return out;
+    }
 
-        'Steps: for step in &workflow.steps {
-            match step {
-                Step::Unconditional(target) => {
-                    let target: &str = target;
-                    match target {
-                        "A" => sum += cube.volume(),
-                        "R" => {},
-                        _ => cubes.push((target.to_owned(), cube))
-                    }
-                    break 'Steps;
-                },
-                Step::GreaterThan(var, bound, target) => {
-                    let target: &str = target;
-                    let (fail, pass) = cube.split(var, *bound);
-                    cube = fail;
-                    match target {
-                        "A" => sum += pass.volume(),
-                        "R" => {},
-                        _ => cubes.push((target.to_owned(), pass))
-                    }
-                },
-                Step::LessThan(var, bound, target) => {
-                    let target: &str = target;
-                    let (pass, fail) = cube.split(var, *bound - 1);
-                    cube = fail;
-                    match target {
-                        "A" => sum += pass.volume(),
-                        "R" => {},
-                        _ => cubes.push((target.to_owned(), pass))
+    fn target_name(&self, target: Target) -> &str {
+        return match target {
+            Target::Accept => "A",
+            Target::Reject => "R",
+            Target::Workflow(id) => &self.names[id]
+        };
+    }
+
+    /// Ids of workflows no part can get to from `in`
+    fn unreachable(&self) -> Vec<usize> {
+        let mut reached: Vec<bool> = vec![false; self.workflows.len()];
+        let mut stack: Vec<usize> = vec![self.start];
+        reached[self.start] = true;
+        while let Some(id) = stack.pop() {
+            for rule in &self.workflows[id] {
+                if let Target::Workflow(next) = rule.target {
+                    if !reached[next] {
+                        reached[next] = true;
+                        stack.push(next);
                     }
                 }
             }
         }
+        return (0..self.workflows.len()).filter(|id| !reached[*id]).collect();
     }
-    return sum;
-}
 
-#[derive(Debug)]
-struct Hypercube {
-    x0: usize,
-    x1: usize,
+    /// (workflow id, rule index) of rules no part in `space` can match, because the rules before
+    /// them already took every part they could
+    fn dead_rules(&self, space: Hypercube) -> Vec<(usize, usize)> {
+        let mut dead: Vec<(usize, usize)> = vec![];
+        for (id, rules) in self.workflows.iter().enumerate() {
+            // parts that get past the rules so far
+            let mut remaining = space;
+            for (idx, rule) in rules.iter().enumerate() {
+                let (matched, unmatched) = rule.condition.divide(remaining);
+                if matched.is_empty() {
+                    dead.push((id, idx));
+                }
+                remaining = unmatched;
+            }
+        }
+        return dead;
+    }
 
-    m0: usize,
-    m1: usize,
+    /// For every workflow: `Some(true)` if it accepts every part in `space` it gets, `Some(false)`
+    /// if it rejects them all, `None` if it depends on the part
+    fn fates(&self, space: Hypercube) -> Vec<Option<bool>> {
+        let dead: HashSet<(usize, usize)> = self.dead_rules(space).into_iter().collect();
+        let mut fates: Vec<Option<Option<bool>>> = vec![None; self.workflows.len()];
+        fn fate(program: &Program, id: usize, dead: &HashSet<(usize, usize)>, fates: &mut Vec<Option<Option<bool>>>) -> Option<bool> {
+            if let Some(known) = fates[id] {
+                return known;
+            }
+            let mut outcome: Option<Option<bool>> = None;
+            for (idx, rule) in program.workflows[id].iter().enumerate() {
+                if dead.contains(&(id, idx)) {
+                    continue;
+                }
+                let this = match rule.target {
+                    Target::Accept => Some(true),
+                    Target::Reject => Some(false),
+                    Target::Workflow(next) => fate(program, next, dead, fates)
+                };
+                outcome = match outcome {
+                    None => Some(this),
+                    Some(other) if other == this => Some(this),
+                    Some(_) => Some(None)
+                };
+            }
+            let result = outcome.flatten();
+            fates[id] = Some(result);
+            return result;
+        }
+        return (0..self.workflows.len()).map(|id| fate(self, id, &dead, &mut fates)).collect();
+    }
 
-    a0: usize,
-    a1: usize,
+    /// A program with fewer rules that does the same to every part in `space` (parts outside it
+    /// may be treated differently):
+    /// - dead rules are dropped
+    /// - targets that always accept or always reject become `A` or `R`
+    /// - a rule followed by an unconditional rule with the same target is dropped
+    /// - workflows that can't be reached any more are removed
+    fn simplified(&self, space: Hypercube) -> Program {
+        let dead: HashSet<(usize, usize)> = self.dead_rules(space).into_iter().collect();
+        let fates = self.fates(space);
+        let resolve = |target: Target| match target {
+            Target::Workflow(id) => match fates[id] {
+                Some(true) => Target::Accept,
+                Some(false) => Target::Reject,
+                None => target
+            },
+            _ => target
+        };
+
+        let mut workflows: Vec<Vec<Rule>> = vec![];
+        for (id, rules) in self.workflows.iter().enumerate() {
+            let mut merged: Vec<Rule> = vec![];
+            for (idx, rule) in rules.iter().enumerate() {
+                if dead.contains(&(id, idx)) {
+                    continue;
+                }
+                let rule = Rule { condition: rule.condition, target: resolve(rule.target) };
+                if rule.condition == Condition::Always {
+                    while merged.last().is_some_and(|r| r.target == rule.target) {
+                        merged.pop();
+                    }
+                }
+                merged.push(rule);
+            }
+            workflows.push(merged);
+        }
+        if let Some(start_fate) = fates[self.start] {
+            let target = if start_fate { Target::Accept } else { Target::Reject };
+            workflows[self.start] = vec![Rule { condition: Condition::Always, target }];
+        }
+
+        // renumber what's still reachable
+        let pruned = Program { names: self.names.clone(), workflows, start: self.start };
+        let unreachable: HashSet<usize> = pruned.unreachable().into_iter().collect();
+        let kept: Vec<usize> = (0..pruned.workflows.len()).filter(|id| !unreachable.contains(id)).collect();
+        let new_id = |old: usize| kept.iter().position(|k| *k == old).unwrap();
+        let renumber = |target: Target| match target {
+            Target::Workflow(id) => Target::Workflow(new_id(id)),
+            _ => target
+        };
+        return Program {
+            names: kept.iter().map(|id| pruned.names[*id].clone()).collect(),
+            workflows: kept.iter()
+                .map(|id| pruned.workflows[*id].iter().map(|r| Rule { condition: r.condition, target: renumber(r.target) }).collect())
+                .collect(),
+            start: new_id(self.start)
+        };
+    }
 
-    s0: usize,
-    s1: usize
+    /// Number of rules over all workflows
+    fn size(&self) -> usize {
+        return self.workflows.iter().map(|rules| rules.len()).sum();
+    }
+
+    /// The workflows as a DOT digraph, with an edge per rule labelled with its condition and
+    /// position, so the decision tree can be followed from `in`
+    fn to_dot(&self) -> String {
+        let mut out = String::from("digraph workflows {\n    node [shape=box];\n");
+        out += "    \"A\" [shape=doublecircle, color=green];\n    \"R\" [shape=doublecircle, color=red];\n";
+        out += &format!("    \"{}\" [style=bold];\n", self.names[self.start]);
+        for (id, rules) in self.workflows.iter().enumerate() {
+            for (idx, rule) in rules.iter().enumerate() {
+                let condition = match rule.condition {
+                    Condition::Always => "else".to_owned(),
+                    condition => condition.to_string()
+                };
+                out += &format!("    \"{}\" -> \"{}\" [label=\"{}: {}\"];\n", self.names[id], self.target_name(rule.target), idx + 1, condition);
+            }
+        }
+        out += "}\n";
+        return out;
+    }
+}
+impl Display for Program {
+    /// Back in the puzzle's notation
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (id, rules) in self.workflows.iter().enumerate() {
+            let rules: Vec<String> = rules.iter().map(|rule| match rule.condition {
+                Condition::Always => self.target_name(rule.target).to_owned(),
+                condition => format!("{}:{}", condition, self.target_name(rule.target))
+            }).collect();
+            writeln!(f, "{}{{{}}}", self.names[id], rules.join(","))?;
+        }
+        return Ok(());
+    }
+}
+
+/// Inclusive rating ranges for x, m, a and s, indexed by [Variable::index]. The cube is empty
+/// when any of them is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Hypercube {
+    ranges: [(usize, usize); 4]
 }
 impl Hypercube {
+    const EMPTY: Hypercube = Hypercube { ranges: [(1, 0); 4] };
+
     fn initial() -> Hypercube {
-        return Hypercube { x0: 1, x1: 4000, m0: 1, m1: 4000, a0: 1, a1: 4000, s0: 1, s1: 4000 };
+        return Hypercube::uniform(RATING_RANGE.0, RATING_RANGE.1);
+    }
+
+    fn uniform(lo: usize, hi: usize) -> Hypercube {
+        return Hypercube { ranges: [(lo, hi); 4] };
+    }
+
+    /// `x=1-2000,s=100-200,a=7`; missing variables keep their range from `defaults`
+    fn parse(data: Span, defaults: Hypercube) -> ParseResult<Hypercube> {
+        let mut cube = defaults;
+        for (key, value) in data.trim().key_values(",", "=")?.iter() {
+            let var = key.parse_with("one of `x`, `m`, `a` or `s`", |s| s.parse::<char>().ok().and_then(Variable::try_decode))?;
+            let (lo, hi) = match value.split_once("-") {
+                Ok((lo, hi)) => (lo.parse()?, hi.parse()?),
+                Err(_) => (value.parse()?, value.parse()?)
+            };
+            if lo > hi {
+                return Err(value.error("a range from low to high"));
+            }
+            cube.ranges[var.index()] = (lo, hi);
+        }
+        return Ok(cube);
+    }
+
+    fn is_empty(&self) -> bool {
+        return self.ranges.iter().any(|(lo, hi)| lo > hi);
     }
 
-    /// \[v0, v1\] over v_m => \[v0, vm\], \[vm+1, v0\]
+    /// \[v0, v1\] over v_m => \[v0, vm\], \[vm+1, v1\], either of which may be empty
     /// So, for a (var > #n) comp, you want split(var_axis, #n)\[1\] for match and \[0\] for non-match
     /// For a (var < #n) comp, you want split(var_axis, #n - 1)\[0\] for match and \[1\] for non-match
     fn split(self, axis: &Variable, point: usize) -> (Hypercube, Hypercube) {
-        match axis {
-            Variable::X => (Hypercube {x0: self.x0, x1: point, ..self}, Hypercube {x0: point+1, x1: self.x1, ..self}),
-            Variable::M => (Hypercube {m0: self.m0, m1: point, ..self}, Hypercube {m0: point+1, m1: self.m1, ..self}),
-            Variable::A => (Hypercube {a0: self.a0, a1: point, ..self}, Hypercube {a0: point+1, a1: self.a1, ..self}),
-            Variable::S => (Hypercube {s0: self.s0, s1: point, ..self}, Hypercube {s0: point+1, s1: self.s1, ..self}),
+        let (lo, hi) = self.ranges[axis.index()];
+        let (mut below, mut above) = (self, self);
+        below.ranges[axis.index()] = (lo, hi.min(point));
+        above.ranges[axis.index()] = (lo.max(point + 1), hi);
+        return (below, above);
+    }
+
+    #[allow(dead_code)]
+    fn intersect(&self, other: &Hypercube) -> Hypercube {
+        let mut out = *self;
+        for (range, (lo, hi)) in out.ranges.iter_mut().zip(other.ranges) {
+            *range = (range.0.max(lo), range.1.min(hi));
         }
+        return out;
     }
 
     #[inline]
     fn volume(self) -> usize {
-        let out = (self.x1 - self.x0 + 1) * (self.m1 - self.m0 + 1) * (self.a1 - self.a0 + 1) * (self.s1 - self.s0 + 1);
-        #[cfg(test)]
-        println!("Calculate volume of {:?} to be {}", self, out);
+        if self.is_empty() {
+            return 0;
+        }
+        let out = self.ranges.iter().map(|(lo, hi)| hi - lo + 1).product();
+        trace!("Calculate volume of {:?} to be {}", self, out);
         return out;
     }
 }
+impl Display for Hypercube {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let ranges: Vec<String> = Variable::all().iter()
+            .map(|var| format!("{}={}-{}", var.encode(), self.ranges[var.index()].0, self.ranges[var.index()].1))
+            .collect();
+        return f.write_str(&ranges.join(","));
+    }
+}
 
+/// A box of parts that all take the same way through the workflows and get accepted
+struct AcceptedBox {
+    cube: Hypercube,
+    /// (workflow id, rule index) of every rule followed, from `in` to the `A`
+    path: Vec<(usize, usize)>
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 #[char_enum]
 enum Variable {
     X = 'x',
@@ -150,6 +529,20 @@ enum Variable {
     A = 'a',
     S = 's'
 }
+impl Variable {
+    fn all() -> [Variable; 4] {
+        return [Variable::X, Variable::M, Variable::A, Variable::S];
+    }
+
+    fn index(&self) -> usize {
+        return match self {
+            Variable::X => 0,
+            Variable::M => 1,
+            Variable::A => 2,
+            Variable::S => 3
+        };
+    }
+}
 
 enum Step {
     /// _ => target
@@ -160,21 +553,19 @@ enum Step {
     LessThan(Variable, usize, String),
 }
 impl Step {
-    fn parse(data: &str) -> Step {
-        if data.contains("<") {
-            let (var, bound_target) = data.split_once("<").unwrap();
-            let var = Variable::decode(var.chars().nth(0).unwrap());
-            let (bound, target) = bound_target.split_once(":").unwrap();
-            let bound = bound.parse::<usize>().unwrap();
-            return Step::LessThan(var, bound, target.to_owned());
-        } else if data.contains(">") {
-            let (var, bound_target) = data.split_once(">").unwrap();
-            let var = Variable::decode(var.chars().nth(0).unwrap());
-            let (bound, target) = bound_target.split_once(":").unwrap();
-            let bound = bound.parse::<usize>().unwrap();
-            return Step::GreaterThan(var, bound, target.to_owned());
-        }
-        return Step::Unconditional(data.to_owned());
+    fn parse(data: Span) -> ParseResult<Step> {
+        let (condition, target) = match data.split_once(":") {
+            Ok((condition, target)) => (condition, target.as_str().to_owned()),
+            Err(_) => return Ok(Step::Unconditional(data.as_str().to_owned()))
+        };
+        let comparison = if condition.as_str().contains("<") { "<" } else { ">" };
+        let (var, bound) = condition.split_once(comparison)?;
+        let var = var.parse_with("one of `x`, `m`, `a` or `s`", |s| s.parse::<char>().ok().and_then(Variable::try_decode))?;
+        let bound = bound.parse::<usize>()?;
+        return match comparison {
+            "<" => Ok(Step::LessThan(var, bound, target)),
+            _ => Ok(Step::GreaterThan(var, bound, target))
+        };
     }
 }
 
@@ -182,33 +573,16 @@ struct Workflow {
     steps: Vec<Step>
 }
 impl Workflow {
-    fn parse(line: &str) -> (String, Workflow) {
-        let line = line.strip_suffix("}").unwrap();
-        let (id, steps_str) = line.split_once("{").unwrap();
-
-        let steps: Vec<Step> = steps_str.split(",").map(|s| Step::parse(s)).collect();
+    fn parse(line: Span) -> ParseResult<(String, Workflow)> {
+        let line = line.trim().strip_suffix("}")?;
+        let (id, steps_str) = line.split_once("{")?;
 
-        return (id.to_owned(), Workflow { steps });
-    }
-
-    /// returns target workflow, including "A" or "R"
-    fn process(&self, xmas: &Xmas) -> String {
-        for step in &self.steps {
-            match step {
-                Step::Unconditional(target) => return target.to_owned(),
-                Step::GreaterThan(var, boundary, target) => {
-                    if xmas.get(var) > *boundary {
-                        return target.to_owned();
-                    }
-                },
-                Step::LessThan(var, boundary, target) => {
-                    if xmas.get(var) < *boundary {
-                        return target.to_owned();
-                    }
-                }
-            }
+        let steps: Vec<Step> = steps_str.separated(",", Step::parse)?;
+        if !matches!(steps.last(), Some(Step::Unconditional(_))) {
+            return Err(steps_str.split(",").last().unwrap().error("an unconditional final step"));
         }
-        panic!("No steps matched, oops");
+
+        return Ok((id.as_str().to_owned(), Workflow { steps }));
     }
 }
 
@@ -219,22 +593,18 @@ struct Xmas {
     s: usize
 }
 impl Xmas {
-    fn parse(line: &str) -> Xmas {
+    fn parse(line: Span) -> ParseResult<Xmas> {
         let line = line.trim()
-            .strip_prefix("{x=").unwrap()
-            .strip_suffix("}").unwrap();
-        // 787,m=2655,a=1222,s=2876
-        let (x, mas) = line.split_once(",m=").unwrap();
-        let x = x.parse().unwrap();
-
-        let (m, as_) = mas.split_once(",a=").unwrap();
-        let m = m.parse().unwrap();
-
-        let (a, s) = as_.split_once(",s=").unwrap();
-        let a = a.parse().unwrap();
-        let s = s.parse().unwrap();
-
-        return Xmas { x, m, a, s };
+            .strip_prefix("{")?
+            .strip_suffix("}")?;
+        // x=787,m=2655,a=1222,s=2876
+        let ratings = line.key_values(",", "=")?;
+        let x = ratings.get("x")?.parse()?;
+        let m = ratings.get("m")?.parse()?;
+        let a = ratings.get("a")?.parse()?;
+        let s = ratings.get("s")?.parse()?;
+
+        return Ok(Xmas { x, m, a, s });
     }
 
     fn get(&self, var: &Variable) -> usize {
@@ -288,7 +658,7 @@ foo{s<2000:R,A}
 #[test]
 fn end_to_end_pipeline() {
     let test_data: &str = &get_test_data();
-    assert_eq!(19114, process(test_data));
+    assert_eq!(19114, process(test_data).unwrap());
 }
 
 #[test]
@@ -298,11 +668,134 @@ fn end_to_end_simple_hyper_pipeline() {
     assert_eq!(256000000000000_usize, Hypercube::initial().volume(), "Sanity check 2");
     println!("\n\nDone with sanity checks");
     let res: usize = (2001*4000*4000*4000) + (1999*4000*4000*2001);
-    assert_eq!(res, process_hyper(test_data));
+    assert_eq!(res, process_hyper(test_data).unwrap());
+}
+
+#[test]
+fn workflow_errors() {
+    let err = process("in{x<2000:foo,A}\nfoo{s<2000:R,m>5:A}\n\n{x=1,m=2,a=3,s=4}").unwrap_err();
+    assert_eq!((2, 14), (err.line, err.column));
+    assert_eq!("an unconditional final step", err.expected);
+
+    let err = process("px{x<2000:A,R}\n\n{x=1,m=2,a=3,s=4}").unwrap_err();
+    assert_eq!("a workflow named `in`", err.expected);
 }
 
 #[test]
 fn end_to_end_hyper_pipeline() {
     let test_data: &str = &get_test_data();
-    assert_eq!(167409079868000, process_hyper(test_data));
+    assert_eq!(167409079868000, process_hyper(test_data).unwrap());
+}
+
+#[test]
+fn workflow_analysis() {
+    let test_data: &str = &get_test_data();
+    let (workflows, xmases) = Span::new(test_data).trim().split_once("\n\n").unwrap();
+    let program = Program::compile(workflows).unwrap();
+    let id = |name: &str| program.names.iter().position(|n| n == name).unwrap();
+
+    let fates = program.fates(Hypercube::initial());
+    assert_eq!(Some(true), fates[id("lnx")]);
+    assert_eq!(Some(false), fates[id("gd")]);
+    assert_eq!(Some(true), fates[id("qs")]);
+    assert_eq!(None, fates[id("in")]);
+    assert!(program.unreachable().is_empty());
+
+    let simplified = program.simplified(Hypercube::initial());
+    assert_eq!(Vec::<usize>::new(), simplified.unreachable());
+    assert_eq!("px{a<2006:qkq,m>2090:A,rfg}
+pv{a>1716:R,A}
+rfg{s<537:R,x>2440:R,A}
+qkq{x<1416:A,crn}
+crn{x>2662:A,R}
+in{s<1351:px,qqz}
+qqz{s>2770:A,m<1801:hdj,R}
+hdj{m>838:A,pv}
+", simplified.to_string());
+
+    let xmases: Vec<Xmas> = xmases.parse_lines(Xmas::parse).unwrap();
+    assert_eq!(19114, simplified.accepted_rating(&xmases));
+    let simplified_data = format!("{}\n{}", simplified, test_data.trim().split_once("\n\n").unwrap().1);
+    assert_eq!(167409079868000, process_hyper(&simplified_data).unwrap());
+    assert!(simplified.to_dot().contains("    \"in\" -> \"px\" [label=\"1: s<1351\"];\n"));
+}
+
+#[test]
+fn dead_and_unreachable() {
+    let program = Program::compile(Span::new("in{x<100:A,x<50:R,m>10:foo,R}\nfoo{A}\nbar{m<4001:R,A}")).unwrap();
+    assert_eq!(vec![(0, 1), (2, 1)], program.dead_rules(Hypercube::initial()));
+    assert_eq!(vec![2], program.unreachable());
+    assert_eq!("in{x<100:A,m>10:A,R}\n", program.simplified(Hypercube::initial()).to_string());
+
+    // past 4000 the last rule of `bar` is alive again, and a part there keeps its fate
+    let program = Program::compile(Span::new("in{x>10:bar,A}\nbar{m<4001:R,A}")).unwrap();
+    let space = Hypercube::uniform(1, 8000);
+    assert_eq!(Vec::<(usize, usize)>::new(), program.dead_rules(space));
+    assert_eq!(vec![(1, 1)], program.dead_rules(Hypercube::initial()));
+    let part = Xmas { x: 20, m: 5000, a: 1, s: 1 };
+    assert!(program.accepts(&part));
+    assert!(program.simplified(space).accepts(&part));
+    assert!(!program.simplified(Hypercube::initial()).accepts(&part));
+
+    // nothing is past the largest rating, even when every rating is allowed
+    let program = Program::compile(Span::new("in{x>18446744073709551615:R,A}")).unwrap();
+    assert_eq!(vec![(0, 0)], program.dead_rules(Hypercube::uniform(0, usize::MAX)));
+
+    let err = Program::compile(Span::new("in{x<100:foo,A}\nfoo{bar}\nbar{in}")).err().unwrap();
+    assert_eq!("workflows that don't send parts round in circles", err.expected);
+    let err = Program::compile(Span::new("in{x<100:nope,A}")).err().unwrap();
+    assert_eq!("a known workflow instead of `nope`", err.expected);
+}
+
+#[test]
+fn accepted_boxes() {
+    let test_data: &str = &get_simple_test_data();
+    let (workflows, _) = Span::new(test_data).trim().split_once("\n\n").unwrap();
+    let program = Program::compile(workflows).unwrap();
+
+    let boxes = program.accepted_boxes(Hypercube::initial());
+    let described: Vec<String> = boxes.iter().map(|b| format!("{}: {}", b.cube, program.path_text(&b.path))).collect();
+    assert_eq!(vec![
+        "x=1-1999,m=1-4000,a=1-4000,s=2000-4000: in (x<2000) -> foo (else) -> A",
+        "x=2000-4000,m=1-4000,a=1-4000,s=1-4000: in (else) -> A"
+    ], described);
+
+    let within = Hypercube::parse(Span::new("x=1-10,s=1999-2000"), Hypercube::initial()).unwrap();
+    assert_eq!(10 * 4000 * 4000, program.accepted_volume(within));
+    let part = Hypercube::parse(Span::new("x=12,m=13,a=14,s=15"), Hypercube::initial()).unwrap();
+    assert!(program.accepted_boxes(part).is_empty());
+
+    // missing variables come from the defaults, whatever they are
+    let within = Hypercube::parse(Span::new("x=1-10"), Hypercube::uniform(5, 6)).unwrap();
+    assert_eq!("x=1-10,m=5-6,a=5-6,s=5-6", within.to_string());
+    let err = Hypercube::parse(Span::new("m=1,x=200-100"), Hypercube::initial()).unwrap_err();
+    assert_eq!((1, 7), (err.line, err.column));
+    assert_eq!("a range from low to high", err.expected);
+}
+
+#[test]
+fn custom_rating_space() {
+    let test_data: &str = &get_test_data();
+    let (workflows, _) = Span::new(test_data).trim().split_once("\n\n").unwrap();
+    let program = Program::compile(workflows).unwrap();
+
+    // every part with ratings from 1500 to 1510, one at a time
+    let space = Hypercube::uniform(1500, 1510);
+    let mut expected = 0;
+    for x in 1500..=1510 {
+        for m in 1500..=1510 {
+            for a in 1500..=1510 {
+                for s in 1500..=1510 {
+                    if program.accepts(&Xmas { x, m, a, s }) {
+                        expected += 1;
+                    }
+                }
+            }
+        }
+    }
+    assert!(expected > 0);
+    assert_eq!(expected, program.accepted_volume(space));
+    for accepted in program.accepted_boxes(space) {
+        assert_eq!(accepted.cube, accepted.cube.intersect(&space));
+    }
 }
@@ -1,25 +1,50 @@
 use std::hash::{Hasher, Hash};
-use std::{fs, collections::{HashMap, VecDeque, HashSet, hash_map::DefaultHasher}};
+use std::{env, fs, io::{self, Write}, collections::{HashMap, VecDeque, HashSet, hash_map::DefaultHasher}};
 
 use char_enum_impl::data_enum;
+use utils::{debug, graph::tarjan, info, parse::{ParseResult, ParseResultExt, Span}, trace};
 
 fn main() {
     println!("AOC 2023 Day 20");
+    utils::log::init();
 
     let contents = fs::read_to_string("src/bin/day20/input.txt").expect("Failed to read input");
 
-    let mut layout = Layout::load(&contents);
-    let mut layout2 = layout.clone();
+    let mut layout = Layout::load(&contents).expect_in("src/bin/day20/input.txt");
+    let layout2 = layout.clone();
+
+    // `--vcd=<file>` dumps the flip-flop and conjunction states over the part 1 presses
+    if let Some(path) = env::args().find_map(|a| a.strip_prefix("--vcd=").map(|p| p.to_owned())) {
+        let mut recording = layout.clone();
+        let mut recorder = VcdRecorder::new(&recording);
+        for _ in 0..1000 {
+            recording.press_observed(&mut recorder);
+        }
+        let mut file = fs::File::create(&path).expect("Failed to create VCD file");
+        recorder.write(&mut file).expect("Failed to write VCD file");
+        info!("Wrote waveform of 1000 presses to {}", path);
+    }
+
+    // `--dot=<file>` draws the network, `--dot-presses=<n>` adds the module states after n presses
+    if let Some(path) = env::args().find_map(|a| a.strip_prefix("--dot=").map(|p| p.to_owned())) {
+        let presses = env::args().find_map(|a| a.strip_prefix("--dot-presses=").and_then(|n| n.parse().ok()));
+        fs::write(&path, layout.to_dot(presses, true)).expect("Failed to write DOT file");
+        info!("Wrote network graph to {}", path);
+    }
+
     let part1 = layout.thousand_product();
 
     println!("Part 1: {}", part1);
 
-    for i in 1..=1_000_000_usize {
-        println!("Starting iteration {}", i);
-        layout2.thousand_product();
+    match layout2.presses_until_low("rx", PRESS_LIMIT) {
+        Ok(presses) => println!("Part 2: {}", presses),
+        Err(e) => println!("Part 2: {}", e)
     }
 }
 
+/// How many times [Layout::presses_until_low] is willing to press the button
+const PRESS_LIMIT: usize = 1_000_000;
+
 #[derive(Clone, Debug)]
 enum Module {
     /// (false - off, true - on)
@@ -33,20 +58,20 @@ enum Module {
     Drain,
 }
 impl Module {
-    fn parse(data: &str) -> (String, Module) {
-        if data == "broadcaster" {
-            return (data.to_owned(), Module::Broadcaster);
+    fn parse(data: Span) -> ParseResult<(String, Module)> {
+        if data.as_str() == "broadcaster" {
+            return Ok((data.as_str().to_owned(), Module::Broadcaster));
         }
-        if data == "output" {
-            return (data.to_owned(), Module::Output(vec![]));
+        if data.as_str() == "output" {
+            return Ok((data.as_str().to_owned(), Module::Output(vec![])));
         }
-        if data.starts_with("%") {
-            return (data[1..].to_owned(), Module::FlipFlop(false));
+        if let Ok(name) = data.strip_prefix("%") {
+            return Ok((name.as_str().to_owned(), Module::FlipFlop(false)));
         }
-        if data.starts_with("&") {
-            return (data[1..].to_owned(), Module::Conjunction(HashMap::new()));
+        if let Ok(name) = data.strip_prefix("&") {
+            return Ok((name.as_str().to_owned(), Module::Conjunction(HashMap::new())));
         }
-        panic!("Unable to parse module");
+        return Err(data.error("`broadcaster`, `output`, `%name` or `&name`"));
     }
 }
 impl Hash for Module {
@@ -84,11 +109,188 @@ impl Into<Pulse> for bool {
     }
 }
 
-/*
- * note: the names of these may differ for your input, make sure to adjust [Layout::feeder_to_idx]
- * rx will get a low pulse when &gq has gotten high pulses from all of its inputs
- * (&xj, &qs, &kz, &km)
- */
+/// Gets told about everything that happens while the button is pressed. `press` counts from 1.
+trait Observer {
+    /// The button is about to be pressed
+    fn press(&mut self, _press: usize) {}
+
+    /// A pulse from `src` is being delivered to `dst`
+    fn pulse(&mut self, _press: usize, _src: &str, _dst: &str, _pulse: Pulse) {}
+
+    /// The state of `module` just changed; a flip-flop flipped or a conjunction's memory of
+    /// one of its inputs changed
+    fn transition(&mut self, _press: usize, _name: &str, _module: &Module) {}
+}
+impl Observer for () {}
+
+/// Counts low and high pulses, both in total and by the module that sent them
+#[derive(Default)]
+struct PulseCounter {
+    low: usize,
+    high: usize,
+    /// src -> (low, high)
+    by_source: HashMap<String, (usize, usize)>
+}
+impl Observer for PulseCounter {
+    fn pulse(&mut self, _press: usize, src: &str, _dst: &str, pulse: Pulse) {
+        let counts = match self.by_source.get_mut(src) {
+            Some(counts) => counts,
+            None => self.by_source.entry(src.to_owned()).or_default()
+        };
+        if pulse.value() {
+            self.high += 1;
+            counts.1 += 1;
+        } else {
+            self.low += 1;
+            counts.0 += 1;
+        }
+    }
+}
+
+/// Records flip-flop and conjunction states as a Value Change Dump, viewable in any waveform
+/// viewer (GTKWave, Surfer, ...).
+///
+/// Every delivered pulse is one time step, so short glitches within a press stay visible. The
+/// current press number is recorded as the `press` signal. Conjunctions are multi-bit signals
+/// holding the remembered input pulses, first input (by name) as the most significant bit.
+struct VcdRecorder {
+    /// module -> (identifier, bits)
+    signals: HashMap<String, (String, Vec<bool>)>,
+    header: String,
+    changes: String,
+    time: u64,
+    written_time: Option<u64>
+}
+impl VcdRecorder {
+    const PRESS_ID: &'static str = "!";
+
+    fn new(layout: &Layout) -> VcdRecorder {
+        let mut names: Vec<&String> = layout.all_stateful_modules.iter().collect();
+        names.sort();
+
+        let mut signals: HashMap<String, (String, Vec<bool>)> = HashMap::new();
+        let mut header = String::new();
+        header += "$version AOC 2023 day 20 $end\n$timescale 1ns $end\n$scope module layout $end\n";
+        header += &format!("$var integer 32 {} press $end\n", VcdRecorder::PRESS_ID);
+        for (i, name) in names.into_iter().enumerate() {
+            let id = VcdRecorder::identifier(i + 1);
+            let bits = VcdRecorder::bits(&layout.modules[name].0);
+            if let Module::Conjunction(states) = &layout.modules[name].0 {
+                header += &format!("$comment {} remembers {} $end\n", name, sorted_keys(states).join(", "));
+            }
+            header += &format!("$var wire {} {} {} $end\n", bits.len(), id, name);
+            signals.insert(name.to_owned(), (id, bits));
+        }
+        header += "$upscope $end\n$enddefinitions $end\n";
+
+        let mut recorder = VcdRecorder { signals, header, changes: String::new(), time: 0, written_time: None };
+        recorder.changes += "$dumpvars\n";
+        recorder.changes += &format!("b{:b} {}\n", layout.iters, VcdRecorder::PRESS_ID);
+        let mut initial: Vec<&(String, Vec<bool>)> = recorder.signals.values().collect();
+        initial.sort();
+        let initial: Vec<String> = initial.iter().map(|(id, bits)| VcdRecorder::value(id, bits)).collect();
+        for line in initial {
+            recorder.changes += &line;
+        }
+        recorder.changes += "$end\n";
+        return recorder;
+    }
+
+    /// Printable identifier, in base 94 over `!`..`~`
+    fn identifier(mut n: usize) -> String {
+        let mut id = String::new();
+        loop {
+            id.push((b'!' + (n % 94) as u8) as char);
+            n /= 94;
+            if n == 0 {
+                return id;
+            }
+        }
+    }
+
+    fn bits(module: &Module) -> Vec<bool> {
+        return match module {
+            Module::FlipFlop(on) => vec![*on],
+            Module::Conjunction(states) => sorted_keys(states).iter().map(|k| states[*k]).collect(),
+            _ => vec![]
+        };
+    }
+
+    fn value(id: &str, bits: &[bool]) -> String {
+        if bits.len() == 1 {
+            return format!("{}{}\n", if bits[0] { 1 } else { 0 }, id);
+        }
+        let bits: String = bits.iter().map(|b| if *b { '1' } else { '0' }).collect();
+        return format!("b{} {}\n", bits, id);
+    }
+
+    fn change(&mut self, line: String) {
+        if self.written_time != Some(self.time) {
+            self.changes += &format!("#{}\n", self.time);
+            self.written_time = Some(self.time);
+        }
+        self.changes += &line;
+    }
+
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(self.header.as_bytes())?;
+        out.write_all(b"#0\n")?;
+        out.write_all(self.changes.as_bytes())?;
+        return writeln!(out, "#{}", self.time + 1);
+    }
+}
+impl Observer for VcdRecorder {
+    fn press(&mut self, press: usize) {
+        self.time += 1;
+        self.change(format!("b{:b} {}\n", press, VcdRecorder::PRESS_ID));
+    }
+
+    fn pulse(&mut self, _press: usize, _src: &str, _dst: &str, _pulse: Pulse) {
+        self.time += 1;
+    }
+
+    fn transition(&mut self, _press: usize, name: &str, module: &Module) {
+        let bits = VcdRecorder::bits(module);
+        let line = match self.signals.get_mut(name) {
+            Some((id, old)) if *old != bits => {
+                *old = bits;
+                VcdRecorder::value(id, old)
+            },
+            _ => return
+        };
+        self.change(line);
+    }
+}
+
+fn sorted_keys(states: &HashMap<String, bool>) -> Vec<&str> {
+    let mut keys: Vec<&str> = states.keys().map(|k| k.as_str()).collect();
+    keys.sort();
+    return keys;
+}
+
+/// Watches for a low pulse into `target`, and for the presses on which each input of `hub`
+/// (the conjunction feeding `target`) sends it a high pulse
+struct TargetWatcher {
+    target: String,
+    hub: Option<String>,
+    /// input -> presses with a high pulse into the hub
+    highs: HashMap<String, Vec<usize>>,
+    low_at: Option<usize>
+}
+impl Observer for TargetWatcher {
+    fn pulse(&mut self, press: usize, src: &str, dst: &str, pulse: Pulse) {
+        if dst == self.target && !pulse.value() && self.low_at.is_none() {
+            self.low_at = Some(press);
+        }
+        if pulse.value() && self.hub.as_deref() == Some(dst) {
+            if let Some(presses) = self.highs.get_mut(src) {
+                if presses.last() != Some(&press) {
+                    presses.push(press);
+                }
+            }
+        }
+    }
+}
 
 #[derive(Clone)]
 struct Layout {
@@ -96,22 +298,9 @@ struct Layout {
     all_stateful_modules: Vec<String>,
     #[allow(dead_code)]
     previous_hashes: HashSet<u64>,
-    iters: usize,
-    intervals: [(Option<usize>, Option<usize>); 4]
+    iters: usize
 }
 impl Layout {
-    /// convert input to 'gq' to an index in the list
-    fn feeder_to_idx(feeder: &String) -> usize {
-        let feeder: &str = &*feeder;
-        match feeder {
-            "xj" => 0,
-            "qs" => 1,
-            "kz" => 2,
-            "km" => 3,
-            _ => panic!("Invalid feeder")
-        }
-    }
-
     #[allow(dead_code)]
     fn hash_me(&self) -> u64 {
         let mut hasher = DefaultHasher::new();
@@ -122,23 +311,27 @@ impl Layout {
         hasher.finish()
     }
 
-    fn load(data: &str) -> Layout {
+    fn load(data: &str) -> ParseResult<Layout> {
+        let data = Span::new(data);
         let mut modules: HashMap<String, (Module, Vec<String>)> = HashMap::new();
         let mut output_found = false;
 
-        let lines = data.trim().split("\n")
-            .map(|l| {
-                let (src, dst) = l.split_once(" -> ").unwrap();
-                let (src_name, src_module) = Module::parse(src);
-                let dst: Vec<String> = dst.split(", ").map(|s| s.to_owned()).collect();
-                if dst.contains(&"output".to_owned()) {
-                    output_found = true;
-                }
-                return (src_name, (src_module, dst));
-            });
-        lines.for_each(|(k, v)| {modules.insert(k, v);});
+        for l in data.trim().lines() {
+            let (src, dst) = l.trim().split_once(" -> ")?;
+            let (src_name, src_module) = Module::parse(src)?;
+            let dst: Vec<String> = dst.split(", ").map(|s| s.as_str().to_owned()).collect();
+            if dst.contains(&"output".to_owned()) {
+                output_found = true;
+            }
+            if modules.insert(src_name, (src_module, dst)).is_some() {
+                return Err(src.error("a module with a new name"));
+            }
+        }
+        if !modules.contains_key("broadcaster") {
+            return Err(data.trim().error("a `broadcaster` module"));
+        }
         if output_found {
-            modules.insert("output".to_owned(), (Module::parse("output").1, vec![]));
+            modules.insert("output".to_owned(), (Module::Output(vec![]), vec![]));
         }
 
         let mut stateful_modules: Vec<String> = vec![];
@@ -169,17 +362,22 @@ impl Layout {
             }
         }
 
-        return Layout {
+        return Ok(Layout {
             modules,
             all_stateful_modules: stateful_modules,
             previous_hashes: HashSet::new(),
-            iters: 0,
-            intervals: [(None, None); 4]
-        };
+            iters: 0
+        });
     }
 
     /// return: (output_signals, low_pulses, high_pulses)
+    #[allow(dead_code)]
     fn press_once(&mut self) -> (Vec<bool>, usize, usize) {
+        return self.press_observed(&mut ());
+    }
+
+    /// [Layout::press_once], reporting every pulse to `observer`
+    fn press_observed(&mut self, observer: &mut impl Observer) -> (Vec<bool>, usize, usize) {
         /*let hash = self.hash_me();
         if self.previous_hashes.contains(&hash) {
             panic!("Cycle detected!!!");
@@ -191,9 +389,11 @@ impl Layout {
         let mut low_pulse_count: usize = 0;
         let mut high_pulse_count: usize = 0;
         pulses.push_back(("button".to_owned(), "broadcaster".to_owned(), Pulse::Low));
+        observer.press(self.iters + 1);
 
         while pulses.len() > 0 {
             let (src, current, pulse) = pulses.pop_front().unwrap();
+            observer.pulse(self.iters + 1, &src, &current, pulse);
 
             if pulse.value() {
                 high_pulse_count += 1;
@@ -202,19 +402,14 @@ impl Layout {
             }
 
             if !self.modules.contains_key(&current) {
-                println!("Module {} does not seem to exist, adding it", current);
+                debug!("Module {} does not seem to exist, adding it", current);
                 self.modules.insert(current, (Module::Drain, vec![]));
                 continue;
             }
 
-            if current == "rx" && !pulse.value() {
-                panic!("Low pulse to rx!");
-            }
-
             let (module, next_targets) = self.modules.get_mut(&current).expect(&format!("Module '{}' not found", current));
 
-            #[cfg(test)]
-            println!("{} {:?} -> {}", src, pulse, current);
+            trace!("{} {:?} -> {}", src, pulse, current);
 
             match module {
                 Module::FlipFlop(state) => {
@@ -222,53 +417,21 @@ impl Layout {
                         continue;
                     }
                     *state = !*state;
+                    let next_pulse: Pulse = (*state).into();
+                    observer.transition(self.iters + 1, &current, module);
                     for next_target in next_targets {
-                        pulses.push_back((current.to_owned(), next_target.to_owned(), (*state).into()));
+                        pulses.push_back((current.to_owned(), next_target.to_owned(), next_pulse));
                     }
                 },
                 Module::Conjunction(states) => {
-                    let old = states.get(&src).unwrap();
-                    if current == "gq" {
-                        if pulse.value() != *old {
-                            println!("\n\n== iter {} gq got a {:?} pulse from {}", self.iters, pulse, src);
-                        }
-                        if pulse.value() {
-                            let idx = Layout::feeder_to_idx(&src);
-                            let (first, second) = &mut self.intervals[idx];
-                            if let None = first {
-                                *first = Some(self.iters);
-                            } else if let None = second {
-                                *second = Some(self.iters);
-
-                                // go through and check that all are filled in
-                                let all_filled = self.intervals.iter().all(|(a, b)| {
-                                    if let None = a {
-                                        return false;
-                                    }
-                                    if let None = b {
-                                        return false;
-                                    }
-                                    return true;
-                                });
-                                if all_filled {
-                                    let mut product: usize = 1;
-                                    for (a, b) in self.intervals {
-                                        let start = a.unwrap();
-                                        let cycle_len = b.unwrap() - start;
-                                        println!("start counting from 0: {}, cycle: {}", start, cycle_len);
-                                        product *= cycle_len; // magic works
-                                    }
-                                    println!("Part 2: {}", product);
-                                    panic!("We're done!");
-                                }
-                            }
-                        }
-                    }
-                    states.insert(src, pulse.value());
+                    let changed = states.insert(src, pulse.value()) != Some(pulse.value());
                     let next_pulse: Pulse = (!states.iter()
                         .map(|(_, b)| *b)
                         .reduce(|acc, e| acc && e)
                         .unwrap()).into();
+                    if changed {
+                        observer.transition(self.iters + 1, &current, module);
+                    }
 
                     for next_target in next_targets {
                         pulses.push_back((current.to_owned(), next_target.to_owned(), next_pulse));
@@ -296,15 +459,227 @@ impl Layout {
     }
 
     fn thousand_product(&mut self) -> usize {
-        let mut low_sum: usize = 0;
-        let mut high_sum: usize = 0;
+        let mut counter = PulseCounter::default();
         for _ in 0..1000 {
-            let (_, low, high) = self.press_once();
-            low_sum += low;
-            high_sum += high;
+            self.press_observed(&mut counter);
         }
-        return low_sum * high_sum;
+        return counter.low * counter.high;
     }
+
+    /// Fewest button presses (starting from the current state) until `target` gets a low pulse.
+    ///
+    /// Usually `target` is fed by a single conjunction whose inputs each send it a high pulse
+    /// periodically, so rather than simulating until they line up, the period of every input is
+    /// measured and the presses where they coincide are worked out from those. Anything else is
+    /// simulated directly. Gives up after `limit` presses.
+    fn presses_until_low(&self, target: &str, limit: usize) -> Result<usize, String> {
+        let feeders: Vec<&String> = self.modules.iter()
+            .filter(|(_, (_, targets))| targets.iter().any(|t| t == target))
+            .map(|(name, _)| name)
+            .collect();
+        if feeders.is_empty() {
+            return Err(format!("nothing sends pulses to `{}`", target));
+        }
+
+        let mut watcher = TargetWatcher { target: target.to_owned(), hub: None, highs: HashMap::new(), low_at: None };
+        if let [hub] = feeders[..] {
+            if let (Module::Conjunction(inputs), _) = &self.modules[hub] {
+                info!("`{}` is fed by conjunction `{}` with inputs {:?}", target, hub, inputs.keys().collect::<Vec<_>>());
+                watcher.hub = Some(hub.to_owned());
+                watcher.highs = inputs.keys().map(|k| (k.to_owned(), vec![])).collect();
+            }
+        }
+
+        let mut layout = self.clone();
+        for press in 1..=limit {
+            layout.press_observed(&mut watcher);
+            if let Some(found) = watcher.low_at {
+                debug!("Low pulse into `{}` seen directly on press {}", target, found);
+                return Ok(found);
+            }
+            // two high pulses from every input give each one's offset and period
+            if watcher.hub.is_some() && watcher.highs.values().all(|p| p.len() >= 2) {
+                let mut inputs: Vec<(&String, &Vec<usize>)> = watcher.highs.iter().collect();
+                inputs.sort();
+                let mut congruences: Vec<(u64, u64)> = vec![];
+                for (input, presses) in inputs {
+                    let (first, period) = (presses[0] as u64, (presses[1] - presses[0]) as u64);
+                    info!("`{}` sends a high pulse on press {} and every {} presses after, found by press {}", input, first, period, press);
+                    congruences.push((first, period));
+                }
+                return coinciding_press(&congruences)
+                    .map(|p| p as usize)
+                    .ok_or_else(|| format!("the inputs of `{}` never send high pulses on the same press", watcher.hub.as_ref().unwrap()));
+            }
+        }
+        return Err(format!("`{}` got no low pulse within {} presses", target, limit));
+    }
+
+    /// Every module name, including targets that aren't defined (like `rx`), sorted
+    fn all_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.modules.iter()
+            .flat_map(|(name, (_, targets))| std::iter::once(name.as_str()).chain(targets.iter().map(|t| t.as_str())))
+            .collect();
+        names.sort();
+        names.dedup();
+        return names;
+    }
+
+    /// Strongly connected components with more than one module, each sorted, in order of their
+    /// first module. In puzzle inputs these are the binary counters feeding the final conjunction.
+    fn cycles(&self) -> Vec<Vec<&str>> {
+        let names = self.all_names();
+        let index_of: HashMap<&str, usize> = names.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+        let edges: Vec<Vec<usize>> = names.iter()
+            .map(|n| match self.modules.get(*n) {
+                Some((_, targets)) => targets.iter().map(|t| index_of[t.as_str()]).collect(),
+                None => vec![]
+            })
+            .collect();
+
+        let mut components: Vec<Vec<&str>> = tarjan(&edges).into_iter()
+            .filter(|c| c.len() > 1)
+            .map(|c| {
+                let mut c: Vec<&str> = c.into_iter().map(|i| names[i]).collect();
+                c.sort();
+                c
+            })
+            .collect();
+        components.sort();
+        return components;
+    }
+
+    /// If `component` is a binary counter (a chain of flip-flops started by the broadcaster, plus
+    /// one conjunction watching some of the bits and resetting the rest), how many presses it
+    /// takes to make the conjunction fire
+    fn counter_period(&self, component: &[&str]) -> Option<usize> {
+        let conjunctions: Vec<&str> = component.iter()
+            .filter(|n| matches!(self.modules.get(**n), Some((Module::Conjunction(_), _))))
+            .copied()
+            .collect();
+        let [conjunction] = conjunctions[..] else {
+            return None;
+        };
+        let (_, broadcast) = self.modules.get("broadcaster")?;
+        let mut bit = broadcast.iter().find(|t| component.contains(&t.as_str()))?;
+        let mut period = 0;
+        let mut place = 1;
+        for _ in 0..component.len() {
+            let (module, targets) = self.modules.get(bit)?;
+            if !matches!(module, Module::FlipFlop(_)) {
+                return None;
+            }
+            if targets.iter().any(|t| t == conjunction) {
+                period += place;
+            }
+            place *= 2;
+            match targets.iter().find(|t| t.as_str() != conjunction && component.contains(&t.as_str())) {
+                Some(next) => bit = next,
+                None => return Some(period)
+            }
+        }
+        return None;
+    }
+
+    /// Graphviz description of the network. Edges are labelled with their position in the
+    /// sender's list of targets. With `presses`, module states after that many presses are shown.
+    /// With `cycles`, the strongly connected parts of the network are grouped into clusters.
+    fn to_dot(&self, presses: Option<usize>, cycles: bool) -> String {
+        let mut layout = self.clone();
+        if let Some(presses) = presses {
+            for _ in 0..presses {
+                layout.press_once();
+            }
+        }
+
+        let mut out = String::new();
+        out += "digraph layout {\n    rankdir=LR;\n    node [fontname=\"monospace\"];\n";
+        if let Some(presses) = presses {
+            out += &format!("    label=\"after {} presses\";\n", presses);
+        }
+
+        if cycles {
+            for (i, component) in self.cycles().iter().enumerate() {
+                let label = match self.counter_period(component) {
+                    Some(period) => format!("counter, fires every {} presses", period),
+                    None => "cycle".to_owned()
+                };
+                out += &format!("    subgraph cluster_{} {{\n        label=\"{}\";\n", i, label);
+                for name in component {
+                    out += &format!("        \"{}\";\n", name);
+                }
+                out += "    }\n";
+            }
+        }
+
+        for name in self.all_names() {
+            let module = layout.modules.get(name).map(|(m, _)| m);
+            let shape = match module {
+                Some(Module::FlipFlop(_)) => "box",
+                Some(Module::Conjunction(_)) => "invhouse",
+                Some(Module::Broadcaster) => "doublecircle",
+                Some(Module::Output(_)) => "doubleoctagon",
+                Some(Module::Drain) | None => "octagon"
+            };
+            let mut attributes = format!("shape={}", shape);
+            if presses.is_some() {
+                match module {
+                    Some(Module::FlipFlop(on)) => {
+                        let (state, color) = if *on { ("on", "gold") } else { ("off", "white") };
+                        attributes += &format!(", label=\"{}\\n{}\", style=filled, fillcolor={}", name, state, color);
+                    },
+                    Some(Module::Conjunction(states)) => {
+                        let high = states.values().filter(|b| **b).count();
+                        attributes += &format!(", label=\"{}\\n{}/{} high\"", name, high, states.len());
+                    },
+                    _ => {}
+                }
+            }
+            out += &format!("    \"{}\" [{}];\n", name, attributes);
+        }
+
+        let mut sources: Vec<&String> = self.modules.keys().collect();
+        sources.sort();
+        for source in sources {
+            for (i, target) in self.modules[source].1.iter().enumerate() {
+                out += &format!("    \"{}\" -> \"{}\" [label=\"{}\"];\n", source, target, i + 1);
+            }
+        }
+        out += "}\n";
+        return out;
+    }
+}
+
+/// Smallest press `t` with `t >= first` and `t = first (mod period)` for every `(first, period)`
+fn coinciding_press(congruences: &[(u64, u64)]) -> Option<u64> {
+    let latest_first = congruences.iter().map(|(first, _)| *first).max()?;
+    // combine the congruences pairwise, as `t = residue (mod modulus)`
+    let (mut residue, mut modulus): (i128, i128) = (0, 1);
+    for (first, period) in congruences {
+        let (first, period) = (*first as i128, *period as i128);
+        let (g, inverse, _) = extended_gcd(modulus, period);
+        if (first - residue) % g != 0 {
+            return None;
+        }
+        let lcm = modulus / g * period;
+        let step = ((first - residue) / g % (period / g)) * inverse % (period / g);
+        residue = (residue + modulus * step).rem_euclid(lcm);
+        modulus = lcm;
+    }
+    let latest_first = latest_first as i128;
+    if residue < latest_first {
+        residue += (latest_first - residue + modulus - 1) / modulus * modulus;
+    }
+    return Some(residue as u64);
+}
+
+/// return: (gcd, x, y) with a*x + b*y = gcd
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        return (a, 1, 0);
+    }
+    let (g, x, y) = extended_gcd(b, a % b);
+    return (g, y, x - (a / b) * y);
 }
 
 #[test]
@@ -315,7 +690,7 @@ broadcaster -> a, b, c
 %b -> c
 %c -> inv
 &inv -> a
-");
+").unwrap();
     let mut layout2 = layout.clone();
     let (_, low, high) = layout.press_once();
     assert_eq!(8, low);
@@ -331,7 +706,7 @@ broadcaster -> a
 &inv -> b
 %b -> con
 &con -> output
-");
+").unwrap();
     let mut layout2 = layout.clone();
 
     let (_, low, high) = layout.press_once();
@@ -341,3 +716,102 @@ broadcaster -> a
 
     assert_eq!(11687500, layout2.thousand_product(), "All the counting");
 }
+
+#[test]
+fn coinciding_presses() {
+    assert_eq!(Some(3 * 7 * 11), coinciding_press(&[(3, 3), (7, 7), (11, 11)]));
+    assert_eq!(Some(12), coinciding_press(&[(4, 4), (6, 6)]));
+    assert_eq!(Some(11), coinciding_press(&[(2, 3), (1, 5)]));
+    assert_eq!(None, coinciding_press(&[(1, 4), (2, 6)]));
+}
+
+#[test]
+fn counters_into_rx() {
+    // two binary counters that reset after 3 and 7 presses, both feeding &hub -> rx
+    let layout: Layout = Layout::load("
+broadcaster -> a0, b0
+%a0 -> a1, ca
+%a1 -> ca
+&ca -> a0, xa
+&xa -> hub
+%b0 -> b1, cb
+%b1 -> b2, cb
+%b2 -> cb
+&cb -> b0, xb
+&xb -> hub
+&hub -> rx
+").unwrap();
+    // simulating directly needs 21 presses, measuring the periods only 14
+    assert_eq!(Ok(21), layout.presses_until_low("rx", 21));
+    assert_eq!(Ok(21), layout.presses_until_low("rx", 14));
+    assert!(layout.presses_until_low("rx", 13).is_err());
+    assert!(layout.presses_until_low("nowhere", 100).is_err());
+}
+
+#[test]
+fn pulse_counting() {
+    let mut layout: Layout = Layout::load("
+broadcaster -> a
+%a -> inv, con
+&inv -> b
+%b -> con
+&con -> output
+").unwrap();
+    let mut counter = PulseCounter::default();
+    layout.press_observed(&mut counter);
+    assert_eq!((4, 4), (counter.low, counter.high));
+    assert_eq!(Some(&(1, 0)), counter.by_source.get("button"));
+    assert_eq!(Some(&(0, 2)), counter.by_source.get("a"));
+    assert_eq!(Some(&(1, 1)), counter.by_source.get("con"));
+}
+
+#[test]
+fn vcd_recording() {
+    let mut layout: Layout = Layout::load("
+broadcaster -> a
+%a -> inv, con
+&inv -> b
+%b -> con
+&con -> output
+").unwrap();
+    let mut recorder = VcdRecorder::new(&layout);
+    layout.press_observed(&mut recorder);
+    let mut out: Vec<u8> = vec![];
+    recorder.write(&mut out).unwrap();
+    let vcd = String::from_utf8(out).unwrap();
+
+    let lines: Vec<&str> = vcd.lines().collect();
+    assert!(lines.contains(&"$var wire 1 \" a $end"));
+    assert!(lines.contains(&"$comment con remembers a, b $end"));
+    assert!(lines.contains(&"$var wire 2 $ con $end"));
+    let changes: Vec<&str> = lines.iter().skip_while(|l| **l != "#1").copied().collect();
+    // press, then a flips on, inv and con hear a, b flips on and con hears b
+    assert_eq!(vec!["#1", "b1 !", "#3", "1\"", "#4", "1%", "#5", "b10 $", "#6", "1#", "#8", "b11 $", "#10"], changes);
+}
+
+#[test]
+fn counter_detection() {
+    let layout: Layout = Layout::load("
+broadcaster -> a0, b0
+%a0 -> a1, ca
+%a1 -> ca
+&ca -> a0, xa
+&xa -> hub
+%b0 -> b1, cb
+%b1 -> b2, cb
+%b2 -> cb
+&cb -> b0, xb
+&xb -> hub
+&hub -> rx
+").unwrap();
+    assert_eq!(vec![vec!["a0", "a1", "ca"], vec!["b0", "b1", "b2", "cb"]], layout.cycles());
+    assert_eq!(Some(3), layout.counter_period(&["a0", "a1", "ca"]));
+    assert_eq!(Some(7), layout.counter_period(&["b0", "b1", "b2", "cb"]));
+
+    let dot = layout.to_dot(Some(2), true);
+    assert!(dot.contains("    subgraph cluster_1 {\n        label=\"counter, fires every 7 presses\";\n"));
+    assert!(dot.contains("    \"broadcaster\" -> \"b0\" [label=\"2\"];\n"));
+    assert!(dot.contains("    \"a1\" [shape=box, label=\"a1\\non\", style=filled, fillcolor=gold];\n"));
+    assert!(dot.contains("    \"rx\" [shape=octagon];\n"));
+    assert!(!layout.to_dot(None, false).contains("cluster"));
+}
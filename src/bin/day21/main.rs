@@ -1,43 +1,46 @@
-use std::{fs, collections::HashSet};
+use std::{env, fs, collections::VecDeque, rc::Rc};
 
 use char_enum_impl::char_enum;
-use utils::colorize;
+use utils::{colorize, debug, info, parse::{ParseResult, ParseResultExt, Span}, viewer::{Render, Step, Viewer}};
 
 fn main() {
-    const SIZE: usize = 131;
-    const HALF_SIZE: usize = 65;
     println!("AOC 2023 Day 21");
+    utils::log::init();
 
     let contents = fs::read_to_string("src/bin/day21/input.txt").expect("Failed to read input");
+    let steps: usize = env::args().find_map(|a| a.strip_prefix("--steps=").and_then(|n| n.parse().ok())).unwrap_or(26501365);
 
-    let mut field = Field::load(&contents);
-    println!("Field width: {}, height: {}", field.width, field.height);
-    let field2 = field.clone();
+    let field = Field::load(&contents).expect_in("src/bin/day21/input.txt");
+    info!("Field width: {}, height: {}", field.width, field.height);
 
-    let total = field.flood(64);
-    println!("Part 1: {}", total);
-    
-    if true {
-        println!("\n\n Part 2 debugging");
-        for mul in 0..=3 {
-            let mut field3 = field2.clone();
-            let steps = HALF_SIZE + (mul * SIZE);
-            let total = field3.flood(steps);
-            println!("After {} iterations: {}", steps, total);
+    // `--view` watches the flood spread for part 1 (or `--steps`) in the terminal instead
+    if env::args().any(|a| a == "--view") {
+        let limit = if env::args().any(|a| a.starts_with("--steps=")) { steps } else { 64 };
+        match Flooding::new(field, limit) {
+            Ok(flooding) => Viewer::new(flooding).run().expect("Viewer failed"),
+            Err(e) => println!("{}", e)
         }
+        return;
     }
 
-    let x = ((26501365 - HALF_SIZE) / SIZE) + 1;
-    // magically calculated from previous sequence using WA:
-    // https://www.wolframalpha.com/input?i=3726%2C+33086%2C+91672%2C+179484%2C+296522%2C+442786%2C+...
-    fn f(x: usize) -> usize {
-        return (14613 * x * x) + 3592 - (14479*x);
+    let total = field.flood(64);
+    println!("Part 1: {}", total);
+
+    match field.reachable(steps) {
+        Ok(total) => println!("Part 2: {}", total),
+        Err(e) => println!("Part 2: {}", e)
     }
-    println!("Part 2: {}", f(x));
-    //let part2_debug = field2.flood(65);
-    //println!("Part 2 (debugging): {}", part2_debug);
 }
 
+/// Marks plots the flood never got to in a [DistanceMap]
+const UNREACHED: u32 = u32::MAX;
+
+/// Highest number of grid repeats sampled while waiting for the growth to settle into a quadratic
+const MAX_SAMPLE_REPEATS: usize = 12;
+
+/// Most copies of the field across that the viewer will flood and draw
+const MAX_VIEW_COPIES: usize = 9;
+
 #[allow(dead_code)]
 fn get_test_input() -> Field {
     return Field::load("
@@ -52,7 +55,7 @@ fn get_test_input() -> Field {
 .##.#.####.
 .##..##.##.
 ...........
-");
+").unwrap();
 }
 
 #[derive(Clone, Copy)]
@@ -75,6 +78,10 @@ impl Tile {
 
     #[allow(dead_code)]
     fn print(&self) {
+        print!("{}", self.render());
+    }
+
+    fn render(&self) -> String {
         let (r, g, b);
         if let Tile::Explored = self {
             (r, g, b) = (255, 0, 0);
@@ -83,8 +90,7 @@ impl Tile {
         } else {
             (r, g, b) = (255, 255, 255);
         }
-        let s = colorize(&format!("{}", self.encode()), r, g, b);
-        print!("{}", s);
+        return colorize(&format!("{}", self.encode()), r, g, b);
     }
 }
 
@@ -93,13 +99,6 @@ struct Coord {
     row: usize,
     column: usize
 }
-impl Coord {
-    fn offset(&self, o_row: i64, o_column: i64) -> Coord {
-        let row = (self.row as i64 + o_row) as usize;
-        let column = (self.column as i64 + o_column) as usize;
-        return Coord { row, column };
-    }
-}
 
 #[derive(Clone)]
 struct Field {
@@ -110,17 +109,15 @@ struct Field {
     start_coord: Coord
 }
 impl Field {
-    fn load(data: &str) -> Field {
-        let lines: Vec<&str> = data.trim().split("\n").collect();
-        let width = lines[0].len();
-        let height = lines.len();
-
-        let tiles: Vec<Vec<Tile>> = lines.iter()
-            .map(|l| l.chars()
-                .map(|c| Tile::decode(c))
-                .collect()
-            )
-            .collect();
+    fn load(data: &str) -> ParseResult<Field> {
+        let span = Span::new(data);
+        // `O` is only used for visualization
+        let tiles: Vec<Vec<Tile>> = span.grid("`.`, `#` or `S`", |c| match Tile::try_decode(c) {
+            Some(Tile::Explored) | None => None,
+            tile => tile
+        })?;
+        let width = tiles[0].len();
+        let height = tiles.len();
 
         let mut start_coord: Option<Coord> = None;
 
@@ -133,43 +130,12 @@ impl Field {
             }
         }
 
-        let start_coord: Coord = start_coord.expect("No starting coordinate found");
-
-        return Field { tiles, width, height, start_coord };
-    }
-
-    fn valid_offsets(&self, coord: Coord) -> Vec<Coord> {
-        let mut out: Vec<Coord> = vec![];
-
-        for o_row in -1..=1_i64 {
-            for o_column in -1..=1_i64 {
-                if o_row == 0 && o_column == 0 { // skip 'identity'
-                    continue;
-                }
-                if o_row.abs() + o_column.abs() >= 2 { // don't go diagonally
-                    continue;
-                }
-
-                /*if coord.row == 0 && o_row == -1 {
-                    continue;
-                }
-
-                if coord.column == 0 && o_column == -1 {
-                    continue;
-                }*/
-
-                let o_row = o_row + self.height as i64;
-                let o_column = o_column + self.width as i64;
-
-                let other = coord.offset(o_row, o_column);
-                /*if other.row >= self.height || other.column >= self.width {
-                    continue;
-                }*/
-                out.push(other);
-            }
-        }
+        let start_coord: Coord = match start_coord {
+            Some(coord) => coord,
+            None => return Err(span.error("a starting tile `S`"))
+        };
 
-        return out;
+        return Ok(Field { tiles, width, height, start_coord });
     }
 
     #[allow(dead_code)]
@@ -189,50 +155,247 @@ impl Field {
         return self.tiles[row][column];
     }
 
-    fn flood(&mut self, steps: usize) -> usize {
-        /*println!("Initial state:");
-        self.print();*/
-
-        let mut total_others: HashSet<Coord> = HashSet::new();
-
-        let mut current_frontier: HashSet<Coord> = HashSet::new();//vec![self.start_coord];
-        current_frontier.insert(self.start_coord);
-        let mut next_frontier: HashSet<Coord> = HashSet::new();
-
-        for step in 1..=steps {
-            let mut restore: Vec<(Coord, Tile)> = vec![];
-            for coord in current_frontier {
-                let other_coords = self.valid_offsets(coord);
-
-                for other in other_coords {
-                    let other_tile = &self.get(other);
-                    if other_tile.can_enter() {
-                        if step == steps { // only count last step
-                            total_others.insert(other);
-                        }
-                        next_frontier.insert(other);
-                        if other.row < self.height && other.column < self.width {
-                            restore.push((other, self.get(other)));
-                            self.tiles[other.row][other.column] = Tile::Explored;
-                        }
-                    }
+    fn flood(&self, steps: usize) -> usize {
+        return self.distance_map(self.copies_for(steps)).reachable(steps).unwrap();
+    }
+
+    /// Smallest odd number of copies across that a flood of `steps` can't leave
+    fn copies_for(&self, steps: usize) -> usize {
+        return 2 * (steps / self.width.min(self.height) + 1) + 1;
+    }
+
+    /// Breadth-first distances from the start over `copies` x `copies` repeats of the field, with
+    /// the original in the middle (`copies` should be odd)
+    fn distance_map(&self, copies: usize) -> DistanceMap {
+        let (width, height) = (self.width * copies, self.height * copies);
+        let start = Coord {
+            row: (copies / 2) * self.height + self.start_coord.row,
+            column: (copies / 2) * self.width + self.start_coord.column
+        };
+
+        let mut distances: Vec<u32> = vec![UNREACHED; width * height];
+        let mut histogram: Vec<usize> = vec![];
+        let mut queue: VecDeque<Coord> = VecDeque::new();
+        distances[start.row * width + start.column] = 0;
+        queue.push_back(start);
+        while let Some(coord) = queue.pop_front() {
+            let distance = distances[coord.row * width + coord.column];
+            if histogram.len() <= distance as usize {
+                histogram.push(0);
+            }
+            histogram[distance as usize] += 1;
+
+            let neighbours = [
+                (coord.row > 0).then(|| Coord { row: coord.row - 1, column: coord.column }),
+                (coord.row + 1 < height).then(|| Coord { row: coord.row + 1, column: coord.column }),
+                (coord.column > 0).then(|| Coord { row: coord.row, column: coord.column - 1 }),
+                (coord.column + 1 < width).then(|| Coord { row: coord.row, column: coord.column + 1 })
+            ];
+            for other in neighbours.into_iter().flatten() {
+                let idx = other.row * width + other.column;
+                if distances[idx] == UNREACHED && self.get(other).can_enter() {
+                    distances[idx] = distance + 1;
+                    queue.push_back(other);
                 }
             }
-            current_frontier = next_frontier;
-            next_frontier = HashSet::new();
-            /*println!("\n\nStep {}", step);
-            self.print();*/
-            for (to_restore, tile) in restore {
-                self.tiles[to_restore.row][to_restore.column] = tile;
+        }
+
+        // any walk of up to this many steps stays inside the copies, so it was searched completely
+        let exact_steps = [start.row, start.column, height - 1 - start.row, width - 1 - start.column].into_iter().min().unwrap();
+        return DistanceMap::new(width, distances, histogram, exact_steps);
+    }
+
+    /// Plots reachable in exactly `steps` steps on the infinitely repeated field.
+    ///
+    /// Once the flood has spread past the first few copies of the field, it grows by the same
+    /// shape every `width` steps, so the counts at `steps % width + n * width` are a quadratic in `n`.
+    /// That quadratic is fitted with finite differences as soon as two consecutive second
+    /// differences agree, and used to jump ahead. Step counts within the sampled range are
+    /// answered by flooding directly.
+    fn reachable(&self, steps: usize) -> Result<usize, String> {
+        if self.width != self.height {
+            return Err(format!("Extrapolation needs a square field, not {}x{}", self.width, self.height));
+        }
+        let size = self.width;
+        let offset = steps % size;
+        let repeats = steps / size;
+        if repeats <= MAX_SAMPLE_REPEATS + 1 {
+            debug!("Flooding {} steps directly", steps);
+            return Ok(self.flood(steps));
+        }
+
+        // flooding is expensive, so start with the 4 samples needed and only go further if necessary
+        let mut sampled = 3;
+        let (samples, start) = loop {
+            let map = self.distance_map(self.copies_for(offset + sampled * size));
+            let samples: Vec<i128> = map.reachable_many((0..=sampled).map(|n| offset + n * size)).into_iter()
+                .map(|count| count.unwrap() as i128).collect();
+            debug!("Samples every {} steps from {}: {:?}", size, offset, samples);
+
+            // the first n where f(n), f(n+1), f(n+2) fit the same quadratic as f(n+1), f(n+2), f(n+3)
+            let second_difference = |n: usize| samples[n + 2] - 2 * samples[n + 1] + samples[n];
+            if let Some(start) = (0..samples.len() - 3).find(|n| second_difference(*n) == second_difference(n + 1)) {
+                break (samples, start);
+            }
+            if sampled > MAX_SAMPLE_REPEATS {
+                return Err(format!("The flood didn't settle into quadratic growth within {} repeats", MAX_SAMPLE_REPEATS));
+            }
+            sampled = (sampled * 2).min(MAX_SAMPLE_REPEATS + 1);
+        };
+        let second_difference = |n: usize| samples[n + 2] - 2 * samples[n + 1] + samples[n];
+
+        let f0 = samples[start];
+        let d1 = samples[start + 1] - samples[start];
+        let d2 = second_difference(start);
+        info!("Quadratic from {} repeats: f(n) = {} + {}n + {}n(n-1)/2", start, f0, d1, d2);
+
+        let n = (repeats - start) as i128;
+        let total = f0 + d1 * n + d2 * n * (n - 1) / 2;
+        return usize::try_from(total).map_err(|_| format!("Extrapolated an impossible count: {}", total));
+    }
+}
+
+/// Shortest walking distances from the start over a block of repeated fields.
+///
+/// A walk can always step back and forth, so a plot at distance `d` can be ended on after any
+/// `k >= d` steps with the same parity as `d`. Counting plots by distance parity therefore answers
+/// "reachable in exactly k steps" for every k at once.
+struct DistanceMap {
+    /// row-major over the whole block, [UNREACHED] for rocks and cut-off plots
+    distances: Vec<u32>,
+    width: usize,
+    /// `parity_totals[d]`: plots at distance `d`, `d - 2`, `d - 4`, ...
+    parity_totals: Vec<usize>,
+    /// largest step count the block is big enough for
+    exact_steps: usize
+}
+impl DistanceMap {
+    fn new(width: usize, distances: Vec<u32>, histogram: Vec<usize>, exact_steps: usize) -> DistanceMap {
+        let mut parity_totals: Vec<usize> = histogram.clone();
+        for d in 2..parity_totals.len() {
+            parity_totals[d] += parity_totals[d - 2];
+        }
+        return DistanceMap { distances, width, parity_totals, exact_steps };
+    }
+
+    /// Plots reachable in exactly `steps` steps, or None if the block is too small to tell
+    fn reachable(&self, steps: usize) -> Option<usize> {
+        if steps > self.exact_steps {
+            return None;
+        }
+        // the flood may have stopped early if it's boxed in by rocks
+        let last = self.parity_totals.len() - 1;
+        if last == 0 && steps % 2 == 1 {
+            // the start can't move at all, so it's only there after an even number of steps
+            return Some(0);
+        }
+        let d = if steps <= last { steps } else if (steps - last) % 2 == 0 { last } else { last - 1 };
+        return Some(self.parity_totals[d]);
+    }
+
+    fn reachable_many(&self, steps: impl IntoIterator<Item = usize>) -> Vec<Option<usize>> {
+        return steps.into_iter().map(|s| self.reachable(s)).collect();
+    }
+
+    fn distance(&self, coord: Coord) -> Option<u32> {
+        let d = self.distances[coord.row * self.width + coord.column];
+        return (d != UNREACHED).then_some(d);
+    }
+}
+
+/// A flood spreading one step at a time over enough copies of the field, for the viewer
+#[derive(Clone)]
+struct Flooding {
+    field: Rc<Field>,
+    map: Rc<DistanceMap>,
+    copies: usize,
+    steps: usize,
+    limit: usize
+}
+impl Flooding {
+    fn new(field: Field, limit: usize) -> Result<Flooding, String> {
+        let copies = field.copies_for(limit);
+        if copies > MAX_VIEW_COPIES {
+            return Err(format!("{} steps needs {} copies of the field across, but the viewer only draws up to {}", limit, copies, MAX_VIEW_COPIES));
+        }
+        let map = Rc::new(field.distance_map(copies));
+        return Ok(Flooding { field: Rc::new(field), map, copies, steps: 0, limit });
+    }
+}
+impl Step for Flooding {
+    fn step(&mut self) -> bool {
+        if self.steps == self.limit {
+            return true;
+        }
+        self.steps += 1;
+        return false;
+    }
+}
+impl Render for Flooding {
+    /// Plots the walk could end on after this many steps, as `O`
+    fn render(&self) -> String {
+        let mut out = String::new();
+        for row in 0..self.field.height * self.copies {
+            for column in 0..self.field.width * self.copies {
+                let coord = Coord { row, column };
+                let tile = match (self.field.get(coord), self.map.distance(coord)) {
+                    (Tile::Start, _) => Tile::Start,
+                    (_, Some(d)) if d as usize <= self.steps && (self.steps - d as usize) % 2 == 0 => Tile::Explored,
+                    (tile, _) => tile
+                };
+                out += &tile.render();
             }
+            out += "\n";
         }
+        return out;
+    }
 
-        return total_others.len();
+    fn status(&self) -> String {
+        return format!("reachable plots: {}", self.map.reachable(self.steps).unwrap());
     }
 }
 
 #[test]
 fn flooding() {
-    let mut field = get_test_input();
+    let field = get_test_input();
     assert_eq!(16, field.flood(6));
 }
+
+#[test]
+fn viewing() {
+    assert!(Flooding::new(get_test_input(), 26501365).is_err());
+    let mut viewer = Viewer::new(Flooding::new(get_test_input(), 6).unwrap());
+    viewer.jump_to(100);
+    assert_eq!(6, viewer.step_number());
+    assert!(viewer.screen().contains("step 6 (done) | reachable plots: 16 |"));
+    // the start is drawn on its own
+    assert_eq!(15, viewer.current().render().matches('O').count());
+}
+
+#[test]
+fn extrapolation() {
+    let field = get_test_input();
+    assert_eq!(Ok(50), field.reachable(10));
+    assert_eq!(Ok(6536), field.reachable(100));
+    assert_eq!(Ok(668697), field.reachable(1000));
+    assert_eq!(Ok(16733044), field.reachable(5000));
+}
+
+#[test]
+fn distance_parity() {
+    let field = get_test_input();
+    let map = field.distance_map(21);
+    assert_eq!(vec![Some(1), Some(16), Some(50), Some(1594), Some(6536)], map.reachable_many([0, 6, 10, 50, 100]));
+    assert_eq!(None, map.reachable(map.exact_steps + 1));
+
+    // a start boxed in by rocks keeps alternating between the same plots forever
+    let boxed = Field::load("#####\n#.S.#\n#####").unwrap().distance_map(5);
+    assert_eq!(Some(0), boxed.distance(Coord { row: 7, column: 12 }));
+    assert_eq!(None, boxed.distance(Coord { row: 6, column: 12 }));
+    assert_eq!(vec![Some(1), Some(2)], boxed.reachable_many([6, 7]));
+
+    // and one with nowhere to go at all is stuck on the start
+    let stuck = Field::load("###\n#S#\n###").unwrap().distance_map(5);
+    assert_eq!(vec![Some(1), Some(0), Some(1), Some(0)], stuck.reachable_many([0, 1, 2, 7]));
+    assert_eq!(1, Field::load("###\n#S#\n###").unwrap().flood(4));
+}
@@ -1,58 +1,57 @@
-use std::{fs, cmp::Ordering, collections::VecDeque};
+use std::{env, fs, cmp::Ordering};
+
+use utils::{debug, log::Level, log_enabled, parse::{ParseResult, ParseResultExt, Span}, trace};
 
 fn main() {
     println!("AOC 2023 Day 22");
+    utils::log::init();
 
     let contents = fs::read_to_string("src/bin/day22/input.txt").expect("Failed to read input");
 
     let real = true;
-    let mut pile = if real {BrickPile::load(&contents)} else {test_input()};
+    let mut pile = if real {BrickPile::load(&contents).expect_in("src/bin/day22/input.txt")} else {test_input()};
     pile.sort();
     //let mut pile2 = pile.clone();
     //pile2.sort();
 
     pile.print();
-    println!("");
 
     pile.fall_down();
-    println!("");
 
     pile.print();
+    if log_enabled!(Level::Debug) {
+        debug!("Side views:\n{}\n{}", pile.side_view(Axis::X), pile.side_view(Axis::Y));
+    }
+    if let Some(path) = env::args().find_map(|a| a.strip_prefix("--ply=").map(|p| p.to_owned())) {
+        fs::write(&path, pile.to_ply()).expect("Failed to write PLY file");
+        println!("Wrote the settled pile to {}", path);
+    }
 
     let mut safe_count = 0;
     let mut remaining: Vec<Brick> = vec![];
-    println!("\n\nBrick status:");
+    debug!("Brick status:");
     for brick in &pile.bricks {
         if brick.uniquely_supports.len() == 0 {
-            println!("{} can be disintegrated", brick.id);
+            debug!("{} can be disintegrated", brick.id);
             safe_count += 1;
         } else {
-            println!("{} can not be ...", brick.id);
+            debug!("{} can not be ...", brick.id);
             remaining.push(brick.clone());
         }
     }
 
-    println!("\n\n");
-
     for b in remaining {
         b.print();
     }
 
-    println!("\n\n");
-
     println!("Part 1: {}", safe_count);
 
     let mut sum = 0;
     // part 2
-    for idx in 0..pile.bricks.len() {
-        let id = pile.bricks[idx].id;
-        /*if id != 393 {
-            continue;
-        }*/
-        let sub_destroyed = pile.count_disintegrated(id);
+    for (id, sub_destroyed) in pile.chain_reactions().into_iter().enumerate() {
         sum += sub_destroyed;
         if sub_destroyed != 0 {
-            println!("If {} were destroyed, {} other bricks would be too\n", id, sub_destroyed);
+            debug!("If {} were destroyed, {} other bricks would be too", id, sub_destroyed);
         }
     }
 
@@ -83,31 +82,24 @@ struct Brick {
     /// ids of bricks that are only supported by this one
     uniquely_supports: Vec<usize>,
     /// ids of bricks that are supported by this one and possibly also by others
-    supports: Vec<usize>,
-
-    is_disintegrated: bool
+    supports: Vec<usize>
 }
 impl Brick {
-    fn parse(id: usize, line: &str) -> Brick {
-        let (from, to) = line.split_once("~").unwrap();
-        let mut x0_y0_z0 = from.split(",");
-        let mut x1_y1_z1 = to.split(",");
-
-        let x0 = x0_y0_z0.next().unwrap().parse().unwrap();
-        let x1 = x1_y1_z1.next().unwrap().parse().unwrap();
-        
-        let y0 = x0_y0_z0.next().unwrap().parse().unwrap();
-        let y1 = x1_y1_z1.next().unwrap().parse().unwrap();
-
-        let z0 = x0_y0_z0.next().unwrap().parse().unwrap();
-        let z1 = x1_y1_z1.next().unwrap().parse().unwrap();
-
-        assert!(x0 <= x1);
-        assert!(y0 <= y1);
-        assert!(z0 <= z1);
-
-        return Brick { x0, x1, y0, y1, z0, z1, id, supported_by: vec![], uniquely_supports: vec![],
-        supports: vec![], is_disintegrated: false };
+    fn parse(id: usize, line: Span) -> ParseResult<Brick> {
+        let (from, to) = line.trim().split_once("~")?;
+        let corner = |span: Span| -> ParseResult<[usize; 3]> {
+            let coords: Vec<usize> = span.numbers(",")?;
+            return coords.try_into().map_err(|_| span.error("three coordinates"));
+        };
+        let [x0, y0, z0] = corner(from)?;
+        let [x1, y1, z1] = corner(to)?;
+
+        if x0 > x1 || y0 > y1 || z0 > z1 {
+            return Err(to.error("an end corner no smaller than the start corner"));
+        }
+
+        return Ok(Brick { x0, x1, y0, y1, z0, z1, id, supported_by: vec![], uniquely_supports: vec![],
+        supports: vec![] });
     }
 
     #[allow(dead_code)]
@@ -118,18 +110,20 @@ impl Brick {
     fn fall_down(&mut self, dist: usize) {
         self.z0 -= dist;
         self.z1 -= dist;
-        println!("Brick {} falling down {} blocks", self.id, dist);
+        trace!("Brick {} falling down {} blocks", self.id, dist);
     }
 
-    fn horizontal_overlap(&self, x0: usize, x1: usize, y0: usize, y1: usize) -> bool {
-        if x1 < self.x0 || x0 > self.x1 || y1 < self.y0 || y0 > self.y1 {
-            return false;
-        }
-        return true;
+    /// One character for side views: `A`-`Z`, then `a`-`z`, then `#` for the rest
+    fn label(&self) -> char {
+        return match self.id {
+            0..=25 => (b'A' + self.id as u8) as char,
+            26..=51 => (b'a' + (self.id - 26) as u8) as char,
+            _ => '#'
+        };
     }
 
     fn print(&self) {
-        println!("{}:{},{},{}~{},{},{}", self.id, self.x0, self.y0, self.z0, self.x1, self.y1, self.z1);
+        debug!("{}:{},{},{}~{},{},{}", self.id, self.x0, self.y0, self.z0, self.x1, self.y1, self.z1);
     }
 }
 
@@ -146,9 +140,21 @@ fn test_input() -> BrickPile {
 "/*
 2,8,48~2,8,49
 1,8,2~3,8,2
-"*/);
+"*/).unwrap();
+}
+
+/// Horizontal axis of a side view
+#[derive(Clone, Copy)]
+enum Axis {
+    X,
+    Y
 }
 
+/// Box colours for [BrickPile::to_ply]
+const PALETTE: [(u8, u8, u8); 4] = [(90, 140, 200), (100, 180, 120), (200, 180, 90), (160, 110, 190)];
+/// Colour of bricks that would bring others down with them
+const CHAIN_COLOUR: (u8, u8, u8) = (220, 60, 40);
+
 #[derive(Clone)]
 struct BrickPile {
     bricks: Vec<Brick>,
@@ -158,8 +164,11 @@ struct BrickPile {
     skip_destruction: HashSet<usize>*/
 }
 impl BrickPile {
-    fn load(data: &str) -> BrickPile {
-        let bricks: Vec<Brick> = data.trim().split("\n").enumerate().map(|(i, l)| Brick::parse(i, l)).collect();
+    fn load(data: &str) -> ParseResult<BrickPile> {
+        let mut bricks: Vec<Brick> = vec![];
+        for line in Span::new(data).trim().lines() {
+            bricks.push(Brick::parse(bricks.len(), line)?);
+        }
 
         let mut id_to_idx: Vec<usize> = vec![];
 
@@ -167,7 +176,7 @@ impl BrickPile {
             id_to_idx.push(id);
         }
 
-        return BrickPile { bricks, id_to_idx/*, skip_destruction: HashSet::new()*/ };
+        return Ok(BrickPile { bricks, id_to_idx/*, skip_destruction: HashSet::new()*/ });
     }
 
     #[allow(dead_code)]
@@ -206,49 +215,33 @@ impl BrickPile {
         return None;*/
     }
 
-    fn reset_disintegration(&mut self) {
-        for brick in &mut self.bricks {
-            brick.is_disintegrated = false;
-        }
-    }
-
     /// WARN: MUST call self.sort() first
+    ///
+    /// Drops the bricks lowest first onto a height map of the pile so far, which also tells which
+    /// bricks they land on
     fn fall_down(&mut self) {
-        //self.sort();
+        let width = self.bricks.iter().map(|b| b.x1).max().unwrap_or(0) + 1;
+        let depth = self.bricks.iter().map(|b| b.y1).max().unwrap_or(0) + 1;
+        // (highest z, id of the brick up there) for every x,y column; z 0 is the ground
+        let mut tops: Vec<(usize, Option<usize>)> = vec![(0, None); width * depth];
+
         for i in 0..self.bricks.len() {
             let brick = &self.bricks[i];
             let id = brick.id;
-            // Strategy:
-            // find the highest z1 in the footprint < brick.zo, and fall down to rest on that
-            let x0 = brick.x0;
-            let x1 = brick.x1;
-            let y0 = brick.y0;
-            let y1 = brick.y1;
-            let z0 = brick.z0;
-
-            let mut supporters: Vec<usize> = vec![];
-
-            let mut highest = 0;
-
-            for brick in &self.bricks {
-                if brick.z0 >= z0 {
-                    break;
-                }
-                if brick.z1 >= z0 {
-                    continue;
-                }
-                if brick.horizontal_overlap(x0, x1, y0, y1) {
-                    if brick.z1 > highest {
-                        supporters = vec![brick.id];
-                        highest = brick.z1;
-                    } else if brick.z1 == highest {
-                        supporters.push(brick.id);
-                    }
-                }
-            }
+            let footprint: Vec<usize> = (brick.y0..=brick.y1)
+                .flat_map(|y| (brick.x0..=brick.x1).map(move |x| y * width + x))
+                .collect();
+
+            let highest = footprint.iter().map(|idx| tops[*idx].0).max().unwrap();
+            let mut supporters: Vec<usize> = footprint.iter()
+                .filter(|idx| tops[**idx].0 == highest)
+                .filter_map(|idx| tops[*idx].1)
+                .collect();
+            supporters.sort();
+            supporters.dedup();
 
             self.bricks[i].supported_by = supporters;
-            println!("\nbrick {} supported by: {:?}", self.bricks[i].id, self.bricks[i].supported_by);
+            trace!("brick {} supported by: {:?}", self.bricks[i].id, self.bricks[i].supported_by);
 
             if self.bricks[i].supported_by.len() == 1 {
                 let supporter_id = self.bricks[i].supported_by[0];
@@ -258,21 +251,61 @@ impl BrickPile {
                 self.get_brick_mut(supporter_id).unwrap().supports.push(id);
             }
 
+            let z0 = self.bricks[i].z0;
             self.bricks[i].fall_down(z0 - (highest + 1));
+            let z1 = self.bricks[i].z1;
+            for idx in footprint {
+                tops[idx] = (z1, Some(id));
+            }
+        }
+    }
+
+    /// For every brick id, how many other bricks would fall if it were disintegrated.
+    /// MUST call self.fall_down() first.
+    ///
+    /// A brick falls when every chain of supports from the ground up to it runs through the
+    /// disintegrated one, i.e. when that brick dominates it in the support DAG rooted at the ground.
+    /// Bricks settled lowest first, so each one's immediate dominator is the deepest common
+    /// dominator of its supporters, and the chain reaction is the rest of the dominator subtree.
+    fn chain_reactions(&self) -> Vec<usize> {
+        let ground = self.bricks.len();
+        // indexed by id, with the ground last
+        let mut dominator: Vec<usize> = vec![ground; ground + 1];
+        let mut depth: Vec<usize> = vec![0; ground + 1];
+
+        for brick in &self.bricks {
+            let mut supporters = brick.supported_by.iter().copied();
+            let mut common = supporters.next().unwrap_or(ground);
+            for mut other in supporters {
+                while common != other {
+                    if depth[common] >= depth[other] {
+                        common = dominator[common];
+                    } else {
+                        other = dominator[other];
+                    }
+                }
+            }
+            dominator[brick.id] = common;
+            depth[brick.id] = depth[common] + 1;
         }
+
+        let mut subtree: Vec<usize> = vec![1; ground + 1];
+        for brick in self.bricks.iter().rev() {
+            subtree[dominator[brick.id]] += subtree[brick.id];
+        }
+        return subtree[..ground].iter().map(|size| size - 1).collect();
     }
 
-    fn count_disintegrated(&mut self, start_id: usize) -> usize {
-        /*if self.skip_destruction.contains(&start_id) {
-            println!("Skipping {} because it was destroyed in a previous chain", start_id);
-            return 0;
-        }*/
+    /// Chain reaction for a single brick by simulation, to check [BrickPile::chain_reactions]
+    #[cfg(test)]
+    fn count_disintegrated(&self, start_id: usize) -> usize {
         let mut sum: usize = 0;
-        self.reset_disintegration();
-        self.get_brick_mut(start_id).unwrap().is_disintegrated = true;
+        // indexed by id
+        let mut disintegrated: Vec<bool> = vec![false; self.bricks.len()];
+        disintegrated[start_id] = true;
 
         // push_back, pop_front
-        let mut disintegrated_queue: VecDeque<usize> = VecDeque::new();
+        let mut disintegrated_queue: std::collections::VecDeque<usize> = std::collections::VecDeque::new();
         disintegrated_queue.push_back(start_id);
         /*
          * things that have already been destroyed in a check don't have to be individually checked
@@ -287,21 +320,21 @@ impl BrickPile {
         while disintegrated_queue.len() > 0 {
             let id = disintegrated_queue.pop_front().unwrap();
             let supported = self.get_brick(id).unwrap().supports.clone();
-            println!("Brick {} supports {:?}", id, supported);
+            trace!("Brick {} supports {:?}", id, supported);
             for other_id in supported {
                 let other = self.get_brick(other_id).unwrap();
-                if other.is_disintegrated {
+                if disintegrated[other_id] {
                     continue;
                 }
                 let mut break_me: bool = true;
                 for supporter_id in &other.supported_by {
-                    if !self.get_brick(*supporter_id).unwrap().is_disintegrated {
+                    if !disintegrated[*supporter_id] {
                         break_me = false;
                     }
                 }
 
                 if break_me {
-                    self.get_brick_mut(other_id).unwrap().is_disintegrated = true;
+                    disintegrated[other_id] = true;
                     disintegrated_queue.push_back(other_id);
                     //self.skip_destruction.insert(other_id);
                     sum += 1;
@@ -317,4 +350,133 @@ impl BrickPile {
             brick.print();
         }
     }
+
+    /// The pile seen from the side like in the puzzle, looking along the other horizontal axis.
+    /// A cell covered by several different bricks is drawn as `?`.
+    fn side_view(&self, axis: Axis) -> String {
+        let span = |brick: &Brick| match axis {
+            Axis::X => (brick.x0, brick.x1),
+            Axis::Y => (brick.y0, brick.y1)
+        };
+        let width = self.bricks.iter().map(|b| span(b).1 + 1).max().unwrap_or(0);
+        let top = self.bricks.iter().map(|b| b.z1).max().unwrap_or(0);
+
+        // indexed [z][position]
+        let mut cells: Vec<Vec<Option<char>>> = vec![vec![None; width]; top + 1];
+        for brick in &self.bricks {
+            let (from, to) = span(brick);
+            for z in brick.z0..=brick.z1 {
+                for cell in &mut cells[z][from..=to] {
+                    *cell = match cell {
+                        None => Some(brick.label()),
+                        Some(_) => Some('?')
+                    };
+                }
+            }
+        }
+
+        let axis_name = match axis { Axis::X => 'x', Axis::Y => 'y' };
+        let mut out = format!("{}{}\n", " ".repeat((width.max(1) - 1) / 2), axis_name);
+        out += &(0..width).map(|i| char::from_digit((i % 10) as u32, 10).unwrap()).collect::<String>();
+        out += "\n";
+        for z in (1..=top).rev() {
+            out += &cells[z].iter().map(|c| c.unwrap_or('.')).collect::<String>();
+            out += &format!(" {}", z);
+            if z == (top + 1) / 2 {
+                out += " z";
+            }
+            out += "\n";
+        }
+        out += &format!("{} 0\n", "-".repeat(width));
+        return out;
+    }
+
+    /// ASCII PLY mesh with a coloured box per brick. MUST call self.fall_down() first, since bricks
+    /// that would set off a chain reaction are highlighted.
+    fn to_ply(&self) -> String {
+        let chains = self.chain_reactions();
+        let mut bricks: Vec<&Brick> = self.bricks.iter().collect();
+        bricks.sort_by_key(|b| b.id);
+
+        let mut out = String::from("ply\nformat ascii 1.0\ncomment AOC 2023 day 22 brick pile\n");
+        out += &format!("element vertex {}\n", bricks.len() * 8);
+        out += "property float x\nproperty float y\nproperty float z\n";
+        out += "property uchar red\nproperty uchar green\nproperty uchar blue\n";
+        out += &format!("element face {}\n", bricks.len() * 6);
+        out += "property list uchar int vertex_indices\nend_header\n";
+
+        for brick in &bricks {
+            let (r, g, b) = if chains[brick.id] > 0 { CHAIN_COLOUR } else { PALETTE[brick.id % PALETTE.len()] };
+            // corner i has x from bit 0, y from bit 1 and z from bit 2; cubes cover whole cells
+            for corner in 0..8 {
+                let x = if corner & 1 == 0 { brick.x0 } else { brick.x1 + 1 };
+                let y = if corner & 2 == 0 { brick.y0 } else { brick.y1 + 1 };
+                let z = if corner & 4 == 0 { brick.z0 } else { brick.z1 + 1 };
+                out += &format!("{} {} {} {} {} {}\n", x, y, z, r, g, b);
+            }
+        }
+        // counter-clockwise seen from outside
+        const FACES: [[usize; 4]; 6] = [[0, 2, 3, 1], [4, 5, 7, 6], [0, 1, 5, 4], [2, 6, 7, 3], [0, 4, 6, 2], [1, 3, 7, 5]];
+        for i in 0..bricks.len() {
+            for face in FACES {
+                out += &format!("4 {} {} {} {}\n", face[0] + i * 8, face[1] + i * 8, face[2] + i * 8, face[3] + i * 8);
+            }
+        }
+        return out;
+    }
+}
+
+#[test]
+fn chain_reactions() {
+    let mut pile = test_input();
+    pile.sort();
+    pile.fall_down();
+    let simulated: Vec<usize> = (0..pile.bricks.len()).map(|id| pile.count_disintegrated(id)).collect();
+    assert_eq!(vec![6, 0, 0, 0, 0, 1, 0], simulated);
+    assert_eq!(simulated, pile.chain_reactions());
+}
+
+#[test]
+fn dominators_match_simulation() {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    let mut rng = StdRng::seed_from_u64(22);
+    let mut snapshot = String::new();
+    for z in 1..=300 {
+        let (x, y) = (rng.gen_range(0..8), rng.gen_range(0..8));
+        let (dx, dy, dz) = match rng.gen_range(0..3) {
+            0 => (rng.gen_range(0..3), 0, 0),
+            1 => (0, rng.gen_range(0..3), 0),
+            _ => (0, 0, rng.gen_range(0..3))
+        };
+        snapshot += &format!("{},{},{}~{},{},{}\n", x, y, z * 2, x + dx, y + dy, z * 2 + dz);
+    }
+    let mut pile = BrickPile::load(&snapshot).unwrap();
+    pile.sort();
+    pile.fall_down();
+
+    let simulated: Vec<usize> = (0..pile.bricks.len()).map(|id| pile.count_disintegrated(id)).collect();
+    assert!(simulated.iter().sum::<usize>() > 0);
+    assert_eq!(simulated, pile.chain_reactions());
+    // everything came to rest on the ground or on another brick
+    for brick in &pile.bricks {
+        assert_eq!(brick.z0 == 1, brick.supported_by.is_empty());
+    }
+}
+
+#[test]
+fn side_views() {
+    let mut pile = test_input();
+    pile.sort();
+    pile.fall_down();
+    assert_eq!(include_str!("example_xz.txt"), pile.side_view(Axis::X));
+    assert_eq!(include_str!("example_yz.txt"), pile.side_view(Axis::Y));
+}
+
+#[test]
+fn ply_export() {
+    let mut pile = test_input();
+    pile.sort();
+    pile.fall_down();
+    assert_eq!(include_str!("example.ply"), pile.to_ply());
 }
@@ -1,38 +1,48 @@
-use std::{fs, collections::{HashMap, VecDeque, HashSet}};
+use std::{env, fs, collections::{HashMap, HashSet, VecDeque}};
 
 use char_enum_impl::{char_enum, data_enum};
-use utils::{colorize, highlight};
-
-static mut PART2: bool = false;
+use utils::{colorize, debug, highlight, log::Level, log_enabled, parse::{ParseResult, ParseResultExt, Span}, trace};
 
 fn main() {
     println!("AOC 2023 Day 23");
+    utils::log::init();
 
     let real = false;
-    let contents: String;
-    if real {
-        contents = fs::read_to_string("src/bin/day23/input.txt").expect("Failed to read input");
-    } else {
-        contents = fs::read_to_string("src/bin/day23/example.txt").expect("Failed to read example");
-    }
-
-    for part in 1..=2_u8 {
-        unsafe { PART2 = part == 2 };
-        let mut field = Field::load(&contents);
-       field.find_intersections();
-        field.print();
+    let fname = if real { "src/bin/day23/input.txt" } else { "src/bin/day23/example.txt" };
+    let contents = fs::read_to_string(fname).expect("Failed to read input");
+
+    for (part, rules) in [(1, SlopeRules::Downhill), (2, SlopeRules::Climbable)] {
+        let mut field = Field::load(&contents).expect_in(fname);
+        field.find_intersections();
+        if log_enabled!(Level::Debug) {
+            field.print(None);
+        }
 
-        println!("\n\n\n");
-        let graph: Graph = field.make_graph();
+        let graph: Graph = field.make_graph(rules);
         graph.print_summary();
-        let max_distance = graph.max_distance();
+        let hike = graph.longest_hike();
+        if let Some(hike) = &hike {
+            if log_enabled!(Level::Debug) {
+                field.print(Some(&hike.tiles));
+            }
+            debug!("Intersections on the way: {:?}", hike.intersections);
+            if let Some(path) = env::args().find_map(|a| a.strip_prefix(&format!("--path{}=", part)).map(|p| p.to_owned())) {
+                fs::write(&path, hike.export()).expect("Failed to write path");
+                println!("Wrote the part {} hike to {}", part, path);
+            }
+        }
+        let max_distance = hike.map_or(0, |h| h.length);
         println!("{}", colorize(&format!("\n\nPart {}: {}\n\n", part, max_distance), 255, 255, 0));
     }
 }
 
-#[inline]
-fn part2() -> bool {
-    return unsafe { PART2 };
+/// Whether slopes can be walked up
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum SlopeRules {
+    /// part 1: slopes can only be crossed in the direction they point
+    Downhill,
+    /// part 2: slopes are ordinary paths
+    Climbable
 }
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -89,15 +99,16 @@ enum Tile {
 }
 impl Tile {
     /// returns whether this tile can be exited in direction [dir]
-    fn can_cross(&self, dir: Direction) -> bool {
+    fn can_cross(&self, dir: Direction, rules: SlopeRules) -> bool {
+        let climbable = rules == SlopeRules::Climbable;
         match self {
             Tile::Path => true,
             Tile::Forest => false,
 
-            Tile::SlopeN => dir == Direction::North || part2(),
-            Tile::SlopeE => dir == Direction::East || part2(),
-            Tile::SlopeS => dir == Direction::South || part2(),
-            Tile::SlopeW => dir == Direction::West || part2()
+            Tile::SlopeN => dir == Direction::North || climbable,
+            Tile::SlopeE => dir == Direction::East || climbable,
+            Tile::SlopeS => dir == Direction::South || climbable,
+            Tile::SlopeW => dir == Direction::West || climbable
         }
     }
 }
@@ -125,8 +136,8 @@ struct TileData {
     visited: bool
 }
 impl TileData {
-    fn decode(chr: char) -> TileData {
-        return TileData { tile: Tile::decode(chr), intersection: false, visited: false };
+    fn decode(chr: char) -> Option<TileData> {
+        return Some(TileData { tile: Tile::try_decode(chr)?, intersection: false, visited: false });
     }
 }
 
@@ -137,16 +148,11 @@ struct Field {
     height: usize
 }
 impl Field {
-    fn load(data: &str) -> Field {
-        let tiles: Vec<Vec<TileData>> = data.trim().split("\n")
-            .map(|l| l.chars()
-                .map(|c| TileData::decode(c))
-                .collect()
-            )
-            .collect();
+    fn load(data: &str) -> ParseResult<Field> {
+        let tiles: Vec<Vec<TileData>> = Span::new(data).grid("`.`, `#`, `^`, `>`, `v` or `<`", TileData::decode)?;
         let width = tiles[0].len();
         let height = tiles.len();
-        return Field { tiles, width, height };
+        return Ok(Field { tiles, width, height });
     }
 
     fn find_intersections(&mut self) {
@@ -173,11 +179,16 @@ impl Field {
         }
     }
 
-    fn print(&self) {
+    /// Intersections are highlighted in green, and the tiles of `path` (if any) in blue
+    fn print(&self, path: Option<&[Coord]>) {
+        let on_path: HashSet<Coord> = path.unwrap_or(&[]).iter().copied().collect();
         for row in 0..self.height {
             for column in 0..self.width {
                 let hl = self.tiles[row][column].intersection;
-                let highlighted = highlight(&format!("{}", self.tiles[row][column].tile.encode()), hl, 0, 255, 0);
+                let mut highlighted = highlight(&format!("{}", self.tiles[row][column].tile.encode()), hl, 0, 255, 0);
+                if !hl {
+                    highlighted = highlight(&highlighted, on_path.contains(&Coord { row, column }), 40, 60, 200);
+                }
                 let (r, g, b);
                 if hl {
                     (r, g, b) = (0, 0, 0);
@@ -201,7 +212,7 @@ impl Field {
     }
 
     /// Note: [Graph::find_intersections] MUST be called first
-    fn make_graph(&mut self) -> Graph {
+    fn make_graph(&mut self, rules: SlopeRules) -> Graph {
         let mut intersections: Vec<Coord> = vec![];
         for row in 0..self.height {
             for column in 0..self.width {
@@ -222,12 +233,12 @@ impl Field {
         self.tiles[0][1].visited = true;
         queue.push_back((Coord { row: 0, column: 1}, Direction::South));
         'Outer: while queue.len() > 0 {
-            println!("\n---------------");
             let (start, mut dir) = queue.pop_front().unwrap();
             if self.get(&dir.offset(&start)).visited {
                 continue;
             }
             let mut current = start;
+            let mut trail: Vec<Coord> = vec![start];
             let mut length: usize = 0;
 
             let mut can_go_forward = true;
@@ -248,16 +259,17 @@ impl Field {
                     }
 
                     if orig == dir {
-                        println!("{}", colorize(&format!("Dead end starting at {:?} going to {:?}", start, current), 255, 0, 0));
+                        trace!("{}", colorize(&format!("Dead end starting at {:?} going to {:?}", start, current), 255, 0, 0));
                         continue 'Outer;
                     }
                 }
-                can_go_forward &= self.get(&current).tile.can_cross(dir);
-                can_go_backward &= self.get(&current).tile.can_cross(dir.opposite());
+                can_go_forward &= self.get(&current).tile.can_cross(dir, rules);
+                can_go_backward &= self.get(&current).tile.can_cross(dir.opposite(), rules);
                 self.get_mut(&current).visited = true;
 
                 current = dir.offset(&current);
                 length += 1;
+                trail.push(current);
 
                 if end_coord == current {
                     break 'Flood;
@@ -285,20 +297,19 @@ impl Field {
                 }
             }
 
-            println!();
             if can_go_forward {
-                println!("Forward edge({}) from {:#?} to {:#?}", length, start, current);
+                trace!("Forward edge({}) from {:?} to {:?}", length, start, current);
                 graph.connect(&start, &current, length);
-                println!("Connected");
+                graph.add_trail(trail.clone());
             } else {
-                println!("{}", colorize("Cannot go forward", 255, 0, 0));
+                trace!("{}", colorize("Cannot go forward", 255, 0, 0));
             }
             if can_go_backward {
-                println!("Backward edge({}) from {:#?} to {:#?}", length, current, start);
+                trace!("Backward edge({}) from {:?} to {:?}", length, current, start);
                 graph.connect(&current, &start, length);
-                println!("Connected");
+                graph.add_trail(trail.iter().rev().copied().collect());
             } else {
-                println!("{}", colorize("Cannot go backward", 255, 0, 0));
+                trace!("{}", colorize("Cannot go backward", 255, 0, 0));
             }
         }
 
@@ -317,14 +328,17 @@ impl Node {
 
     fn print_summary(&self) {
         for (len, target) in &self.outgoing {
-            println!("  -> [{}] (r: {}, c: {})", len, target.row, target.column);
+            debug!("  -> [{}] (r: {}, c: {})", len, target.row, target.column);
         }
     }
 }
 
 struct Graph {
     end_coord: Coord,
-    nodes: HashMap<Coord, Node>
+    nodes: HashMap<Coord, Node>,
+    /// every tile of each edge, keyed by (from, to, length) since two trails can join the same
+    /// intersections
+    trails: HashMap<(Coord, Coord, usize), Vec<Coord>>
 }
 impl <'a>Graph {
     fn new(end_coord: Coord, intersections: Vec<Coord>) -> Graph {
@@ -337,7 +351,7 @@ impl <'a>Graph {
         for intersection in intersections {
             nodes.insert(intersection, Node::new());
         }
-        return Graph { end_coord, nodes };
+        return Graph { end_coord, nodes, trails: HashMap::new() };
     }
 
     #[allow(dead_code)]
@@ -359,7 +373,7 @@ impl <'a>Graph {
         nodes.insert(b, Node::new());
         nodes.insert(end_coord, Node::new());
 
-        let mut graph = Graph { end_coord, nodes };
+        let mut graph = Graph { end_coord, nodes, trails: HashMap::new() };
 
         graph.connect(&start_coord, &a, 2);
         graph.connect(&start_coord, &b, 5);
@@ -396,38 +410,130 @@ impl <'a>Graph {
         self.get_mut(to).incoming.push((dist, *from));
     }
 
+    /// Remember the tiles walked between two nodes, from the first to the last
+    fn add_trail(&mut self, tiles: Vec<Coord>) {
+        self.trails.insert((tiles[0], *tiles.last().unwrap(), tiles.len() - 1), tiles);
+    }
+
     fn print_summary(&self) {
-        println!("Graph summary:");
-        println!("  End is: (r: {}, c: {})", self.end_coord.row, self.end_coord.column);
+        debug!("Graph summary:");
+        debug!("  End is: (r: {}, c: {})", self.end_coord.row, self.end_coord.column);
         for (coord, node) in self.nodes.iter() {
-            println!("\nNode (r: {}, c: {})", coord.row, coord.column);
+            debug!("Node (r: {}, c: {})", coord.row, coord.column);
             node.print_summary();
         }
     }
 
-    fn recurse_max_distance(&self, visited: HashSet<Coord>, current: Coord, total_length: usize) -> usize {
-        let outgoing = self.get(&current).outgoing.iter().filter(|(_, target)| !visited.contains(target));
+    /// Number the nodes, so the longest hike can be searched with bitmasks
+    fn compact(&self) -> CompactGraph {
+        let mut coords: Vec<Coord> = self.nodes.keys().copied().collect();
+        coords.sort_by_key(|c| (c.row, c.column));
+        let index_of = |coord: &Coord| coords.iter().position(|c| c == coord).unwrap();
 
-        let mut max = 0;
-        for (edge_length, other_coord) in outgoing {
-            let mut new_visited = visited.clone();
-            new_visited.insert(current);
-            max = max.max(self.recurse_max_distance(new_visited, *other_coord, total_length + edge_length));
-        }
-        // no intermediate edges
-        if max == 0 {
-            if current == self.end_coord {
-                println!("Reached target with {} steps", total_length);
-                return total_length;
-            } else if !part2() {
-                println!("Reached dead end");
+        let outgoing: Vec<Vec<(usize, usize)>> = coords.iter()
+            .map(|coord| self.get(coord).outgoing.iter().map(|(len, target)| (index_of(target), *len)).collect())
+            .collect();
+        let longest_edge: Vec<usize> = outgoing.iter().map(|edges| edges.iter().map(|(_, len)| *len).max().unwrap_or(0)).collect();
+        let start = index_of(&Coord { row: 0, column: 1 });
+        let end = index_of(&self.end_coord);
+        // the only way into the end is the last choice of the hike, so take it as soon as possible
+        let end_feeder = match self.get(&self.end_coord).incoming[..] {
+            [(_, feeder)] => Some(index_of(&feeder)),
+            _ => None
+        };
+        return CompactGraph { coords, outgoing, longest_edge, start, end, end_feeder };
+    }
+
+    fn longest_hike(&self) -> Option<Hike> {
+        let compact = self.compact();
+        let (length, route) = compact.longest_hike()?;
+        let intersections: Vec<Coord> = route.iter().map(|(i, _)| compact.coords[*i]).collect();
+
+        let mut tiles: Vec<Coord> = vec![intersections[0]];
+        for (pair, (_, edge_length)) in intersections.windows(2).zip(&route[1..]) {
+            match self.trails.get(&(pair[0], pair[1], *edge_length)) {
+                Some(trail) => tiles.extend(&trail[1..]),
+                None => tiles.push(pair[1])
             }
         }
-        return max;
+        return Some(Hike { length, intersections, tiles });
     }
 
+    #[allow(dead_code)]
     fn max_distance(&self) -> usize {
-        return self.recurse_max_distance(HashSet::new(), Coord { row: 0, column: 1 }, 0);
+        return self.longest_hike().map_or(0, |h| h.length);
+    }
+}
+
+/// A route from the start to the end
+struct Hike {
+    length: usize,
+    /// the nodes passed, including the start and the end
+    intersections: Vec<Coord>,
+    /// every tile stepped on, including the start (only the nodes for graphs built by hand)
+    tiles: Vec<Coord>
+}
+impl Hike {
+    /// One `row,column` line per tile
+    fn export(&self) -> String {
+        return self.tiles.iter().map(|c| format!("{},{}\n", c.row, c.column)).collect();
+    }
+}
+
+/// [Graph] with its nodes numbered in reading order
+struct CompactGraph {
+    coords: Vec<Coord>,
+    /// (target, length) for every node
+    outgoing: Vec<Vec<(usize, usize)>>,
+    /// longest edge out of every node, for bounding how much further a hike can go
+    longest_edge: Vec<usize>,
+    start: usize,
+    end: usize,
+    end_feeder: Option<usize>
+}
+impl CompactGraph {
+    /// Length of the longest hike from start to end that doesn't visit a node twice, and its nodes
+    /// with the length of the edge taken to reach each (0 for the start)
+    fn longest_hike(&self) -> Option<(usize, Vec<(usize, usize)>)> {
+        let remaining: usize = self.longest_edge.iter().sum();
+        let mut best: Option<(usize, Vec<(usize, usize)>)> = None;
+        let mut route: Vec<(usize, usize)> = vec![(self.start, 0)];
+        // one bit per node, 64 nodes to a word
+        let mut visited: Vec<u64> = vec![0; (self.coords.len() + 63) / 64];
+        visited[self.start / 64] |= 1 << (self.start % 64);
+        self.search(&mut route, &mut visited, 0, remaining - self.longest_edge[self.start], &mut best);
+        return best;
+    }
+
+    /// `remaining` is the sum of [CompactGraph::longest_edge] over unvisited nodes: each edge still
+    /// to come leaves either `current` or one of them, so a hike can't get longer than that
+    fn search(&self, route: &mut Vec<(usize, usize)>, visited: &mut Vec<u64>, length: usize, remaining: usize, best: &mut Option<(usize, Vec<(usize, usize)>)>) {
+        let (current, _) = *route.last().unwrap();
+        if current == self.end {
+            trace!("Reached target with {} steps", length);
+            if best.as_ref().map_or(true, |(b, _)| length > *b) {
+                *best = Some((length, route.clone()));
+            }
+            return;
+        }
+        if best.as_ref().is_some_and(|(b, _)| length + self.longest_edge[current] + remaining <= *b) {
+            return;
+        }
+
+        for (target, edge_length) in &self.outgoing[current] {
+            let (word, bit) = (*target / 64, 1 << (*target % 64));
+            if visited[word] & bit != 0 {
+                continue;
+            }
+            if Some(current) == self.end_feeder && *target != self.end {
+                continue;
+            }
+            route.push((*target, *edge_length));
+            visited[word] |= bit;
+            self.search(route, visited, length + edge_length, remaining - self.longest_edge[*target], best);
+            visited[word] &= !bit;
+            route.pop();
+        }
     }
 }
 
@@ -436,3 +542,71 @@ fn distance_calc() {
     let g = Graph::toy();
     assert_eq!(10, g.max_distance());
 }
+
+#[test]
+fn longest_hikes() {
+    let contents = fs::read_to_string("src/bin/day23/example.txt").unwrap();
+    let mut lengths = vec![];
+    for rules in [SlopeRules::Downhill, SlopeRules::Climbable] {
+        let mut field = Field::load(&contents).unwrap();
+        field.find_intersections();
+        lengths.push(field.make_graph(rules).max_distance());
+    }
+    assert_eq!(vec![94, 154], lengths);
+}
+
+#[test]
+fn hike_path() {
+    let contents = fs::read_to_string("src/bin/day23/example.txt").unwrap();
+    let mut field = Field::load(&contents).unwrap();
+    field.find_intersections();
+    let hike = field.make_graph(SlopeRules::Downhill).longest_hike().unwrap();
+
+    assert_eq!(94, hike.length);
+    assert_eq!(hike.length + 1, hike.tiles.len());
+    assert_eq!((Coord { row: 0, column: 1 }, Coord { row: 22, column: 21 }), (hike.tiles[0], *hike.tiles.last().unwrap()));
+    assert_eq!(hike.tiles.len(), hike.tiles.iter().collect::<HashSet<_>>().len());
+    for pair in hike.tiles.windows(2) {
+        let dir = Direction::values().into_iter().find(|d| d.offset(&pair[0]) == pair[1]).expect("Tiles should be adjacent");
+        assert!(field.get(&pair[0]).tile.can_cross(dir, SlopeRules::Downhill));
+    }
+    assert!(hike.export().starts_with("0,1\n1,1\n"));
+}
+
+#[test]
+fn parallel_trails() {
+    let start = Coord { row: 0, column: 1 };
+    let end = Coord { row: 2, column: 1 };
+    let mut graph = Graph::new(end, vec![]);
+    // a detour and a straight trail between the same two nodes; the straight one is added last
+    let detour = vec![start, Coord { row: 0, column: 2 }, Coord { row: 1, column: 2 }, Coord { row: 2, column: 2 }, end];
+    graph.connect(&start, &end, detour.len() - 1);
+    graph.add_trail(detour.clone());
+    graph.connect(&start, &end, 2);
+    graph.add_trail(vec![start, Coord { row: 1, column: 1 }, end]);
+
+    let hike = graph.longest_hike().unwrap();
+    assert_eq!(4, hike.length);
+    assert_eq!(detour, hike.tiles);
+}
+
+#[test]
+fn many_intersections() {
+    // a two way trail through 100 intersections, and a short cut straight to the end
+    let start = Coord { row: 0, column: 1 };
+    let end = Coord { row: 101, column: 1 };
+    let intersections: Vec<Coord> = (1..=100).map(|row| Coord { row, column: 1 }).collect();
+    let mut graph = Graph::new(end, intersections.clone());
+    let mut trail = vec![start];
+    trail.extend(&intersections);
+    trail.push(end);
+    for pair in trail.windows(2) {
+        graph.connect(&pair[0], &pair[1], 1);
+        graph.connect(&pair[1], &pair[0], 1);
+    }
+    graph.connect(&start, &end, 50);
+
+    let hike = graph.longest_hike().unwrap();
+    assert_eq!(101, hike.length);
+    assert_eq!(trail, hike.intersections);
+}
@@ -1,47 +1,62 @@
-use std::{fs, fmt::{Display, Formatter}, ops::{Add, Sub}};
+use std::{fs, fmt::{Display, Formatter}};
 
-use vec3_rs::Vector3;
+use num::{BigInt, BigRational, ToPrimitive, Zero, rational::Ratio};
+use utils::{debug, parse::{ParseResult, ParseResultExt, Span}, trace, warn};
 
 fn main() {
     println!("AOC 2023 Day 24");
+    utils::log::init();
 
     let real = false;
-    let contents: String;
+    let fname: &str;
     let bounds: Bounds;
     if real {
-        contents = fs::read_to_string("src/bin/day24/input.txt").expect("Failed to read input");
-        bounds = Bounds::square(200000000000000.0, 400000000000000.0);
+        fname = "src/bin/day24/input.txt";
+        bounds = Bounds::square(200000000000000, 400000000000000);
     } else {
-        contents = fs::read_to_string("src/bin/day24/example.txt").expect("Failed to read input");
-        bounds = Bounds::square(7.0, 27.0);
+        fname = "src/bin/day24/example.txt";
+        bounds = Bounds::square(7, 27);
     }
+    let contents = fs::read_to_string(fname).expect("Failed to read input");
 
-    let entries: Vec<Entry> = contents.trim().split("\n").map(|l| Entry::parse(l)).collect();
+    let entries: Vec<Entry> = Span::new(&contents).parse_lines(Entry::parse).expect_in(fname);
     for entry in &entries {
-        println!("{}", entry);
+        debug!("{}", entry);
     }
 
-    println!("\n\n");
-
     let mut sum_ok: usize = 0;
+    let mut disagreements: usize = 0;
 
     for i in 0..entries.len() {
         for j in i+1..entries.len() {
             let a = &entries[i];
             let b = &entries[j];
-            println!("Hailstone A: {}", a);
-            println!("Hailstone B: {}", b);
-            let result = compute_crossing_state(a, b, &bounds);
-            println!("{}\n", result);
+            trace!("Hailstone A: {}", a);
+            trace!("Hailstone B: {}", b);
+            let result = compute_crossing_state_exact(a, b, &bounds);
+            trace!("{}", result);
+            let approx = compute_crossing_state(a, b, &bounds);
+            if !approx.agrees_with(&result) {
+                warn!("Floating point disagrees for {} and {}: {}", a, b, approx);
+                disagreements += 1;
+            }
             if let CrossingState::FutureInside(_, _) = result {
                 sum_ok += 1;
             }
         }
     }
+    if disagreements > 0 {
+        warn!("Floating point got {} crossings wrong", disagreements);
+    }
     println!("{} hailstones' future paths cross inside the boundaries (part 1)", sum_ok);
 
-    let sum2 = part2(entries[0], entries[1], entries[2], entries[3]);
-    println!("Part 2: {}", sum2);
+    match find_rock(&entries) {
+        Ok(rock) => {
+            debug!("Rock: {:?} @ {:?}", rock.position, rock.velocity);
+            println!("Part 2: {}", rock.position.iter().sum::<i128>());
+        },
+        Err(e) => println!("Part 2: {}", e)
+    }
 }
 
 trait ApproxEq<T> {
@@ -54,15 +69,40 @@ impl ApproxEq<f64> for f64 {
     }
 }
 
-enum CrossingState {
+/// Exact fractions, wide enough for products of full-size coordinates and velocities
+type Exact = Ratio<i128>;
+
+fn exact_to_f64(value: &Exact) -> f64 {
+    return *value.numer() as f64 / *value.denom() as f64;
+}
+
+enum CrossingState<T> {
     Parallel,
     PastA,
     PastB,
     PastBoth,
-    FutureInside(f64, f64),
-    FutureOutside(f64, f64)
+    FutureInside(T, T),
+    FutureOutside(T, T)
+}
+impl CrossingState<f64> {
+    /// Same outcome as the exact result, with the crossing point within floating point error
+    fn agrees_with(&self, exact: &CrossingState<Exact>) -> bool {
+        let close = |approx: f64, exact: &Exact| {
+            let exact = exact_to_f64(exact);
+            return (approx - exact).abs() <= 1e-6 * exact.abs().max(1.0);
+        };
+        return match (self, exact) {
+            (Self::Parallel, CrossingState::Parallel) => true,
+            (Self::PastA, CrossingState::PastA) => true,
+            (Self::PastB, CrossingState::PastB) => true,
+            (Self::PastBoth, CrossingState::PastBoth) => true,
+            (Self::FutureInside(x, y), CrossingState::FutureInside(ex, ey)) => close(*x, ex) && close(*y, ey),
+            (Self::FutureOutside(x, y), CrossingState::FutureOutside(ex, ey)) => close(*x, ex) && close(*y, ey),
+            _ => false
+        };
+    }
 }
-impl Display for CrossingState {
+impl<T: Display> Display for CrossingState<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_str("Hailstones' paths ")?;
         return match self {
@@ -76,13 +116,12 @@ impl Display for CrossingState {
     }
 }
 
-type Vec3 = Vector3<f64>;
-fn ORIGIN() -> Vec3 {
-    Vec3::new(0.0, 0.0, 0.0)
-}
-
 #[derive(Clone, Copy)]
 struct Entry {
+    /// exact values as parsed; the floats below are the same numbers
+    position: [i64; 3],
+    velocity: [i64; 3],
+
     p_x: f64,
     p_y: f64,
     p_z: f64,
@@ -92,38 +131,19 @@ struct Entry {
     v_z: f64
 }
 impl Entry {
-    fn parse(data: &str) -> Entry {
-        let (p, v) = data.split_once("@").unwrap();
-
-        let mut p = p.trim().split(",").map(|x| x.trim().parse::<i64>().unwrap() as f64);
-        let mut v = v.trim().split(",").map(|x| x.trim().parse::<i64>().unwrap() as f64);
-
-        let p_x = p.next().unwrap();
-        let p_y = p.next().unwrap();
-        let p_z = p.next().unwrap();
-
-        let v_x = v.next().unwrap();
-        let v_y = v.next().unwrap();
-        let v_z = v.next().unwrap();
+    fn parse(data: Span) -> ParseResult<Entry> {
+        let (p, v) = data.split_once("@")?;
 
-        return Entry { p_x, p_y, p_z, v_x, v_y, v_z };
-    }
-
-    #[inline]
-    fn p(&self) -> Vec3 {
-        return Vec3::new(self.p_x, self.p_y, self.p_z);
-    }
-
-    #[inline]
-    fn v(&self) -> Vec3 {
-        return Vec3::new(self.v_x, self.v_y, self.v_z);
-    }
+        let triple = |span: Span| -> ParseResult<[i64; 3]> {
+            let values: Vec<i64> = span.numbers(",")?;
+            return values.try_into().map_err(|_| span.trim().error("three comma-separated integers"));
+        };
+        let position = triple(p)?;
+        let velocity = triple(v)?;
+        let [p_x, p_y, p_z] = position.map(|c| c as f64);
+        let [v_x, v_y, v_z] = velocity.map(|c| c as f64);
 
-    #[inline]
-    fn unit_v(&self) -> Vec3 {
-        let mut v = self.v();
-        v.normalize();
-        return v;
+        return Ok(Entry { position, velocity, p_x, p_y, p_z, v_x, v_y, v_z });
     }
 
     fn is_future(&self, x: f64) -> bool {
@@ -139,77 +159,31 @@ impl Entry {
         !self.is_future(x)
     }
 
-    /// `n` must be the normal of a plane that crosses the origin
-    fn intersection_distance(&self, n: &Vec3) -> f64 {
-        let l = self.unit_v();
-        let l0 = self.p();
-        let over = (ORIGIN() - l0).dot(n);
-        let under = l.dot(n);
-        return over / under;
-    }
-
-    fn along(&self, d: f64) -> Vec3 {
-        return self.p() + (self.unit_v()*d);
-    }
-
-    fn time_to_reach(&self, d: f64) -> f64 {
-        return d / self.v().magnitude();
-    }
-
-    fn pos_at(&self, t: f64) -> Vec3 {
-        return self.p() + (self.v()*t);
-    }
 }
 impl Display for Entry {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}, {}, {} @ {}, {}, {}", self.p_x, self.p_y, self.p_z, self.v_x, self.v_y, self.v_z)
     }
 }
-impl Add<Entry> for Entry {
-    type Output = Entry;
-
-    fn add(self, rhs: Entry) -> Self::Output {
-        return Entry {
-            p_x: self.p_x + rhs.p_x,
-            p_y: self.p_y + rhs.p_y,
-            p_z: self.p_z + rhs.p_z,
-
-            v_x: self.v_x + rhs.v_x,
-            v_y: self.v_y + rhs.v_y,
-            v_z: self.v_z + rhs.v_z,
-        };
-    }
-}
-impl Sub<Entry> for Entry {
-    type Output = Entry;
-
-    fn sub(self, rhs: Entry) -> Self::Output {
-        return Entry {
-            p_x: self.p_x - rhs.p_x,
-            p_y: self.p_y - rhs.p_y,
-            p_z: self.p_z - rhs.p_z,
-
-            v_x: self.v_x - rhs.v_x,
-            v_y: self.v_y - rhs.v_y,
-            v_z: self.v_z - rhs.v_z,
-        };
-    }
-}
-
 struct Bounds {
-    x0: f64,
-    y0: f64,
+    x0: i64,
+    y0: i64,
 
-    x1: f64,
-    y1: f64
+    x1: i64,
+    y1: i64
 }
 impl Bounds {
-    fn square(min: f64, max: f64) -> Bounds {
+    fn square(min: i64, max: i64) -> Bounds {
         return Bounds { x0: min, y0: min, x1: max, y1: max };
     }
 
     fn contains(&self, x: f64, y: f64) -> bool {
-        return self.x0 <= x && x <= self.x1 && self.y0 <= y && y <= self.y1;
+        return self.x0 as f64 <= x && x <= self.x1 as f64 && self.y0 as f64 <= y && y <= self.y1 as f64;
+    }
+
+    fn contains_exact(&self, x: &Exact, y: &Exact) -> bool {
+        let bound = |b: i64| Exact::from_integer(b as i128);
+        return bound(self.x0) <= *x && *x <= bound(self.x1) && bound(self.y0) <= *y && *y <= bound(self.y1);
     }
 }
 
@@ -227,7 +201,7 @@ fn crossing_coord(a: &Entry, b: &Entry) -> (f64, f64) {
     return (x, y);
 }
 
-fn compute_crossing_state(a: &Entry, b: &Entry, bounds: &Bounds) -> CrossingState {
+fn compute_crossing_state(a: &Entry, b: &Entry, bounds: &Bounds) -> CrossingState<f64> {
     if (a.v_y * b.v_x).approx_eq(b.v_y * a.v_x) {
         return CrossingState::Parallel;
     }
@@ -250,55 +224,195 @@ fn compute_crossing_state(a: &Entry, b: &Entry, bounds: &Bounds) -> CrossingStat
     }
 }
 
-fn part2(a: Entry, b: Entry, c: Entry, d: Entry) -> i64 {
-    println!("\n\nReference: {}\n", a);
-    let b = &(b - a);
-    let c = &(c - a);
-    let d = &(d - a);
-
-    // p1 is the origin
-    let p2 = b.p();
-    //let p3 = c.p();
-    //let p4 = d.p();
-
-    // v1 is stopped
-    let v2 = b.v();
-    //let v3 = c.v();
-    //let v4 = d.v();
-
-    println!("p2: {}, v2: {}", p2, v2);
-
-    // Normal of plane including the origin and the path of b (stone 2)
-    let mut normal = p2.cross(&v2);
-    normal.normalize();
-    let normal = &normal;
-
-    println!("Normal: {}\n", normal);
-
-    let d3 = c.intersection_distance(normal);
-    let d4 = d.intersection_distance(normal);
-
-    let intersect_c: Vec3 = c.along(d3);
-    let intersect_d: Vec3 = d.along(d4);
-
-    let intersect_time_c: f64 = c.time_to_reach(d3);
-    let intersect_time_d: f64 = d.time_to_reach(d4);
-
-    // debugging info
-    println!("Hit hailstone {} at t={}, pos={}", *c+a, intersect_time_c, (*c+a).pos_at(intersect_time_c));
-    println!("Hit hailstone {} at t={}, pos={}", *d+a, intersect_time_d, (*d+a).pos_at(intersect_time_d));
-
-    // delta(dist) / delta(time)
-    let ivel: Vec3 = (intersect_d - intersect_c) / (intersect_time_d - intersect_time_c);
-    println!("\nInitial velocity: {}", ivel + a.v());
-    // to find initial position do following:
-    // (intersect_c - ipos) / (intersect_time_c) = ivel
-    // (intersect_c - ipos) = ivel * intersect_time_c
-    // ipos = intersect_c - (ivel * intersect_time_c)
-    let ipos: Vec3 = intersect_c - (ivel * intersect_time_c);
-    // transform back to global frame of reference
-    let ipos = ipos + a.p();
-    println!("Initial position: {}", ipos);
-    let sum = (ipos.get_x().round() as i64) + (ipos.get_y().round() as i64) + (ipos.get_z().round() as i64);
-    return sum;
+/// Same as [compute_crossing_state], but on the integers as parsed, so borderline crossings
+/// far from the origin are decided correctly
+fn compute_crossing_state_exact(a: &Entry, b: &Entry, bounds: &Bounds) -> CrossingState<Exact> {
+    let [ax, ay, _] = a.position.map(i128::from);
+    let [avx, avy, _] = a.velocity.map(i128::from);
+    let [bx, by, _] = b.position.map(i128::from);
+    let [bvx, bvy, _] = b.velocity.map(i128::from);
+
+    // solve a.p + t * a.v = b.p + s * b.v in x and y
+    let det = avx * bvy - avy * bvx;
+    if det == 0 {
+        return CrossingState::Parallel;
+    }
+    let (dx, dy) = (bx - ax, by - ay);
+    let t = Exact::new(dx * bvy - dy * bvx, det);
+    let s = Exact::new(dx * avy - dy * avx, det);
+
+    let zero = Exact::from_integer(0);
+    let (past_a, past_b) = (t <= zero, s <= zero);
+    if past_a {
+        if past_b {
+            return CrossingState::PastBoth;
+        } else {
+            return CrossingState::PastA;
+        }
+    } else if past_b {
+        return CrossingState::PastB;
+    }
+
+    let x = Exact::from_integer(ax) + t * avx;
+    let y = Exact::from_integer(ay) + t * avy;
+    if bounds.contains_exact(&x, &y) {
+        return CrossingState::FutureInside(x, y);
+    } else {
+        return CrossingState::FutureOutside(x, y);
+    }
+}
+
+/// The thrown rock: it starts at `position` and moves `velocity` every nanosecond
+#[derive(Debug, PartialEq)]
+struct Rock {
+    position: [i128; 3],
+    velocity: [i128; 3]
+}
+impl Rock {
+    /// When the rock hits `entry`, if it does so at a whole, non-negative time
+    fn collision_time(&self, entry: &Entry) -> Option<i128> {
+        let mut time: Option<i128> = None;
+        for i in 0..3 {
+            let gap = i128::from(entry.position[i]) - self.position[i];
+            let closing = self.velocity[i] - i128::from(entry.velocity[i]);
+            if closing == 0 {
+                if gap != 0 {
+                    return None;
+                }
+                continue;
+            }
+            if gap % closing != 0 || time.is_some_and(|t| t != gap / closing) {
+                return None;
+            }
+            time = Some(gap / closing);
+        }
+        // same position and velocity on every axis: they touch straight away
+        return time.or(Some(0)).filter(|t| *t >= 0);
+    }
+}
+
+/// One row of the linear system in the unknowns `[px, py, pz, vx, vy, vz]`, then the right hand side
+type Row = [BigRational; 7];
+
+/// The rock (P, V) hits hailstone (p, v) when (P - p) × (V - v) = 0. The P × V term is the same for
+/// every hailstone, so subtracting the equations of two hailstones `a` and `b` leaves three that are
+/// linear: P × (v_b - v_a) + (p_b - p_a) × V = p_b × v_b - p_a × v_a
+fn rock_equations(a: &Entry, b: &Entry) -> [Row; 3] {
+    let cross = |l: [i128; 3], r: [i128; 3]| [l[1]*r[2] - l[2]*r[1], l[2]*r[0] - l[0]*r[2], l[0]*r[1] - l[1]*r[0]];
+    let (pa, va) = (a.position.map(i128::from), a.velocity.map(i128::from));
+    let (pb, vb) = (b.position.map(i128::from), b.velocity.map(i128::from));
+    let w = [0, 1, 2].map(|i| vb[i] - va[i]);
+    let q = [0, 1, 2].map(|i| pb[i] - pa[i]);
+    let (cross_a, cross_b) = (cross(pa, va), cross(pb, vb));
+    let rhs = [0, 1, 2].map(|i| cross_b[i] - cross_a[i]);
+
+    let rows: [[i128; 7]; 3] = [
+        [0, w[2], -w[1], 0, -q[2], q[1], rhs[0]],
+        [-w[2], 0, w[0], q[2], 0, -q[0], rhs[1]],
+        [w[1], -w[0], 0, -q[1], q[0], 0, rhs[2]]
+    ];
+    return rows.map(|row| row.map(|c| BigRational::from_integer(BigInt::from(c))));
+}
+
+/// Find the rock that hits every hailstone, from the equations between consecutive hailstones.
+/// Elimination is done on exact fractions, and the result is checked against every hailstone.
+fn find_rock(entries: &[Entry]) -> Result<Rock, String> {
+    let mut rows: Vec<Row> = entries.windows(2).flat_map(|pair| rock_equations(&pair[0], &pair[1])).collect();
+
+    // Gauss-Jordan elimination over all the rows; any rows left over must reduce to 0 = 0
+    for col in 0..6 {
+        let pivot = (col..rows.len()).find(|r| !rows[*r][col].is_zero())
+            .ok_or_else(|| format!("The hailstones don't pin down the rock ({} of 6 unknowns determined)", col))?;
+        rows.swap(col, pivot);
+        let scale = rows[col][col].clone();
+        for c in col..7 {
+            rows[col][c] = &rows[col][c] / &scale;
+        }
+        for r in 0..rows.len() {
+            if r == col || rows[r][col].is_zero() {
+                continue;
+            }
+            let factor = rows[r][col].clone();
+            for c in col..7 {
+                let delta = &factor * &rows[col][c];
+                rows[r][c] -= delta;
+            }
+        }
+    }
+    if rows[6..].iter().any(|row| !row[6].is_zero()) {
+        return Err("No single straight throw fits all the hailstones".to_owned());
+    }
+
+    let mut solution = [0i128; 6];
+    for (i, value) in solution.iter_mut().enumerate() {
+        let exact = &rows[i][6];
+        if !exact.is_integer() {
+            return Err(format!("The rock would need a fractional {}: {}", ["x", "y", "z", "vx", "vy", "vz"][i], exact));
+        }
+        *value = exact.to_integer().to_i128().ok_or_else(|| format!("The rock's {} doesn't fit in 128 bits", i))?;
+    }
+    let rock = Rock { position: [solution[0], solution[1], solution[2]], velocity: [solution[3], solution[4], solution[5]] };
+
+    for entry in entries {
+        match rock.collision_time(entry) {
+            Some(t) => debug!("Hit hailstone {} at t={}", entry, t),
+            None => return Err(format!("The rock at {:?} @ {:?} misses hailstone {}", rock.position, rock.velocity, entry))
+        }
+    }
+    return Ok(rock);
+}
+
+#[cfg(test)]
+fn example() -> Vec<Entry> {
+    return Span::new(&fs::read_to_string("src/bin/day24/example.txt").unwrap()).parse_lines(Entry::parse).unwrap();
+}
+
+#[test]
+fn exact_crossings() {
+    let entries = example();
+    let bounds = Bounds::square(7, 27);
+    let mut inside = 0;
+    for i in 0..entries.len() {
+        for j in i+1..entries.len() {
+            let exact = compute_crossing_state_exact(&entries[i], &entries[j], &bounds);
+            assert!(compute_crossing_state(&entries[i], &entries[j], &bounds).agrees_with(&exact));
+            if let CrossingState::FutureInside(_, _) = exact {
+                inside += 1;
+            }
+        }
+    }
+    assert_eq!(2, inside);
+
+    // the first pair crosses at (14 + 1/3, 15 + 1/3)
+    match compute_crossing_state_exact(&entries[0], &entries[1], &bounds) {
+        CrossingState::FutureInside(x, y) => assert_eq!((Exact::new(43, 3), Exact::new(46, 3)), (x, y)),
+        other => panic!("Unexpected crossing: {}", other)
+    }
+}
+
+#[test]
+fn exact_borderline() {
+    // crosses exactly on the far corner of the area, one step further out is outside
+    let a = Entry::parse(Span::new("400000000000000, 0, 0 @ 0, 1, 0")).unwrap();
+    let b = Entry::parse(Span::new("0, 400000000000000, 0 @ 1, 0, 0")).unwrap();
+    let bounds = Bounds::square(200000000000000, 400000000000000);
+    assert!(matches!(compute_crossing_state_exact(&a, &b, &bounds), CrossingState::FutureInside(_, _)));
+    let c = Entry::parse(Span::new("400000000000001, 0, 0 @ 0, 1, 0")).unwrap();
+    assert!(matches!(compute_crossing_state_exact(&c, &b, &bounds), CrossingState::FutureOutside(_, _)));
+}
+
+#[test]
+fn rock_throw() {
+    let entries = example();
+    let rock = find_rock(&entries).unwrap();
+    assert_eq!(Rock { position: [24, 13, 10], velocity: [-3, 1, 2] }, rock);
+    let times: Vec<Option<i128>> = entries.iter().map(|e| rock.collision_time(e)).collect();
+    assert_eq!(vec![Some(5), Some(3), Some(4), Some(6), Some(1)], times);
+
+    // knock the last hailstone off course, so no throw hits them all
+    let mut moved = entries.clone();
+    moved[4] = Entry::parse(Span::new("20, 19, 16 @  1, -5, -3")).unwrap();
+    assert!(find_rock(&moved).is_err());
+    // two hailstones aren't enough to pin the rock down
+    assert!(find_rock(&entries[..2]).is_err());
 }
@@ -1,124 +1,70 @@
-use std::{fs, collections::{HashMap, HashSet}, hash::{Hash, Hasher}, fmt::{Debug, Display}};
+use std::{env, fs, cmp::Reverse, collections::{BinaryHeap, HashMap, HashSet}};
 
-use rand::seq::SliceRandom;
-use utils::{DijkstraNode, DijkstraData};
+use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
+use utils::{debug, graph::{self, Style, UndirectedGraph}, info, log::Level, log_enabled, parse::{ParseResult, ParseResultExt, Span}};
 
 fn main() {
     println!("AOC 2023 Day 25");
+    utils::log::init();
 
-    let real = true;
-    let contents: String;
-    if real {
-        contents = fs::read_to_string("src/bin/day25/input.txt").expect("Failed to read input");
-    } else {
-        contents = fs::read_to_string("src/bin/day25/example.txt").expect("Failed to read example");
-    }
+    let real = !env::args().any(|a| a == "--example");
+    let fname = if real { "src/bin/day25/input.txt" } else { "src/bin/day25/example.txt" };
+    let contents = fs::read_to_string(fname).expect("Failed to read input");
 
-    let mut graph = Graph::new();
-    for line in contents.trim().split("\n") {
-        let (from, to) = line.split_once(": ").unwrap();
-        let to = to.split(" ");
-        for t in to {
-            graph.join(to_label(from), to_label(t));
-        }
-    }
+    let graph = Graph::load(&contents).expect_in(fname);
 
-    let labels: Vec<Label> = graph.nodes.keys().map(|l| *l).collect();
-    let rng = &mut rand::thread_rng();
-    // Find cut-nodes
-    let mut edge_count: HashMap<(Label, Label), usize> = HashMap::new();
-    for _ in 0..1000 {
-        let a = labels.choose(rng).unwrap();
-        let b = labels.choose(rng).unwrap();
-        if a == b {
-            continue;
-        }
+    // `--karger[=<seed>]` switches to randomised contraction, `--trials=<n>` sets how often it tries
+    let karger_seed: Option<u64> = env::args()
+        .find_map(|a| if a == "--karger" { Some(0) } else { a.strip_prefix("--karger=").and_then(|s| s.parse().ok()) });
+    let trials: usize = env::args().find_map(|a| a.strip_prefix("--trials=").and_then(|s| s.parse().ok())).unwrap_or(1000);
 
-        if !real {
-            println!("a: {}, b: {}", to_string(a), to_string(b));
-        }
+    let cut = match karger_seed {
+        Some(seed) => graph.karger_min_cut(seed, trials),
+        None => graph.min_cut()
+    }.expect("Graph needs at least two components");
 
-        let a = DijkstraCompatNode { graph: &graph, lbl: *a };
-        let b = DijkstraCompatNode { graph: &graph, lbl: *b };
-
-        let hlt = |node: &DijkstraCompatNode<'_>| -> bool {
-            return b.lbl == node.lbl;
-        };
-
-        let d = DijkstraData::dijkstra(a, (), hlt);
-        //println!("{}", d.best_distance.get(&b).unwrap());
-        /*for (k, v) in &d.prev_in_chain {
-            println!("{} <- {}", v, k);
-        }*/
-        let mut curr = &b;
-        loop {
-            let prev = match d.prev_in_chain.get(curr) {
-                None => break,
-                Some(v) => v
-            };
-            let first = curr.lbl.min(prev.lbl);
-            let second = curr.lbl.max(prev.lbl);
-
-            edge_count.insert((first, second), 1 + match edge_count.get(&(first, second)) {
-                None => 0,
-                Some(v) => *v
-            });
-
-            curr = prev;
-        }
-        //println!("{}", to_string(&d.prev_in_chain.get(&b).unwrap().lbl));
-        //println!("\n\n\n\n");
-    }
-
-    let mut to_cut: Vec<(&(Label, Label), &usize)> = edge_count.iter().collect();
-    to_cut.sort_unstable_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
-
-    println!("\n\n\n");
-
-    let a: usize;
-    let b: usize;
-    /*
-    // just use GraphViz in 'neato' mode to determine the cuts
-    if real {
-        graph.cut(to_label("klk"), to_label("xgz"));
-        graph.cut(to_label("vmq"), to_label("cbl"));
-        graph.cut(to_label("bvz"), to_label("nvf"));
-
-        a = graph.count_from(to_label("klk"));
-        b = graph.count_from(to_label("xgz"));
-    } else {
-        graph.cut(to_label("hfx"), to_label("pzl"));
-        graph.cut(to_label("bvb"), to_label("cmg"));
-        graph.cut(to_label("nvd"), to_label("jqt"));
-
-        a = graph.count_from(to_label("hfx"));
-        b = graph.count_from(to_label("pzl"));
-    }*/
-    for i in 0..3 {
-        let ((from, to), _) = to_cut[i];
-        println!("Should cut: {}, {}", to_string(from), to_string(to));
-        graph.cut(*from, *to);
+    for (from, to) in &cut.edges {
+        info!("Should cut: {}, {}", to_string(from), to_string(to));
     }
-    a = graph.count_from(to_cut[0].0.0);
-    b = graph.count_from(to_cut[0].0.1);
-
-    println!("A: {}", a);
-    println!("B: {}", b);
+    let (a, b) = cut.sizes;
+    info!("A: {}", a);
+    info!("B: {}", b);
     println!("Part 1: {}", a*b);
 
-    if !real {
-        for (lbl, node) in graph.nodes.iter() {
-            println!("Node {:?} [{}] -> {:?}", to_string(lbl), node.visited, node.connections.iter()
-                .map(|l| to_string(l)).collect::<Vec<_>>().join(", "));
-        }
+    // `--dot[=<file>]` and `--svg[=<file>]` draw the graph with the cut highlighted, next to the
+    // input by default
+    let indexed = graph.indexed();
+    let style = cut.style(&indexed);
+    if let Some(path) = output_path("--dot", fname, "gv") {
+        fs::write(&path, graph::to_dot(&indexed, "aoc25", &style)).expect("Failed to write DOT file");
+        info!("Wrote {}", path);
+    }
+    if let Some(path) = output_path("--svg", fname, "svg") {
+        let positions = graph::force_layout(&indexed, 300);
+        fs::write(&path, graph::to_svg(&indexed, &positions, &style, 800.0)).expect("Failed to write SVG file");
+        info!("Wrote {}", path);
+    }
 
-        println!("debug graph:\n\n");
-        for (_, node) in graph.nodes.iter() {
-            node.print_debug();
+    if log_enabled!(Level::Debug) {
+        debug!("debug graph:");
+        let mut labels: Vec<&Label> = graph.nodes.keys().collect();
+        labels.sort();
+        for lbl in labels {
+            graph.get(*lbl).print_debug();
         }
     }
 }
 
+/// `flag=<path>`, or `flag` alone for `<input>.<extension>`
+fn output_path(flag: &str, input: &str, extension: &str) -> Option<String> {
+    return env::args().find_map(|a| {
+        if a == flag {
+            return Some(format!("{}.{}", input, extension));
+        }
+        return a.strip_prefix(flag)?.strip_prefix("=").map(|p| p.to_owned());
+    });
+}
+
 type Label = [char; 3];
 
 fn to_label(data: &str) -> Label {
@@ -130,59 +76,93 @@ fn to_label(data: &str) -> Label {
     return chars;
 }
 
+fn parse_label(span: Span) -> ParseResult<Label> {
+    let span = span.trim();
+    if span.as_str().chars().count() != 3 {
+        return Err(span.error("a three character component name"));
+    }
+    return Ok(to_label(span.as_str()));
+}
+
 fn to_string(lbl: &Label) -> String {
     return String::from_iter(lbl.iter());
 }
 
-#[derive(Clone, Copy)]
-struct DijkstraCompatNode<'a> {
-    graph: &'a Graph,
-    lbl: Label
+/// Edges to remove to split the graph in two, and how many components end up on either side
+#[derive(Debug, PartialEq)]
+struct MinCut {
+    /// each sorted, in sorted order
+    edges: Vec<(Label, Label)>,
+    sizes: (usize, usize),
+    /// which side each node (by [IndexedGraph] index) ends up on
+    side: Vec<bool>
 }
-impl <'a>DijkstraCompatNode<'a> {
-    fn with_label(&self, lbl: Label) -> DijkstraCompatNode<'a> {
-        return DijkstraCompatNode { graph: self.graph, lbl };
+impl MinCut {
+    /// Cut edges in red, the two sides in blue and green
+    fn style(&self, graph: &IndexedGraph) -> Style {
+        let mut style = Style::default();
+        for (node, side) in self.side.iter().enumerate() {
+            style.node_colours.insert(node, if *side { "lightblue" } else { "palegreen" }.to_owned());
+        }
+        for (a, b) in &self.edges {
+            let (a, b) = (graph.index_of(a), graph.index_of(b));
+            style.edge_colours.insert((a.min(b), a.max(b)), "red".to_owned());
+        }
+        return style;
     }
 }
-impl PartialEq for DijkstraCompatNode<'_> {
-    fn eq(&self, other: &Self) -> bool {
-        return self.lbl == other.lbl;
-    }
+
+/// [Graph] with nodes numbered in label order
+struct IndexedGraph {
+    labels: Vec<Label>,
+    /// neighbours of every node, sorted
+    adjacency: Vec<Vec<usize>>
 }
-impl Eq for DijkstraCompatNode<'_> {}
-impl Hash for DijkstraCompatNode<'_> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.lbl.hash(state);
+impl IndexedGraph {
+    fn index_of(&self, lbl: &Label) -> usize {
+        return self.labels.binary_search(lbl).unwrap();
     }
-}
-impl Display for DijkstraCompatNode<'_> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&to_string(&self.lbl))
+
+    /// Describe the cut that puts the nodes marked in `side` on one side
+    fn describe_cut(&self, side: Vec<bool>) -> MinCut {
+        let mut edges: Vec<(Label, Label)> = self.edges().into_iter()
+            .filter(|(a, b)| side[*a] != side[*b])
+            .map(|(a, b)| (self.labels[a], self.labels[b]))
+            .collect();
+        edges.sort();
+        let inside = side.iter().filter(|s| **s).count();
+        return MinCut { edges, sizes: (inside, side.len() - inside), side };
     }
 }
-impl <'a>DijkstraNode<()> for DijkstraCompatNode<'a> {
-    fn get_connected(&self, _: &()) -> Vec<(Self, usize)> where Self: Sized {
-        return self.graph.get(self.lbl).connections.iter().map(|c| (self.with_label(*c), 1_usize)).collect();
+impl UndirectedGraph for IndexedGraph {
+    fn node_count(&self) -> usize {
+        return self.labels.len();
+    }
+
+    fn node_name(&self, node: usize) -> String {
+        return to_string(&self.labels[node]);
+    }
+
+    fn edges(&self) -> Vec<(usize, usize)> {
+        return self.adjacency.iter().enumerate()
+            .flat_map(|(a, neighbours)| neighbours.iter().filter(move |b| a < **b).map(move |b| (a, *b)))
+            .collect();
     }
 }
 
 struct Node {
-    #[allow(dead_code)]
     lbl: Label,
-    connections: HashSet<Label>,
-    visited: bool
+    connections: HashSet<Label>
 }
 impl Node {
     fn new(lbl: Label) -> Node {
-        return Node { lbl, connections: HashSet::new(), visited: false };
+        return Node { lbl, connections: HashSet::new() };
     }
 
     fn print_debug(&self) {
-        print!("{}:", to_string(&self.lbl));
-        for con in &self.connections {
-            print!(" {}", to_string(con));
-        }
-        println!();
+        let mut connections: Vec<String> = self.connections.iter().map(|c| to_string(c)).collect();
+        connections.sort();
+        debug!("{}: {}", to_string(&self.lbl), connections.join(" "));
     }
 }
 
@@ -194,6 +174,18 @@ impl Graph {
         return Graph { nodes: HashMap::new() };
     }
 
+    fn load(data: &str) -> ParseResult<Graph> {
+        let mut graph = Graph::new();
+        for line in Span::new(data).trim().lines() {
+            let (from, to) = line.split_once(": ")?;
+            let from = parse_label(from)?;
+            for t in to.split_whitespace() {
+                graph.join(from, parse_label(t)?);
+            }
+        }
+        return Ok(graph);
+    }
+
     fn join(&mut self, a: Label, b: Label) {
         if !self.nodes.contains_key(&a) {
             self.nodes.insert(a, Node::new(a));
@@ -205,43 +197,181 @@ impl Graph {
         self.nodes.get_mut(&b).unwrap().connections.insert(a);
     }
 
-    fn cut(&mut self, a: Label, b: Label) {
-        self.nodes.get_mut(&a).unwrap().connections.remove(&b);
-        self.nodes.get_mut(&b).unwrap().connections.remove(&a);
-    }
-
     fn get(&self, lbl: Label) -> &Node {
         return self.nodes.get(&lbl).unwrap();
     }
 
-    fn get_mut(&mut self, lbl: Label) -> &mut Node {
-        return self.nodes.get_mut(&lbl).unwrap();
+    fn indexed(&self) -> IndexedGraph {
+        let mut labels: Vec<Label> = self.nodes.keys().copied().collect();
+        labels.sort();
+        let index_of: HashMap<Label, usize> = labels.iter().enumerate().map(|(i, l)| (*l, i)).collect();
+        let adjacency: Vec<Vec<usize>> = labels.iter()
+            .map(|l| {
+                let mut neighbours: Vec<usize> = self.get(*l).connections.iter().map(|c| index_of[c]).collect();
+                neighbours.sort();
+                neighbours
+            })
+            .collect();
+        return IndexedGraph { labels, adjacency };
     }
 
-    fn count_from(&mut self, lbl: Label) -> usize {
-        let mut sum = 1;
-        let mut frontier: Vec<Label> = vec![];
+    /// Deterministic global minimum cut (Stoer–Wagner), `None` with fewer than two nodes
+    fn min_cut(&self) -> Option<MinCut> {
+        let indexed = self.indexed();
+        let n = indexed.node_count();
+        if n < 2 {
+            return None;
+        }
+
+        // super node -> neighbouring super node -> number of edges between them
+        let mut weights: Vec<HashMap<usize, usize>> = indexed.adjacency.iter()
+            .map(|neighbours| neighbours.iter().map(|b| (*b, 1)).collect())
+            .collect();
+        let mut members: Vec<Vec<usize>> = (0..n).map(|i| vec![i]).collect();
+        let mut active: Vec<bool> = vec![true; n];
+        let mut best: Option<(usize, Vec<usize>)> = None;
+
+        for _ in 1..n {
+            // maximum adjacency ordering, ties going to the lowest index
+            let start = active.iter().position(|a| *a).unwrap();
+            let mut key: Vec<usize> = vec![0; n];
+            let mut added: Vec<bool> = vec![false; n];
+            let mut heap: BinaryHeap<(usize, Reverse<usize>)> = BinaryHeap::new();
+            heap.push((0, Reverse(start)));
+            let (mut previous, mut last, mut cut_weight) = (start, start, 0);
+            while let Some((k, Reverse(v))) = heap.pop() {
+                if added[v] || k != key[v] {
+                    continue;
+                }
+                added[v] = true;
+                (previous, last, cut_weight) = (last, v, k);
+                for (u, w) in &weights[v] {
+                    if !added[*u] {
+                        key[*u] += w;
+                        heap.push((key[*u], Reverse(*u)));
+                    }
+                }
+            }
+
+            let reached = (0..n).filter(|v| added[*v]).count();
+            if reached < active.iter().filter(|a| **a).count() {
+                // disconnected, so whatever was reached can be split off for free
+                let side: Vec<usize> = (0..n).filter(|v| added[*v]).flat_map(|v| members[v].clone()).collect();
+                best = Some((0, side));
+                break;
+            }
+            if best.as_ref().is_none_or(|(w, _)| cut_weight < *w) {
+                best = Some((cut_weight, members[last].clone()));
+            }
+
+            // merge the last node added into the one before it
+            let moved = std::mem::take(&mut members[last]);
+            members[previous].extend(moved);
+            let last_weights = std::mem::take(&mut weights[last]);
+            for (u, w) in last_weights {
+                weights[u].remove(&last);
+                if u != previous {
+                    *weights[previous].entry(u).or_default() += w;
+                    *weights[u].entry(previous).or_default() += w;
+                }
+            }
+            active[last] = false;
+        }
+
+        let (_, side_members) = best?;
+        let mut side = vec![false; n];
+        for v in side_members {
+            side[v] = true;
+        }
+        return Some(indexed.describe_cut(side));
+    }
 
-        self.get_mut(lbl).visited = true;
-        for connection in &self.get(lbl).connections {
-            frontier.push(*connection);
+    /// Smallest cut found by `trials` runs of Karger's random contraction. The same seed always
+    /// gives the same result. `None` with fewer than two nodes.
+    fn karger_min_cut(&self, seed: u64, trials: usize) -> Option<MinCut> {
+        let indexed = self.indexed();
+        let n = indexed.node_count();
+        if n < 2 {
+            return None;
         }
+        let mut edges: Vec<(usize, usize)> = indexed.edges();
 
-        while frontier.len() > 0 {
-            let label = frontier.pop().unwrap();
-            if self.get(label).visited {
-                continue;
+        fn find(parent: &mut [usize], v: usize) -> usize {
+            let mut root = v;
+            while parent[root] != root {
+                root = parent[root];
+            }
+            let mut v = v;
+            while parent[v] != root {
+                let next = parent[v];
+                parent[v] = root;
+                v = next;
             }
-            println!("Handling: {}", to_string(&label));
-            sum += 1;
+            return root;
+        }
 
-            self.get_mut(label).visited = true;
-            for connection in &self.get(label).connections {
-                if !self.get(*connection).visited {
-                    frontier.push(*connection);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut best: Option<MinCut> = None;
+        for trial in 0..trials.max(1) {
+            // contracting edges in a random order until two super nodes are left
+            edges.shuffle(&mut rng);
+            let mut parent: Vec<usize> = (0..n).collect();
+            let mut components = n;
+            for (a, b) in &edges {
+                if components == 2 {
+                    break;
+                }
+                let (ra, rb) = (find(&mut parent, *a), find(&mut parent, *b));
+                if ra != rb {
+                    parent[ra] = rb;
+                    components -= 1;
                 }
             }
+            let root = find(&mut parent, 0);
+            let side: Vec<bool> = (0..n).map(|v| find(&mut parent, v) == root).collect();
+            let cut = indexed.describe_cut(side);
+            if best.as_ref().is_none_or(|b| cut.edges.len() < b.edges.len()) {
+                debug!("Trial {} found a cut of {} edges", trial, cut.edges.len());
+                best = Some(cut);
+            }
         }
-        return sum;
+        return best;
     }
 }
+
+#[cfg(test)]
+fn example() -> Graph {
+    return Graph::load(&fs::read_to_string("src/bin/day25/example.txt").unwrap()).unwrap();
+}
+
+#[test]
+fn stoer_wagner() {
+    let cut = example().min_cut().unwrap();
+    let expected: Vec<(Label, Label)> = vec![
+        (to_label("bvb"), to_label("cmg")),
+        (to_label("hfx"), to_label("pzl")),
+        (to_label("jqt"), to_label("nvd"))
+    ];
+    assert_eq!(expected, cut.edges);
+    assert_eq!(54, cut.sizes.0 * cut.sizes.1);
+    assert_eq!(15, cut.sizes.0 + cut.sizes.1);
+}
+
+#[test]
+fn seeded_karger() {
+    let graph = example();
+    let first = graph.karger_min_cut(7, 200).unwrap();
+    assert_eq!(graph.min_cut().unwrap().edges, first.edges);
+    assert_eq!(first, graph.karger_min_cut(7, 200).unwrap());
+}
+
+#[test]
+fn cut_drawing() {
+    let graph = example();
+    let indexed = graph.indexed();
+    let style = graph.min_cut().unwrap().style(&indexed);
+    let dot = graph::to_dot(&indexed, "aoc25", &style);
+    assert_eq!(3, dot.matches("[color=\"red\", penwidth=3]").count());
+    assert!(dot.contains("    \"hfx\" -- \"pzl\" [color=\"red\", penwidth=3];\n"));
+    assert_eq!(15, dot.matches("fillcolor=\"lightblue\"").count() + dot.matches("fillcolor=\"palegreen\"").count());
+}
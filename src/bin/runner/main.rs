@@ -0,0 +1,100 @@
+use std::{env, path::{Path, PathBuf}, process::Command, time::Instant};
+
+use utils::{info, warn};
+
+/// Solves several days at once by launching the already-built `dayNN` binaries side by side.
+///
+/// Usage: `runner [-j <jobs>] [day or first-last ...]`, plus the usual `-q`/`-v`/`--log=` flags,
+/// which are passed on to every day. Without any days, every day binary that was built is run.
+/// Output is printed in day order once everything has finished, so it doesn't depend on timing.
+fn main() {
+    println!("AOC 2023 Runner");
+    utils::log::init();
+
+    let bin_dir: PathBuf = env::current_exe().expect("Failed to locate runner")
+        .parent().expect("Runner has no parent directory").to_owned();
+
+    let mut jobs = utils::parallel::threads();
+    let mut days: Vec<u8> = vec![];
+    let mut forwarded: Vec<String> = vec![];
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-j" || arg == "--jobs" {
+            jobs = args.next().and_then(|j| j.parse().ok()).expect("Expected a job count after -j");
+        } else if let Some(j) = arg.strip_prefix("--jobs=") {
+            jobs = j.parse().expect("Expected a job count after --jobs=");
+        } else if arg.starts_with('-') {
+            forwarded.push(arg);
+        } else {
+            days.extend(parse_days(&arg));
+        }
+    }
+    if days.is_empty() {
+        days = (1..=25).filter(|d| day_binary(&bin_dir, *d).exists()).collect();
+    }
+    let jobs = jobs.clamp(1, days.len().max(1));
+
+    // split the machine between the days, unless told otherwise
+    let threads_per_day = env::var(utils::parallel::ENV_VAR).ok()
+        .unwrap_or_else(|| (utils::parallel::threads() / jobs).max(1).to_string());
+
+    info!("Running {} days with {} jobs and {} threads each", days.len(), jobs, threads_per_day);
+
+    let wall = Instant::now();
+    let outcomes = utils::parallel::map_with(jobs, &days, |day| {
+        let start = Instant::now();
+        let output = Command::new(day_binary(&bin_dir, *day))
+            .args(&forwarded)
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .env(utils::parallel::ENV_VAR, &threads_per_day)
+            .output();
+        return (*day, output, start.elapsed());
+    });
+
+    let mut failed: Vec<u8> = vec![];
+    for (day, output, elapsed) in outcomes {
+        println!("\n===== Day {:02} =====", day);
+        match output {
+            Ok(output) => {
+                print!("{}", String::from_utf8_lossy(&output.stdout));
+                eprint!("{}", String::from_utf8_lossy(&output.stderr));
+                if !output.status.success() {
+                    warn!("Day {:02} exited with {}", day, output.status);
+                    failed.push(day);
+                }
+            },
+            Err(e) => {
+                warn!("Day {:02} could not be started: {}", day, e);
+                failed.push(day);
+            }
+        }
+        info!("Day {:02} took {:.2?}", day, elapsed);
+    }
+
+    info!("All days took {:.2?}", wall.elapsed());
+    if !failed.is_empty() {
+        println!("\nFailed days: {:?}", failed);
+        std::process::exit(1);
+    }
+}
+
+fn day_binary(bin_dir: &Path, day: u8) -> PathBuf {
+    return bin_dir.join(format!("day{:02}{}", day, env::consts::EXE_SUFFIX));
+}
+
+/// `7` or an inclusive range like `3-9`
+fn parse_days(arg: &str) -> Vec<u8> {
+    let parse = |s: &str| s.trim().parse::<u8>().ok().filter(|d| (1..=25).contains(d))
+        .unwrap_or_else(|| panic!("Expected a day from 1 to 25, found `{}`", s));
+    return match arg.split_once('-') {
+        Some((first, last)) => (parse(first)..=parse(last)).collect(),
+        None => vec![parse(arg)]
+    };
+}
+
+#[test]
+fn day_arguments() {
+    assert_eq!(vec![7], parse_days("7"));
+    assert_eq!(vec![3, 4, 5], parse_days("3-5"));
+}
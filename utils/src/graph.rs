@@ -0,0 +1,256 @@
+//! Drawing undirected graphs: Graphviz DOT export, and a force-directed layout rendered straight
+//! to SVG for when Graphviz isn't installed. Also strongly connected components of directed ones.
+
+use std::{collections::HashMap, f64::consts::PI};
+
+/// An undirected graph with nodes numbered `0..node_count()`
+pub trait UndirectedGraph {
+    fn node_count(&self) -> usize;
+
+    fn node_name(&self, node: usize) -> String;
+
+    /// Every edge once, as `(lower, higher)`
+    fn edges(&self) -> Vec<(usize, usize)>;
+}
+
+/// Colours for particular nodes and edges; anything missing is drawn in the default style
+#[derive(Default)]
+pub struct Style {
+    pub node_colours: HashMap<usize, String>,
+    /// keyed by `(lower, higher)`
+    pub edge_colours: HashMap<(usize, usize), String>
+}
+impl Style {
+    fn edge_colour(&self, a: usize, b: usize) -> Option<&String> {
+        return self.edge_colours.get(&(a.min(b), a.max(b)));
+    }
+}
+
+/// `strict graph` in DOT; coloured edges are drawn thicker so they stand out
+pub fn to_dot(graph: &impl UndirectedGraph, name: &str, style: &Style) -> String {
+    let mut out = format!("strict graph {} {{\n    node [style=filled, fillcolor=white];\n", name);
+    for node in 0..graph.node_count() {
+        match style.node_colours.get(&node) {
+            Some(colour) => out += &format!("    \"{}\" [fillcolor=\"{}\"];\n", graph.node_name(node), colour),
+            None => out += &format!("    \"{}\";\n", graph.node_name(node))
+        }
+    }
+    for (a, b) in graph.edges() {
+        out += &format!("    \"{}\" -- \"{}\"", graph.node_name(a), graph.node_name(b));
+        if let Some(colour) = style.edge_colour(a, b) {
+            out += &format!(" [color=\"{}\", penwidth=3]", colour);
+        }
+        out += ";\n";
+    }
+    out += "}\n";
+    return out;
+}
+
+/// Fruchterman–Reingold layout in a unit square. Starts from a sunflower spiral, so the result
+/// only depends on the graph.
+pub fn force_layout(graph: &impl UndirectedGraph, iterations: usize) -> Vec<(f64, f64)> {
+    let n = graph.node_count();
+    let edges = graph.edges();
+    let golden_angle = PI * (3.0 - 5f64.sqrt());
+    let mut positions: Vec<(f64, f64)> = (0..n)
+        .map(|i| {
+            let r = 0.5 * ((i as f64 + 0.5) / n as f64).sqrt();
+            let theta = i as f64 * golden_angle;
+            (0.5 + r * theta.cos(), 0.5 + r * theta.sin())
+        })
+        .collect();
+    if n < 2 {
+        return positions;
+    }
+
+    // ideal distance between nodes
+    let k = (1.0 / n as f64).sqrt();
+    let mut temperature = 0.1;
+    let cooling = temperature / (iterations.max(1) as f64 + 1.0);
+    for _ in 0..iterations {
+        let mut displacement: Vec<(f64, f64)> = vec![(0.0, 0.0); n];
+        for a in 0..n {
+            for b in (a + 1)..n {
+                let (dx, dy) = (positions[a].0 - positions[b].0, positions[a].1 - positions[b].1);
+                let distance = (dx * dx + dy * dy).sqrt().max(1e-6);
+                let push = k * k / distance;
+                let (fx, fy) = (dx / distance * push, dy / distance * push);
+                displacement[a] = (displacement[a].0 + fx, displacement[a].1 + fy);
+                displacement[b] = (displacement[b].0 - fx, displacement[b].1 - fy);
+            }
+        }
+        for (a, b) in &edges {
+            let (dx, dy) = (positions[*a].0 - positions[*b].0, positions[*a].1 - positions[*b].1);
+            let distance = (dx * dx + dy * dy).sqrt().max(1e-6);
+            let pull = distance * distance / k;
+            let (fx, fy) = (dx / distance * pull, dy / distance * pull);
+            displacement[*a] = (displacement[*a].0 - fx, displacement[*a].1 - fy);
+            displacement[*b] = (displacement[*b].0 + fx, displacement[*b].1 + fy);
+        }
+        for (position, (dx, dy)) in positions.iter_mut().zip(displacement) {
+            let length = (dx * dx + dy * dy).sqrt().max(1e-9);
+            let step = length.min(temperature);
+            position.0 = (position.0 + dx / length * step).clamp(0.0, 1.0);
+            position.1 = (position.1 + dy / length * step).clamp(0.0, 1.0);
+        }
+        temperature -= cooling;
+    }
+    return positions;
+}
+
+/// Draw the graph at `positions` (in a unit square, see [force_layout]) as a `size` pixel SVG
+pub fn to_svg(graph: &impl UndirectedGraph, positions: &[(f64, f64)], style: &Style, size: f64) -> String {
+    let margin = 20.0;
+    let scale = |(x, y): (f64, f64)| (margin + x * (size - 2.0 * margin), margin + y * (size - 2.0 * margin));
+
+    let mut out = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {0} {0}\">\n", size);
+    out += &format!("<rect width=\"{0}\" height=\"{0}\" fill=\"white\"/>\n", size);
+    // highlighted edges last so they're drawn on top
+    let (mut plain, mut coloured): (Vec<(usize, usize)>, Vec<(usize, usize)>) = (vec![], vec![]);
+    for (a, b) in graph.edges() {
+        if style.edge_colour(a, b).is_some() { coloured.push((a, b)) } else { plain.push((a, b)) }
+    }
+    for (a, b) in plain.into_iter().chain(coloured) {
+        let ((x1, y1), (x2, y2)) = (scale(positions[a]), scale(positions[b]));
+        let (colour, width) = match style.edge_colour(a, b) {
+            Some(colour) => (colour.as_str(), 3),
+            None => ("#999999", 1)
+        };
+        out += &format!("<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"{}\"/>\n", x1, y1, x2, y2, colour, width);
+    }
+    for node in 0..graph.node_count() {
+        let (x, y) = scale(positions[node]);
+        let fill = style.node_colours.get(&node).map(|c| c.as_str()).unwrap_or("white");
+        out += &format!("<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"4\" fill=\"{}\" stroke=\"black\"><title>{}</title></circle>\n", x, y, fill, graph.node_name(node));
+        out += &format!("<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"monospace\" font-size=\"9\">{}</text>\n", x + 5.0, y - 5.0, graph.node_name(node));
+    }
+    out += "</svg>\n";
+    return out;
+}
+
+/// Tarjan's strongly connected components over an adjacency list. Components come out in reverse
+/// topological order: nothing in a component points into one listed after it.
+///
+/// Iterative, so long chains don't overflow the stack.
+pub fn tarjan(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let n = edges.len();
+    let mut index: Vec<Option<usize>> = vec![None; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack: Vec<usize> = vec![];
+    let mut next = 0;
+    let mut components: Vec<Vec<usize>> = vec![];
+
+    for root in 0..n {
+        if index[root].is_some() {
+            continue;
+        }
+        // (node, how many of its edges have been followed)
+        let mut calls: Vec<(usize, usize)> = vec![(root, 0)];
+        index[root] = Some(next);
+        low[root] = next;
+        next += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some((v, followed)) = calls.last_mut() {
+            let v = *v;
+            if let Some(&w) = edges[v].get(*followed) {
+                *followed += 1;
+                match index[w] {
+                    None => {
+                        index[w] = Some(next);
+                        low[w] = next;
+                        next += 1;
+                        stack.push(w);
+                        on_stack[w] = true;
+                        calls.push((w, 0));
+                    },
+                    Some(w_index) if on_stack[w] => low[v] = low[v].min(w_index),
+                    _ => {}
+                }
+                continue;
+            }
+
+            calls.pop();
+            if Some(low[v]) == index[v] {
+                let mut component = vec![];
+                loop {
+                    let w = stack.pop().unwrap();
+                    on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+            if let Some((parent, _)) = calls.last() {
+                low[*parent] = low[*parent].min(low[v]);
+            }
+        }
+    }
+    return components;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// two triangles joined by one edge
+    struct Bowtie;
+    impl UndirectedGraph for Bowtie {
+        fn node_count(&self) -> usize {
+            return 6;
+        }
+
+        fn node_name(&self, node: usize) -> String {
+            return format!("n{}", node);
+        }
+
+        fn edges(&self) -> Vec<(usize, usize)> {
+            return vec![(0, 1), (0, 2), (1, 2), (2, 3), (3, 4), (3, 5), (4, 5)];
+        }
+    }
+
+    #[test]
+    fn drawing() {
+        let mut style = Style::default();
+        style.edge_colours.insert((2, 3), "red".to_owned());
+        style.node_colours.insert(4, "lightblue".to_owned());
+
+        let dot = to_dot(&Bowtie, "bowtie", &style);
+        assert!(dot.starts_with("strict graph bowtie {\n"));
+        assert!(dot.contains("    \"n2\" -- \"n3\" [color=\"red\", penwidth=3];\n"));
+        assert!(dot.contains("    \"n4\" [fillcolor=\"lightblue\"];\n"));
+
+        let positions = force_layout(&Bowtie, 200);
+        assert_eq!(positions, force_layout(&Bowtie, 200));
+        assert!(positions.iter().all(|(x, y)| (0.0..=1.0).contains(x) && (0.0..=1.0).contains(y)));
+        let distance = |a: usize, b: usize| ((positions[a].0 - positions[b].0).powi(2) + (positions[a].1 - positions[b].1).powi(2)).sqrt();
+        // the triangles should end up further from each other than their own corners
+        assert!(distance(0, 5) > distance(0, 1));
+
+        let svg = to_svg(&Bowtie, &positions, &style, 200.0);
+        assert_eq!(7, svg.matches("<line").count());
+        assert_eq!(6, svg.matches("<circle").count());
+        assert_eq!(1, svg.matches("stroke=\"red\"").count());
+    }
+
+    #[test]
+    fn strongly_connected() {
+        // 0 -> 1 -> 2 -> 0 loop, feeding 3 <-> 4, and 5 on its own
+        let edges = vec![vec![1], vec![2], vec![0, 3], vec![4], vec![3], vec![0]];
+        let mut components = tarjan(&edges);
+        for component in components.iter_mut() {
+            component.sort();
+        }
+        assert_eq!(vec![vec![3, 4], vec![0, 1, 2], vec![5]], components);
+
+        // a long chain is fine too
+        let chain: Vec<Vec<usize>> = (0..1_000_000).map(|i| if i + 1 < 1_000_000 { vec![i + 1] } else { vec![] }).collect();
+        let components = tarjan(&chain);
+        assert_eq!(1_000_000, components.len());
+        assert_eq!(vec![999_999], components[0]);
+    }
+}
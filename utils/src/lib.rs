@@ -1,3 +1,10 @@
+pub mod graph;
+pub mod log;
+pub mod parallel;
+pub mod parse;
+pub mod polygon;
+pub mod viewer;
+
 use std::{collections::{HashSet, HashMap}, hash::Hash};
 
 pub fn colorize(input: &str, r: u8, g: u8, b: u8) -> String {
@@ -0,0 +1,287 @@
+//! Tiny levelled logging facade for the day solvers.
+//!
+//! Messages are written to stderr as `[LEVEL target] message`, where the target is the
+//! module path of the call site (so `day20`, `day22`, ...). What gets through is controlled
+//! by a filter spec in the style of `env_logger`, for example `info,day20=trace` or `day23=debug`.
+//! The spec is read from the `AOC_LOG` environment variable, and [init] additionally understands
+//! the `-q`, `-v`, `-vv`, `-vvv` and `--log=<spec>` command line flags.
+//!
+//! By default only warnings and errors are shown, so diagnostics can stay in the code without
+//! flooding the terminal. Tests can look at what was logged with [capture].
+
+use std::{cell::RefCell, env, fmt::{self, Display, Formatter}, sync::{RwLock, atomic::{AtomicU8, Ordering}}};
+
+/// Environment variable holding the filter spec
+pub const ENV_VAR: &str = "AOC_LOG";
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5
+}
+impl Level {
+    pub fn parse(name: &str) -> Option<Level> {
+        return match name.trim().to_ascii_lowercase().as_str() {
+            "error" => Some(Level::Error),
+            "warn" | "warning" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            "trace" => Some(Level::Trace),
+            _ => None
+        };
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE"
+        };
+    }
+}
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        return f.pad(self.name());
+    }
+}
+
+/// Parsed filter spec. A level of `None` means the target is switched off entirely.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Filter {
+    default: Option<Level>,
+    targets: Vec<(String, Option<Level>)>
+}
+impl Filter {
+    pub const fn new(default: Option<Level>) -> Filter {
+        return Filter { default, targets: Vec::new() };
+    }
+
+    /// Parse a comma separated spec of `level` and `target=level` entries (`off` disables).
+    /// Unknown entries are ignored.
+    pub fn parse(spec: &str) -> Filter {
+        let mut filter = Filter::new(Some(Level::Warn));
+        for entry in spec.split(',').map(|e| e.trim()).filter(|e| !e.is_empty()) {
+            match entry.split_once('=') {
+                Some((target, level)) => {
+                    if let Some(level) = parse_level(level) {
+                        filter.targets.push((target.trim().to_owned(), level));
+                    }
+                },
+                None => {
+                    if let Some(level) = parse_level(entry) {
+                        filter.default = level;
+                    } else {
+                        // a bare target name enables everything for it
+                        filter.targets.push((entry.to_owned(), Some(Level::Trace)));
+                    }
+                }
+            }
+        }
+        return filter;
+    }
+
+    /// Most verbose level allowed for `target`, using the longest matching target prefix
+    pub fn level_for(&self, target: &str) -> Option<Level> {
+        let mut best: Option<(usize, Option<Level>)> = None;
+        for (name, level) in &self.targets {
+            let matches = target == name || (target.starts_with(name.as_str()) && target[name.len()..].starts_with("::"));
+            if matches && best.is_none_or(|(len, _)| name.len() >= len) {
+                best = Some((name.len(), *level));
+            }
+        }
+        return match best {
+            Some((_, level)) => level,
+            None => self.default
+        };
+    }
+
+    fn max_level(&self) -> Option<Level> {
+        return self.targets.iter().map(|(_, l)| *l).fold(self.default, |acc, l| acc.max(l));
+    }
+}
+
+fn parse_level(name: &str) -> Option<Option<Level>> {
+    if name.trim().eq_ignore_ascii_case("off") {
+        return Some(None);
+    }
+    return Level::parse(name).map(Some);
+}
+
+/// One captured log message
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Record {
+    pub level: Level,
+    pub target: String,
+    pub message: String
+}
+
+const UNINITIALIZED: u8 = u8::MAX;
+
+static FILTER: RwLock<Filter> = RwLock::new(Filter::new(Some(Level::Warn)));
+/// Cheap pre-check so disabled calls in hot loops don't need to take the lock
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(UNINITIALIZED);
+
+thread_local! {
+    static CAPTURE: RefCell<Option<(Level, Vec<Record>)>> = const { RefCell::new(None) };
+}
+
+fn level_to_u8(level: Option<Level>) -> u8 {
+    return level.map_or(0, |l| l as u8);
+}
+
+/// Replace the active filter
+pub fn set_filter(filter: Filter) {
+    let max = level_to_u8(filter.max_level());
+    *FILTER.write().unwrap() = filter;
+    MAX_LEVEL.store(max, Ordering::Relaxed);
+}
+
+/// Configure logging from [ENV_VAR] and the process arguments. Later sources win:
+/// the environment is applied first, then `-q`/`-v...`, then `--log=<spec>`.
+pub fn init() {
+    let mut filter = filter_from_env();
+    for arg in env::args().skip(1) {
+        if arg == "-q" || arg == "--quiet" {
+            filter.default = Some(Level::Error);
+        } else if arg.len() > 1 && arg.starts_with('-') && arg[1..].chars().all(|c| c == 'v') {
+            filter.default = Some(match arg.len() - 1 {
+                1 => Level::Info,
+                2 => Level::Debug,
+                _ => Level::Trace
+            });
+        } else if let Some(spec) = arg.strip_prefix("--log=") {
+            filter = Filter::parse(spec);
+        }
+    }
+    set_filter(filter);
+}
+
+fn filter_from_env() -> Filter {
+    return match env::var(ENV_VAR) {
+        Ok(spec) => Filter::parse(&spec),
+        Err(_) => Filter::new(Some(Level::Warn))
+    };
+}
+
+/// Whether a message at `level` for `target` would go anywhere
+pub fn enabled(level: Level, target: &str) -> bool {
+    let captured = CAPTURE.with(|c| c.borrow().as_ref().map(|(max, _)| level <= *max));
+    if let Some(captured) = captured {
+        return captured;
+    }
+
+    let mut max = MAX_LEVEL.load(Ordering::Relaxed);
+    if max == UNINITIALIZED {
+        set_filter(filter_from_env());
+        max = MAX_LEVEL.load(Ordering::Relaxed);
+    }
+    if level as u8 > max {
+        return false;
+    }
+    return FILTER.read().unwrap().level_for(target).is_some_and(|l| level <= l);
+}
+
+/// Emit a message. Prefer the [crate::info!] style macros, which skip formatting when disabled.
+pub fn log(level: Level, target: &str, args: fmt::Arguments) {
+    let unhandled = CAPTURE.with(|c| {
+        if let Some((_, records)) = c.borrow_mut().as_mut() {
+            records.push(Record { level, target: target.to_owned(), message: args.to_string() });
+            return false;
+        }
+        return true;
+    });
+    if unhandled {
+        eprintln!("[{:<5} {}] {}", level, target, args);
+    }
+}
+
+/// Run `f`, collecting every message up to `level` logged on the current thread instead of
+/// printing it. The global filter is ignored while capturing.
+///
+/// Only the calling thread is captured: messages from other threads, such as the workers of
+/// [crate::parallel], are printed (or filtered) as usual and don't show up in the result.
+pub fn capture<R>(level: Level, f: impl FnOnce() -> R) -> (R, Vec<Record>) {
+    let previous = CAPTURE.with(|c| c.borrow_mut().replace((level, vec![])));
+    let result = f();
+    let (_, records) = CAPTURE.with(|c| c.replace(previous)).unwrap();
+    return (result, records);
+}
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {
+        if $crate::log::enabled($level, module_path!()) {
+            $crate::log::log($level, module_path!(), format_args!($($arg)+));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! log_enabled {
+    ($level:expr) => {
+        $crate::log::enabled($level, module_path!())
+    };
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Error, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Warn, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Info, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Debug, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => { $crate::log!($crate::log::Level::Trace, $($arg)+) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_parsing() {
+        let filter = Filter::parse("info,day20=trace,day22=off");
+        assert_eq!(Some(Level::Info), filter.level_for("day01"));
+        assert_eq!(Some(Level::Trace), filter.level_for("day20"));
+        assert_eq!(Some(Level::Trace), filter.level_for("day20::layout"));
+        assert_eq!(Some(Level::Info), filter.level_for("day200"));
+        assert_eq!(None, filter.level_for("day22"));
+        assert_eq!(Some(Level::Trace), filter.max_level());
+
+        assert_eq!(Some(Level::Warn), Filter::parse("").level_for("day05"));
+    }
+
+    #[test]
+    fn capturing() {
+        let (value, records) = capture(Level::Debug, || {
+            crate::info!("answer is {}", 42);
+            crate::trace!("too chatty");
+            crate::debug!("details");
+            7
+        });
+        assert_eq!(7, value);
+        assert_eq!(vec![
+            Record { level: Level::Info, target: "utils::log::tests".to_owned(), message: "answer is 42".to_owned() },
+            Record { level: Level::Debug, target: "utils::log::tests".to_owned(), message: "details".to_owned() },
+        ], records);
+    }
+}
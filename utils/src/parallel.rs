@@ -0,0 +1,65 @@
+//! Small helpers for running independent work on std threads.
+//!
+//! Work is handed out one item at a time to a fixed number of scoped worker threads, and every
+//! result is stored at the index of the item it came from. The output therefore never depends on
+//! scheduling, only on the input order. The thread count comes from the `AOC_THREADS` environment
+//! variable when set, and from [std::thread::available_parallelism] otherwise.
+
+use std::{env, num::NonZeroUsize, sync::{Mutex, atomic::{AtomicUsize, Ordering}}, thread};
+
+/// Environment variable overriding the number of worker threads
+pub const ENV_VAR: &str = "AOC_THREADS";
+
+/// Number of worker threads to use, always at least 1
+pub fn threads() -> usize {
+    if let Some(count) = env::var(ENV_VAR).ok().and_then(|v| v.trim().parse::<usize>().ok()) {
+        return count.max(1);
+    }
+    return thread::available_parallelism().map(NonZeroUsize::get).unwrap_or(1);
+}
+
+/// Apply `f` to every item on [threads] worker threads, returning the results in input order
+pub fn map<T, R, F>(items: &[T], f: F) -> Vec<R> where T: Sync, R: Send, F: Fn(&T) -> R + Sync {
+    return map_with(threads(), items, f);
+}
+
+/// [map] with an explicit thread count. A count of 1 (or a single item) runs on the calling thread.
+pub fn map_with<T, R, F>(threads: usize, items: &[T], f: F) -> Vec<R> where T: Sync, R: Send, F: Fn(&T) -> R + Sync {
+    let threads = threads.clamp(1, items.len().max(1));
+    if threads == 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new((0..items.len()).map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    if idx >= items.len() {
+                        break;
+                    }
+                    let result = f(&items[idx]);
+                    results.lock().unwrap()[idx] = Some(result);
+                }
+            });
+        }
+    });
+    return results.into_inner().unwrap().into_iter().map(|r| r.expect("Worker skipped an item")).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_input_order() {
+        let items: Vec<u64> = (0..500).collect();
+        let expected: Vec<u64> = items.iter().map(|i| i * i).collect();
+        for threads in [1, 2, 7, 64] {
+            assert_eq!(expected, map_with(threads, &items, |i| i * i));
+        }
+        assert_eq!(Vec::<u64>::new(), map_with(4, &[], |i: &u64| *i));
+    }
+}
@@ -0,0 +1,319 @@
+//! Location-aware parsing helpers.
+//!
+//! A [Span] is a piece of the input that remembers which line and column it started at, so that
+//! anything carved out of it (by [Span::lines], [Span::split], [Span::split_once], ...) can report
+//! a [ParseError] pointing at the exact spot that did not look as expected.
+
+use std::{collections::HashMap, error::Error, fmt::{self, Display, Formatter}, str::FromStr};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseError {
+    /// file the input came from, if known (see [ParseError::in_file])
+    pub file: Option<String>,
+    /// 1-based
+    pub line: usize,
+    /// 1-based, in characters
+    pub column: usize,
+    pub expected: String,
+    pub found: String
+}
+impl ParseError {
+    pub fn in_file(self, file: &str) -> ParseError {
+        return ParseError { file: Some(file.to_owned()), ..self };
+    }
+}
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let file = self.file.as_deref().unwrap_or("<input>");
+        return write!(f, "{}:{}:{}: expected {}, found {}", file, self.line, self.column, self.expected, self.found);
+    }
+}
+impl Error for ParseError {}
+
+pub type ParseResult<T> = Result<T, ParseError>;
+
+pub trait ParseResultExt<T> {
+    /// Attach a file name to the error, if any
+    fn in_file(self, file: &str) -> ParseResult<T>;
+    /// Like [Result::expect], but the panic message is the located error for `file`
+    fn expect_in(self, file: &str) -> T;
+}
+impl <T>ParseResultExt<T> for ParseResult<T> {
+    fn in_file(self, file: &str) -> ParseResult<T> {
+        return self.map_err(|e| e.in_file(file));
+    }
+
+    fn expect_in(self, file: &str) -> T {
+        match self.in_file(file) {
+            Ok(v) => v,
+            Err(e) => panic!("{}", e)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Span<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize
+}
+impl <'a>Span<'a> {
+    pub fn new(text: &'a str) -> Span<'a> {
+        return Span { text, line: 1, column: 1 };
+    }
+
+    pub fn as_str(&self) -> &'a str {
+        return self.text;
+    }
+
+    pub fn line(&self) -> usize {
+        return self.line;
+    }
+
+    pub fn column(&self) -> usize {
+        return self.column;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.text.is_empty();
+    }
+
+    /// Sub-span of the byte range `start..end`
+    fn slice(&self, start: usize, end: usize) -> Span<'a> {
+        let skipped = &self.text[..start];
+        let (line, column) = match skipped.rfind('\n') {
+            Some(idx) => (self.line + skipped.matches('\n').count(), skipped[idx+1..].chars().count() + 1),
+            None => (self.line, self.column + skipped.chars().count())
+        };
+        return Span { text: &self.text[start..end], line, column };
+    }
+
+    /// Error located at the start of this span
+    pub fn error(&self, expected: impl Into<String>) -> ParseError {
+        let first_line = self.text.split('\n').next().unwrap_or("").trim_end_matches('\r');
+        let found = if self.text.is_empty() {
+            "end of input".to_owned()
+        } else if first_line.is_empty() {
+            "end of line".to_owned()
+        } else if first_line.chars().count() > 24 {
+            format!("`{}...`", first_line.chars().take(24).collect::<String>())
+        } else {
+            format!("`{}`", first_line)
+        };
+        return ParseError { file: None, line: self.line, column: self.column, expected: expected.into(), found };
+    }
+
+    pub fn trim(&self) -> Span<'a> {
+        let start = self.text.len() - self.text.trim_start().len();
+        let end = self.text.trim_end().len().max(start);
+        return self.slice(start, end);
+    }
+
+    /// Lines of the span, without their line endings
+    pub fn lines(&self) -> impl Iterator<Item = Span<'a>> + 'a {
+        let this = *self;
+        return this.split("\n").map(|l| match l.text.strip_suffix('\r') {
+            Some(stripped) => Span { text: stripped, ..l },
+            None => l
+        });
+    }
+
+    pub fn split(&self, separator: &'a str) -> impl Iterator<Item = Span<'a>> + 'a {
+        let this = *self;
+        let mut start = 0;
+        return this.text.split(separator).map(move |part| {
+            let span = this.slice(start, start + part.len());
+            start += part.len() + separator.len();
+            return span;
+        });
+    }
+
+    pub fn split_whitespace(&self) -> impl Iterator<Item = Span<'a>> + 'a {
+        let this = *self;
+        return this.text.split_whitespace().map(move |part| {
+            let start = part.as_ptr() as usize - this.text.as_ptr() as usize;
+            return this.slice(start, start + part.len());
+        });
+    }
+
+    pub fn split_once(&self, separator: &str) -> ParseResult<(Span<'a>, Span<'a>)> {
+        return match self.text.find(separator) {
+            Some(idx) => Ok((self.slice(0, idx), self.slice(idx + separator.len(), self.text.len()))),
+            None => Err(self.error(format!("`{}`", separator)))
+        };
+    }
+
+    pub fn strip_prefix(&self, prefix: &str) -> ParseResult<Span<'a>> {
+        if self.text.starts_with(prefix) {
+            return Ok(self.slice(prefix.len(), self.text.len()));
+        }
+        return Err(self.error(format!("`{}`", prefix)));
+    }
+
+    pub fn strip_suffix(&self, suffix: &str) -> ParseResult<Span<'a>> {
+        if self.text.ends_with(suffix) {
+            return Ok(self.slice(0, self.text.len() - suffix.len()));
+        }
+        let at = self.text.len().saturating_sub(suffix.len());
+        let at = (0..=at).rev().find(|i| self.text.is_char_boundary(*i)).unwrap_or(0);
+        return Err(self.slice(at, self.text.len()).error(format!("`{}`", suffix)));
+    }
+
+    /// Parse the whole (trimmed) span with [FromStr]
+    pub fn parse<T: FromStr>(&self) -> ParseResult<T> {
+        let trimmed = self.trim();
+        return trimmed.text.parse::<T>().map_err(|_| trimmed.error(format!("a value of type `{}`", short_type_name::<T>())));
+    }
+
+    /// Parse the whole span with `f`, reporting `expected` when it returns `None`
+    pub fn parse_with<T>(&self, expected: &str, f: impl FnOnce(&'a str) -> Option<T>) -> ParseResult<T> {
+        return f(self.text).ok_or_else(|| self.error(expected));
+    }
+
+    /// The span must consist of exactly one character
+    pub fn single_char(&self) -> ParseResult<char> {
+        let mut chars = self.text.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(self.error("a single character"))
+        };
+    }
+
+    /// Decode every character with `decode`, pointing at the first one it rejects
+    pub fn decode_chars<T>(&self, expected: &str, decode: impl Fn(char) -> Option<T>) -> ParseResult<Vec<T>> {
+        let mut out = vec![];
+        for (idx, chr) in self.text.char_indices() {
+            match decode(chr) {
+                Some(v) => out.push(v),
+                None => return Err(self.slice(idx, idx + chr.len_utf8()).error(expected))
+            }
+        }
+        return Ok(out);
+    }
+
+    /// Parse each piece between `separator`s with `item`
+    pub fn separated<T>(&self, separator: &'a str, mut item: impl FnMut(Span<'a>) -> ParseResult<T>) -> ParseResult<Vec<T>> {
+        return self.split(separator).map(|s| item(s)).collect();
+    }
+
+    /// Parse each non-blank line with `item`
+    pub fn parse_lines<T>(&self, mut item: impl FnMut(Span<'a>) -> ParseResult<T>) -> ParseResult<Vec<T>> {
+        return self.lines().filter(|l| !l.text.trim().is_empty()).map(|l| item(l)).collect();
+    }
+
+    /// List of numbers between `separator`s. A separator of `" "` accepts any run of whitespace.
+    pub fn numbers<T: FromStr>(&self, separator: &'a str) -> ParseResult<Vec<T>> {
+        if separator == " " {
+            return self.split_whitespace().map(|s| s.parse()).collect();
+        }
+        return self.trim().separated(separator, |s| s.parse());
+    }
+
+    /// Rectangular grid of characters, one row per non-blank line
+    pub fn grid<T>(&self, expected: &str, decode: impl Fn(char) -> Option<T>) -> ParseResult<Vec<Vec<T>>> {
+        let mut rows: Vec<Vec<T>> = vec![];
+        for line in self.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            let row = line.decode_chars(expected, &decode)?;
+            if let Some(first) = rows.first() {
+                if row.len() != first.len() {
+                    return Err(line.error(format!("a row of {} tiles", first.len())));
+                }
+            }
+            rows.push(row);
+        }
+        if rows.is_empty() {
+            return Err(self.error("at least one row"));
+        }
+        return Ok(rows);
+    }
+
+    /// `key=value` style pairs separated by `separator`
+    pub fn key_values(&self, separator: &'a str, assign: &str) -> ParseResult<KeyValues<'a>> {
+        let mut entries: Vec<(Span<'a>, Span<'a>)> = vec![];
+        for entry in self.split(separator) {
+            let (key, value) = entry.trim().split_once(assign)?;
+            if entries.iter().any(|(k, _)| k.text == key.text) {
+                return Err(key.error("a key that was not already given"));
+            }
+            entries.push((key, value));
+        }
+        return Ok(KeyValues { span: *self, entries });
+    }
+}
+impl <'a>From<&'a str> for Span<'a> {
+    fn from(text: &'a str) -> Span<'a> {
+        return Span::new(text);
+    }
+}
+impl Display for Span<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        return f.write_str(self.text);
+    }
+}
+
+/// Result of [Span::key_values]
+#[derive(Clone, Debug)]
+pub struct KeyValues<'a> {
+    span: Span<'a>,
+    entries: Vec<(Span<'a>, Span<'a>)>
+}
+impl <'a>KeyValues<'a> {
+    pub fn get(&self, key: &str) -> ParseResult<Span<'a>> {
+        return self.entries.iter()
+            .find(|(k, _)| k.text == key)
+            .map(|(_, v)| *v)
+            .ok_or_else(|| self.span.error(format!("key `{}`", key)));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Span<'a>, Span<'a>)> + '_ {
+        return self.entries.iter().copied();
+    }
+
+    pub fn to_map(&self) -> HashMap<&'a str, Span<'a>> {
+        return self.entries.iter().map(|(k, v)| (k.text, *v)).collect();
+    }
+}
+
+fn short_type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    return name.rsplit("::").next().unwrap_or(name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locations() {
+        let input = Span::new("\n  first line\nsecond: 1, 2, x\n");
+        let lines: Vec<Span> = input.trim().lines().collect();
+        assert_eq!(2, lines.len());
+        assert_eq!((2, 3), (lines[0].line(), lines[0].column()));
+
+        let (label, rest) = lines[1].split_once(": ").unwrap();
+        assert_eq!("second", label.as_str());
+        let err = rest.numbers::<u32>(",").unwrap_err();
+        assert_eq!(ParseError { file: None, line: 3, column: 15, expected: "a value of type `u32`".to_owned(), found: "`x`".to_owned() }, err);
+        assert_eq!("day01/input.txt:3:15: expected a value of type `u32`, found `x`", err.in_file("day01/input.txt").to_string());
+    }
+
+    #[test]
+    fn combinators() {
+        assert_eq!(vec![3_u8, 41, 5], Span::new(" 3  41 5 ").numbers(" ").unwrap());
+
+        let pairs = Span::new("x=787,m=2655").key_values(",", "=").unwrap();
+        assert_eq!(2655_usize, pairs.get("m").unwrap().parse().unwrap());
+        assert_eq!(1, pairs.get("s").unwrap_err().column);
+        assert!(Span::new("x=1,x=2").key_values(",", "=").is_err());
+
+        let err = Span::new("ab\n.c#").grid("a grid tile", |c| if c == '#' { None } else { Some(c) }).unwrap_err();
+        assert_eq!((2, 3), (err.line, err.column));
+        let err = Span::new("abc\n  ab\n").grid("a letter", Some).unwrap_err();
+        assert_eq!((2, 3, "a row of 3 tiles"), (err.line, err.column, err.expected.as_str()));
+
+        let err = Span::new("Game 4").strip_prefix("Card ").unwrap_err();
+        assert_eq!("`Card `", err.expected);
+        let err = Span::new("abc").strip_suffix(")").unwrap_err();
+        assert_eq!((1, 3, "`c`"), (err.line, err.column, err.found.as_str()));
+    }
+}
@@ -0,0 +1,193 @@
+//! Polygons with integer corners, for counting grid cells enclosed by a loop.
+//!
+//! Corners are `(x, y)` with y growing downwards, like rows of the puzzle input. A polygon is
+//! closed implicitly: the last corner connects back to the first.
+//!
+//! Everything is exact: the shoelace formula gives twice the area as an integer, and Pick's
+//! theorem (`area = interior + boundary / 2 - 1`) turns that into lattice point counts.
+
+pub type Point = (i64, i64);
+
+/// A step along the grid, with y growing downwards
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right
+}
+impl Direction {
+    pub fn offset(&self, (x, y): Point, dist: i64) -> Point {
+        return match self {
+            Direction::Up => (x, y - dist),
+            Direction::Down => (x, y + dist),
+            Direction::Left => (x - dist, y),
+            Direction::Right => (x + dist, y)
+        };
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Polygon {
+    pub vertices: Vec<Point>
+}
+impl Polygon {
+    pub fn new(vertices: Vec<Point>) -> Polygon {
+        return Polygon { vertices };
+    }
+
+    /// Follow the moves from the origin. If they end back at the origin, that last corner isn't
+    /// repeated; otherwise the polygon closes with a straight (possibly diagonal) edge.
+    pub fn from_moves(moves: impl IntoIterator<Item = (Direction, i64)>) -> Polygon {
+        let mut vertices: Vec<Point> = vec![(0, 0)];
+        for (dir, dist) in moves {
+            vertices.push(dir.offset(*vertices.last().unwrap(), dist));
+        }
+        if vertices.len() > 1 && vertices.last() == vertices.first() {
+            vertices.pop();
+        }
+        return Polygon { vertices };
+    }
+
+    /// Every edge as (from, to), including the closing one
+    pub fn edges(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        let n = self.vertices.len();
+        return (0..n).map(move |i| (self.vertices[i], self.vertices[(i + 1) % n]));
+    }
+
+    /// Whether every edge is horizontal or vertical
+    pub fn is_rectilinear(&self) -> bool {
+        return self.edges().all(|((x1, y1), (x2, y2))| x1 == x2 || y1 == y2);
+    }
+
+    /// Twice the signed area (shoelace formula). Positive when the corners go clockwise on
+    /// screen, since y grows downwards.
+    pub fn signed_double_area(&self) -> i64 {
+        return self.edges().map(|((x1, y1), (x2, y2))| x1 * y2 - x2 * y1).sum();
+    }
+
+    /// Area enclosed by the edges, through the middle of the boundary cells
+    pub fn area(&self) -> f64 {
+        return self.signed_double_area().unsigned_abs() as f64 / 2.0;
+    }
+
+    /// Euclidean length of the edges
+    pub fn perimeter(&self) -> f64 {
+        return self.edges().map(|((x1, y1), (x2, y2))| (((x2 - x1).pow(2) + (y2 - y1).pow(2)) as f64).sqrt()).sum();
+    }
+
+    /// Lattice points on the edges; the same as the perimeter for rectilinear polygons
+    pub fn boundary_points(&self) -> u64 {
+        return self.edges().map(|((x1, y1), (x2, y2))| gcd((x2 - x1).unsigned_abs(), (y2 - y1).unsigned_abs())).sum();
+    }
+
+    /// Lattice points strictly inside, by Pick's theorem
+    pub fn interior_points(&self) -> u64 {
+        return (self.signed_double_area().unsigned_abs() + 2).saturating_sub(self.boundary_points()) / 2;
+    }
+
+    /// Lattice points inside or on the edges: the number of cells covered when every corner is
+    /// the centre of a cell
+    pub fn area_with_boundary(&self) -> u64 {
+        return self.interior_points() + self.boundary_points();
+    }
+
+    /// Indices of two edges that touch or cross without being neighbours, or neighbouring edges
+    /// that double back over each other. Edge `i` runs from corner `i` to corner `i + 1`.
+    pub fn self_intersection(&self) -> Option<(usize, usize)> {
+        let edges: Vec<(Point, Point)> = self.edges().collect();
+        let n = edges.len();
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let (a, b) = (edges[i], edges[j]);
+                let neighbours = j == i + 1 || (i == 0 && j == n - 1);
+                if neighbours {
+                    // they share a corner; only a problem if they fold back onto each other
+                    let (ax, ay) = (a.1.0 - a.0.0, a.1.1 - a.0.1);
+                    let (bx, by) = (b.1.0 - b.0.0, b.1.1 - b.0.1);
+                    if n > 2 && ax * by - ay * bx == 0 && ax * bx + ay * by < 0 {
+                        return Some((i, j));
+                    }
+                } else if segments_touch(a, b) {
+                    return Some((i, j));
+                }
+            }
+        }
+        return None;
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    return if b == 0 { a } else { gcd(b, a % b) };
+}
+
+/// Sign of the turn from `a -> b` to `a -> c`
+fn orientation(a: Point, b: Point, c: Point) -> i128 {
+    let cross = (b.0 - a.0) as i128 * (c.1 - a.1) as i128 - (b.1 - a.1) as i128 * (c.0 - a.0) as i128;
+    return cross.signum();
+}
+
+/// Whether `p`, known to be in line with `segment`, lies on it
+fn on_segment(p: Point, (a, b): (Point, Point)) -> bool {
+    return a.0.min(b.0) <= p.0 && p.0 <= a.0.max(b.0) && a.1.min(b.1) <= p.1 && p.1 <= a.1.max(b.1);
+}
+
+fn segments_touch(s: (Point, Point), t: (Point, Point)) -> bool {
+    let (o1, o2) = (orientation(s.0, s.1, t.0), orientation(s.0, s.1, t.1));
+    let (o3, o4) = (orientation(t.0, t.1, s.0), orientation(t.0, t.1, s.1));
+    if o1 != o2 && o3 != o4 {
+        return true;
+    }
+    return (o1 == 0 && on_segment(t.0, s)) || (o2 == 0 && on_segment(t.1, s))
+        || (o3 == 0 && on_segment(s.0, t)) || (o4 == 0 && on_segment(s.1, t));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lagoon_counts() {
+        use Direction::*;
+        // 3x2 rectangle of corners: 4x3 cells dug out
+        let rectangle = Polygon::from_moves([(Right, 3), (Down, 2), (Left, 3), (Up, 2)]);
+        assert_eq!(4, rectangle.vertices.len());
+        assert!(rectangle.is_rectilinear());
+        assert_eq!(12, rectangle.signed_double_area());
+        assert_eq!(6.0, rectangle.area());
+        assert_eq!(10.0, rectangle.perimeter());
+        assert_eq!(10, rectangle.boundary_points());
+        assert_eq!(2, rectangle.interior_points());
+        assert_eq!(12, rectangle.area_with_boundary());
+        assert_eq!(None, rectangle.self_intersection());
+
+        // the other way round only flips the sign
+        let reversed = Polygon::from_moves([(Down, 2), (Right, 3), (Up, 2), (Left, 3)]);
+        assert_eq!(-12, reversed.signed_double_area());
+        assert_eq!(12, reversed.area_with_boundary());
+    }
+
+    #[test]
+    fn general_polygons() {
+        let triangle = Polygon::new(vec![(0, 0), (4, 0), (0, 4)]);
+        assert!(!triangle.is_rectilinear());
+        assert_eq!(8.0, triangle.area());
+        assert_eq!(12, triangle.boundary_points());
+        assert_eq!(3, triangle.interior_points());
+        assert_eq!(None, triangle.self_intersection());
+    }
+
+    #[test]
+    fn self_intersections() {
+        use Direction::*;
+        // a figure of eight crosses in the middle
+        let bowtie = Polygon::new(vec![(0, 0), (2, 2), (2, 0), (0, 2)]);
+        assert_eq!(Some((0, 2)), bowtie.self_intersection());
+        // two loops touching at a corner
+        let touching = Polygon::from_moves([(Right, 1), (Down, 1), (Right, 1), (Down, 1), (Left, 1), (Up, 1), (Left, 1), (Up, 1)]);
+        assert!(touching.self_intersection().is_some());
+        // going straight back over the last edge
+        let folded = Polygon::from_moves([(Right, 2), (Down, 2), (Up, 1), (Left, 2)]);
+        assert_eq!(Some((1, 2)), folded.self_intersection());
+    }
+}
@@ -0,0 +1,387 @@
+//! Terminal step-through viewer for grid simulations.
+//!
+//! Anything implementing [Step] and [Render] (and [Clone], so earlier steps can be revisited) can
+//! be handed to [Viewer::run], which takes over the terminal in raw mode and redraws the state
+//! after every step. Keys:
+//!
+//! - `space`: run / pause
+//! - `→` or `l`: one step forward, `←` or `h`: one step back
+//! - `+` / `-`: run faster / slower
+//! - `g`: jump to a step number (type it, then `enter`)
+//! - `q` or `esc`: quit
+//!
+//! Stepping back replays nothing: every state visited is kept, so it's meant for puzzle sized
+//! simulations rather than millions of steps. Nothing is simulated past [MAX_STEPS].
+
+use std::{io::{self, Read, Write}, time::{Duration, Instant}};
+
+/// A simulation that can move forward one step at a time
+pub trait Step {
+    /// Advance by one step; true once there is nothing left to do, in which case the state is
+    /// left as it was
+    fn step(&mut self) -> bool;
+}
+
+/// A simulation that can draw itself
+pub trait Render {
+    /// The picture, one line per row (colours are fine)
+    fn render(&self) -> String;
+
+    /// One line of counts shown under the picture, such as energized tiles or load
+    fn status(&self) -> String;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+    Char(char),
+    Left,
+    Right,
+    Up,
+    Down,
+    Enter,
+    Backspace,
+    Escape
+}
+
+/// Decode the bytes read from a raw-mode terminal into key presses
+pub fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = vec![];
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let key = match bytes[idx] {
+            0x1b if bytes.get(idx + 1) == Some(&b'[') && idx + 2 < bytes.len() => {
+                idx += 2;
+                match bytes[idx] {
+                    b'A' => Some(Key::Up),
+                    b'B' => Some(Key::Down),
+                    b'C' => Some(Key::Right),
+                    b'D' => Some(Key::Left),
+                    _ => None
+                }
+            },
+            // ctrl-c quits too, since signals are off in raw mode
+            0x1b | 0x03 => Some(Key::Escape),
+            b'\r' | b'\n' => Some(Key::Enter),
+            0x7f | 0x08 => Some(Key::Backspace),
+            b if b.is_ascii() && !b.is_ascii_control() => Some(Key::Char(b as char)),
+            _ => None
+        };
+        keys.extend(key);
+        idx += 1;
+    }
+    return keys;
+}
+
+/// The viewer stops simulating after this many steps, so jumping far into a simulation that never
+/// finishes can't use up all the memory
+pub const MAX_STEPS: usize = 10_000;
+
+const MIN_DELAY: Duration = Duration::from_millis(1);
+const MAX_DELAY: Duration = Duration::from_secs(5);
+
+pub struct Viewer<S: Step + Render + Clone> {
+    /// `history[n]` is the state after `n` steps
+    history: Vec<S>,
+    current: usize,
+    /// number of steps after which [Step::step] said it was done
+    last: Option<usize>,
+    running: bool,
+    delay: Duration,
+    /// digits typed so far after `g`
+    jump: Option<String>
+}
+impl <S: Step + Render + Clone>Viewer<S> {
+    pub fn new(initial: S) -> Viewer<S> {
+        return Viewer { history: vec![initial], current: 0, last: None, running: false, delay: Duration::from_millis(200), jump: None };
+    }
+
+    pub fn current(&self) -> &S {
+        return &self.history[self.current];
+    }
+
+    /// How many steps the current state is from the start
+    pub fn step_number(&self) -> usize {
+        return self.current;
+    }
+
+    pub fn is_running(&self) -> bool {
+        return self.running;
+    }
+
+    pub fn delay(&self) -> Duration {
+        return self.delay;
+    }
+
+    /// Move one step forward, simulating if this step hasn't been seen yet.
+    /// Returns false at the end of the simulation, or at [MAX_STEPS].
+    pub fn forward(&mut self) -> bool {
+        if self.current + 1 < self.history.len() {
+            self.current += 1;
+            return true;
+        }
+        if self.last.is_some() || self.current >= MAX_STEPS {
+            return false;
+        }
+        let mut next = self.history[self.current].clone();
+        if next.step() {
+            self.last = Some(self.current);
+            return false;
+        }
+        self.history.push(next);
+        self.current += 1;
+        return true;
+    }
+
+    /// Returns false at the start
+    pub fn back(&mut self) -> bool {
+        if self.current == 0 {
+            return false;
+        }
+        self.current -= 1;
+        return true;
+    }
+
+    /// Go to step `target`, or as close as the simulation (or [MAX_STEPS]) gets
+    pub fn jump_to(&mut self, target: usize) {
+        if target < self.history.len() {
+            self.current = target;
+            return;
+        }
+        self.current = self.history.len() - 1;
+        while self.current < target && self.forward() {}
+    }
+
+    /// React to a key press; false means quit
+    pub fn handle(&mut self, key: Key) -> bool {
+        if let Some(digits) = &mut self.jump {
+            match key {
+                Key::Char(c) if c.is_ascii_digit() => digits.push(c),
+                Key::Backspace => { digits.pop(); },
+                Key::Enter => {
+                    let target = digits.parse().ok();
+                    self.jump = None;
+                    if let Some(target) = target {
+                        self.running = false;
+                        self.jump_to(target);
+                    }
+                },
+                Key::Escape => self.jump = None,
+                _ => {}
+            }
+            return true;
+        }
+
+        match key {
+            Key::Char('q') | Key::Escape => return false,
+            Key::Char(' ') => self.running = !self.running,
+            Key::Right | Key::Char('l') => {
+                self.running = false;
+                self.forward();
+            },
+            Key::Left | Key::Char('h') => {
+                self.running = false;
+                self.back();
+            },
+            Key::Char('+') | Key::Char('=') | Key::Up => self.delay = (self.delay / 2).max(MIN_DELAY),
+            Key::Char('-') | Key::Down => self.delay = (self.delay * 2).min(MAX_DELAY),
+            Key::Char('g') => self.jump = Some(String::new()),
+            _ => {}
+        }
+        return true;
+    }
+
+    /// One step of playback when running; pauses at the end
+    pub fn tick(&mut self) {
+        if self.running && !self.forward() {
+            self.running = false;
+        }
+    }
+
+    /// The whole screen: picture, status line and a line of help or the jump prompt
+    pub fn screen(&self) -> String {
+        let state = self.current();
+        let mut out = state.render();
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+        let done = if self.last == Some(self.current) {
+            " (done)"
+        } else if self.current == MAX_STEPS {
+            " (step limit)"
+        } else {
+            ""
+        };
+        let playing = if self.running { "running" } else { "paused" };
+        out += &format!("step {}{} | {} | {} every {}ms\n", self.current, done, state.status(), playing, self.delay.as_millis());
+        match &self.jump {
+            Some(digits) => out += &format!("jump to step: {}", digits),
+            None => out += "space: run/pause  ←/→: step  +/-: speed  g: jump  q: quit"
+        }
+        return out;
+    }
+
+    /// Take over the terminal until the user quits
+    pub fn run(mut self) -> io::Result<()> {
+        let _raw = RawMode::enable()?;
+        let mut stdout = io::stdout();
+        let mut stdin = io::stdin();
+        let mut buffer = [0_u8; 64];
+        let mut last_tick = Instant::now();
+        loop {
+            write!(stdout, "\x1b[H\x1b[2J{}", self.screen())?;
+            stdout.flush()?;
+
+            let timeout = if self.running { self.delay.saturating_sub(last_tick.elapsed()) } else { Duration::from_secs(60) };
+            if wait_for_input(timeout)? {
+                let count = stdin.read(&mut buffer)?;
+                if !parse_keys(&buffer[..count]).into_iter().all(|key| self.handle(key)) {
+                    break;
+                }
+            }
+            if self.running && last_tick.elapsed() >= self.delay {
+                self.tick();
+                last_tick = Instant::now();
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Whether stdin has something to read within `timeout`
+fn wait_for_input(timeout: Duration) -> io::Result<bool> {
+    let mut fd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+    let millis = timeout.as_millis().min(i32::MAX as u128) as i32;
+    let ready = unsafe { libc::poll(&mut fd, 1, millis) };
+    if ready < 0 {
+        let error = io::Error::last_os_error();
+        // a resize or similar; just redraw
+        return if error.kind() == io::ErrorKind::Interrupted { Ok(false) } else { Err(error) };
+    }
+    return Ok(ready > 0);
+}
+
+/// Keys arrive one at a time without echo, on the alternate screen with the cursor hidden, while
+/// this is alive. Everything is put back on drop, however the viewer exits.
+struct RawMode {
+    original: libc::termios
+}
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) != 1 {
+                return Err(io::Error::new(io::ErrorKind::Other, "the viewer needs a terminal on stdin"));
+            }
+            let mut original: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = original;
+            // output processing stays on, so `\n` still goes back to the start of the line
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+            raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+            raw.c_cc[libc::VMIN] = 0;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mode = RawMode { original };
+            // alternate screen, hide the cursor
+            let mut stdout = io::stdout();
+            write!(stdout, "\x1b[?1049h\x1b[?25l")?;
+            stdout.flush()?;
+            return Ok(mode);
+        }
+    }
+}
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts up to a limit
+    #[derive(Clone)]
+    struct Counter {
+        value: usize,
+        limit: usize
+    }
+    impl Step for Counter {
+        fn step(&mut self) -> bool {
+            if self.value == self.limit {
+                return true;
+            }
+            self.value += 1;
+            return false;
+        }
+    }
+    impl Render for Counter {
+        fn render(&self) -> String {
+            return "#".repeat(self.value);
+        }
+
+        fn status(&self) -> String {
+            return format!("value: {}", self.value);
+        }
+    }
+
+    #[test]
+    fn key_parsing() {
+        assert_eq!(vec![Key::Right, Key::Char('q'), Key::Left, Key::Escape, Key::Enter, Key::Backspace],
+            parse_keys(b"\x1b[Cq\x1b[D\x1b\r\x7f"));
+        assert_eq!(vec![Key::Escape], parse_keys(&[0x03]));
+    }
+
+    #[test]
+    fn stepping() {
+        let mut viewer = Viewer::new(Counter { value: 0, limit: 5 });
+        assert!(!viewer.back());
+        assert!(viewer.handle(Key::Right));
+        assert!(viewer.handle(Key::Char('l')));
+        assert_eq!(2, viewer.current().value);
+        viewer.handle(Key::Left);
+        assert_eq!(1, viewer.step_number());
+
+        // running goes to the end and stops there
+        viewer.handle(Key::Char(' '));
+        assert!(viewer.is_running());
+        for _ in 0..10 {
+            viewer.tick();
+        }
+        assert!(!viewer.is_running());
+        assert_eq!(5, viewer.current().value);
+        assert!(viewer.screen().contains("step 5 (done) | value: 5 | paused"));
+
+        // jumping, both to a seen step and past the end
+        for key in [Key::Char('g'), Key::Char('3'), Key::Char('9'), Key::Backspace, Key::Enter] {
+            viewer.handle(key);
+        }
+        assert_eq!(3, viewer.step_number());
+        viewer.handle(Key::Char('g'));
+        assert!(viewer.screen().ends_with("jump to step: "));
+        for key in [Key::Char('9'), Key::Enter] {
+            viewer.handle(key);
+        }
+        assert_eq!(5, viewer.step_number());
+
+        // a simulation that never ends stops at the limit
+        let mut endless = Viewer::new(Counter { value: 0, limit: usize::MAX });
+        endless.jump_to(usize::MAX);
+        assert_eq!(MAX_STEPS, endless.step_number());
+        assert!(!endless.forward());
+        assert!(endless.screen().contains("step 10000 (step limit) |"));
+
+        let delay = viewer.delay();
+        viewer.handle(Key::Char('+'));
+        assert_eq!(delay / 2, viewer.delay());
+        assert!(!viewer.handle(Key::Char('q')));
+    }
+}